@@ -0,0 +1,24 @@
+precision mediump float;
+
+uniform sampler2D u_source;
+uniform vec2 u_source_size;
+uniform vec2 u_target_size;
+
+in vec2 v_uv;
+
+out vec3 color;
+
+const float PI = 3.14159265358979;
+const float SCANLINE_DEPTH = 0.3;
+const float VIGNETTE_STRENGTH = 0.5;
+
+void main() {
+    // One scanline per row of the source, unless rows are too thin to show them.
+    float rows = min(u_source_size.y, u_target_size.y * 0.5);
+    float scanline = 1.0 - SCANLINE_DEPTH * (1.0 - sin(fract(v_uv.y * rows) * PI));
+
+    vec2 from_centre = v_uv - 0.5;
+    float vignette = 1.0 - VIGNETTE_STRENGTH * dot(from_centre, from_centre);
+
+    color = texture(u_source, v_uv).rgb * scanline * vignette;
+}
@@ -0,0 +1,7 @@
+out vec2 v_uv;
+
+// A single triangle covering the screen, with no vertex attributes.
+void main() {
+    v_uv = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,34 @@
+precision mediump float;
+
+out vec3 color;
+
+uniform float u_debug_view;
+
+flat in float v_light;
+flat in int v_leaf;
+flat in int v_object;
+flat in int v_trigger;
+
+const float LIGHT_SCALE = 2.0;
+
+// A distinct, light colour for each id, by stepping the hue by the golden ratio.
+vec3 id_color(int id) {
+    float hue = fract(float(id) * 0.618034);
+    vec3 rgb = clamp(abs(mod(hue * 6.0 + vec3(0.0, 4.0, 2.0), 6.0) - 3.0) - 1.0, 0.0, 1.0);
+    return mix(vec3(1.0), rgb, 0.7) * 0.9;
+}
+
+void main() {
+    float light = clamp(v_light * LIGHT_SCALE, 0.0, 1.0);
+    if (u_debug_view < 1.5) {  // Leaves.
+        color = id_color(v_leaf);
+    } else if (u_debug_view < 2.5) {  // Light.
+        color = vec3(light);
+    } else if (v_trigger != 0) {  // Triggers.
+        color = vec3(1.0, 1.0, 0.0);
+    } else if (v_object != 0) {
+        color = id_color(v_object);
+    } else {
+        color = vec3(0.25 + 0.25 * light);
+    }
+}
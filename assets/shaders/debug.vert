@@ -0,0 +1,22 @@
+uniform samplerBuffer u_lights;
+uniform mat4 u_projection;
+uniform mat4 u_modelview;
+
+in vec3 a_pos;
+in int a_light;
+in int a_debug_leaf;
+in int a_debug_object;
+in int a_debug_trigger;
+
+flat out float v_light;
+flat out int v_leaf;
+flat out int v_object;
+flat out int v_trigger;
+
+void main() {
+    v_light = texelFetch(u_lights, a_light).r;
+    v_leaf = a_debug_leaf;
+    v_object = a_debug_object;
+    v_trigger = a_debug_trigger;
+    gl_Position = u_projection * u_modelview * vec4(a_pos, 1);
+}
@@ -0,0 +1,9 @@
+precision mediump float;
+
+out vec3 color;
+
+in vec3 v_color;
+
+void main() {
+    color = v_color;
+}
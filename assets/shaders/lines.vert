@@ -0,0 +1,11 @@
+uniform mat4 u_transform;
+
+in vec3 a_pos;
+in vec3 a_color;
+
+out vec3 v_color;
+
+void main() {
+    v_color = a_color;
+    gl_Position = u_transform * vec4(a_pos, 1);
+}
@@ -3,12 +3,16 @@ precision mediump float;
 out vec3 color;
 
 uniform sampler2D u_palette;
+uniform float u_colormap_mode;
 uniform sampler2D u_texture;
 uniform float u_tiled_band_size;
 
 flat in vec2 v_r;
 in vec4 v_p;
 
+// The unlit colormap is the first row of `u_palette`, the inverse one the last of 33.
+const float INVERSE_V = 32.5 / 33.0;
+
 void main() {
     vec2 uv = vec2(v_p.x, v_p.y) / v_p.w * vec2(1, -1);
     uv = vec2(uv.x - 4.0 * v_r.x / 3.14159265358, uv.y + 1.0 + v_r.y);
@@ -22,5 +26,5 @@ void main() {
         uv.y = 1.0 - uv.y;
     }
     float palette_index = texture(u_texture, uv).r;
-    color = texture(u_palette, vec2(palette_index, 0.0)).rgb;
+    color = texture(u_palette, vec2(palette_index, u_colormap_mode > 1.5 ? INVERSE_V : 0.0)).rgb;
 }
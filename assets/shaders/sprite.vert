@@ -6,21 +6,43 @@ uniform samplerBuffer u_lights;
 
 in vec3 a_pos;
 in vec2 a_atlas_uv;
+in int a_atlas_page;
 in vec2 a_tile_uv;
 in vec2 a_tile_size;
 in float a_local_x;
+in float a_yaw;
 in int a_num_frames;
+in int a_rotation;
 in int a_light;
+in int a_fullbright;
 
 out float v_dist;
 out vec2 v_tile_uv;
 flat out vec2 v_atlas_uv;
+flat out int v_atlas_page;
 flat out vec2 v_tile_size;
 flat out float v_light;
 
 const float ANIM_FPS = 8.0 / 35.0;
+const float PI = 3.14159265358979;
+
+// Picks one of the eight rotations (1 to 8) based on the angle between the thing's facing and the
+// direction it's viewed from, like vanilla's `R_ProjectSprite`.
+int view_rotation() {
+    mat3 rotation = mat3(u_modelview);
+    vec3 camera = -(transpose(rotation) * u_modelview[3].xyz);
+    vec2 to_thing = a_pos.xz - camera.xz;
+    float relative = a_yaw - atan(to_thing.y, to_thing.x) + PI * (9.0 / 8.0);
+    return int(floor(mod(relative, 2.0 * PI) / (PI / 4.0))) % 8 + 1;
+}
 
 void main() {
+    if (a_rotation != 0 && a_rotation != view_rotation()) {
+        // Collapse the quads of all other rotations to a single point outside the clip volume.
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        return;
+    }
+
     v_tile_uv = a_tile_uv;
     if (a_num_frames == 1) {
       v_atlas_uv = a_atlas_uv;
@@ -35,13 +57,13 @@ void main() {
         float atlas_v = a_atlas_uv.y + n_rows_down * a_tile_size.y;
         v_atlas_uv = vec2(atlas_u, atlas_v);
     }
+    v_atlas_page = a_atlas_page;
     v_tile_size = a_tile_size;
 
     vec3 right = vec3(u_modelview[0][0], u_modelview[1][0], u_modelview[2][0]);
     vec3 pos = a_pos + right * a_local_x;
     vec4 projected_pos = u_projection * (u_modelview * vec4(pos, 1.0));
-    v_light = texelFetch(u_lights, a_light).r;
+    v_light = a_fullbright != 0 ? 1.0 : texelFetch(u_lights, a_light).r;
     v_dist = projected_pos.w;
     gl_Position = projected_pos;
-
 }
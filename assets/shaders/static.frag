@@ -3,26 +3,45 @@ precision mediump float;
 out vec3 color;
 
 uniform vec2 u_atlas_size;
-uniform sampler2D u_atlas;
+uniform sampler2DArray u_atlas;
 uniform sampler2D u_palette;
+uniform float u_colormap_mode;
 
 in float v_dist;
 in vec2 v_tile_uv;
 flat in vec2 v_atlas_uv;
+flat in int v_atlas_page;
 flat in vec2 v_tile_size;
 flat in float v_light;
 
 const float DIST_SCALE = 0.9;
 const float LIGHT_SCALE = 2.0;
 
+// `u_palette` has a row per light level, then the inverse colormap.
+const float NUM_LIGHT_COLORMAPS = 32.0;
+const float NUM_PALETTE_ROWS = 33.0;
+
+// The `u_palette` row for a light level, following `u_colormap_mode`.
+float colormap_v(float light) {
+    float row;
+    if (u_colormap_mode > 1.5) {  // Inverse.
+        row = NUM_LIGHT_COLORMAPS;
+    } else if (u_colormap_mode > 0.5) {  // Fullbright.
+        row = 0.0;
+    } else {
+        row = clamp(floor((1.0 - light) * NUM_LIGHT_COLORMAPS), 0.0, NUM_LIGHT_COLORMAPS - 1.0);
+    }
+    return (row + 0.5) / NUM_PALETTE_ROWS;
+}
+
 void main() {
     vec2 uv = mod(v_tile_uv, v_tile_size) + v_atlas_uv;
-    vec2 palette_index = texture(u_atlas, uv / u_atlas_size).rg;
+    vec2 palette_index = texture(u_atlas, vec3(uv / u_atlas_size, float(v_atlas_page))).rg;
     if (palette_index.g > .5) {  // Transparent pixel.
         discard;
     } else {
         float dist_term = min(1.0, 1.0 - DIST_SCALE / (v_dist + DIST_SCALE));
         float light = v_light * LIGHT_SCALE - dist_term;
-        color = texture(u_palette, vec2(palette_index.r, 1.0 - light)).rgb;
+        color = texture(u_palette, vec2(palette_index.r, colormap_v(light))).rgb;
     }
 }
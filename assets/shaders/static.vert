@@ -7,6 +7,7 @@ uniform float u_time;
 
 in vec3 a_pos;
 in vec2 a_atlas_uv;
+in int a_atlas_page;
 in vec2 a_tile_uv;
 in vec2 a_tile_size;
 in float a_scroll_rate;
@@ -17,6 +18,7 @@ in int a_light;
 out float v_dist;
 out vec2 v_tile_uv;
 flat out vec2 v_atlas_uv;
+flat out int v_atlas_page;
 flat out vec2 v_tile_size;
 flat out float v_light;
 
@@ -37,6 +39,7 @@ void main() {
         float atlas_v = a_atlas_uv.y + n_rows_down * a_row_height;
         v_atlas_uv = vec2(atlas_u, atlas_v);
     }
+    v_atlas_page = a_atlas_page;
     v_tile_size = a_tile_size;
     vec4 projected_pos = u_projection * u_modelview * vec4(a_pos, 1);
     v_dist = projected_pos.w;
@@ -0,0 +1,17 @@
+precision mediump float;
+
+out vec3 color;
+
+uniform sampler2D u_palette;
+uniform sampler2D u_texture;
+
+in vec2 v_uv;
+
+void main() {
+    vec2 palette_index = texture(u_texture, v_uv).rg;
+    if (palette_index.g > .5) {  // Transparent pixel.
+        discard;
+    } else {
+        color = texture(u_palette, vec2(palette_index.r, 0.0)).rgb;
+    }
+}
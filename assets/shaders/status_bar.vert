@@ -0,0 +1,11 @@
+uniform mat4 u_transform;
+
+in vec3 a_pos;
+in vec2 a_uv;
+
+out vec2 v_uv;
+
+void main() {
+    v_uv = a_uv;
+    gl_Position = u_transform * vec4(a_pos, 1);
+}
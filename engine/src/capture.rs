@@ -0,0 +1,195 @@
+use super::errors::{ErrorKind, Result};
+use super::system::InfallibleSystem;
+use super::tick::Tick;
+use super::window::Window;
+use crate::internal_derive::DependenciesFrom;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct Config {
+    /// Where screenshots and recordings are saved; created if missing.
+    pub directory: PathBuf,
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    config: &'context Config,
+    tick: &'context mut Tick,
+}
+
+/// Saves rendered frames to PNG files, on request from any system.
+///
+/// Frames are read back by the renderer right after they are drawn, so a request made during a
+/// tick captures the frame rendered at the end of that tick.
+pub struct Capture {
+    directory: PathBuf,
+    screenshot_requested: bool,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    directory: PathBuf,
+    next_frame: u32,
+}
+
+impl Capture {
+    /// Saves the next rendered frame as a timestamped PNG file.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Starts or stops saving every tick's frame as a numbered PNG file, in a new timestamped
+    /// directory. While recording, no frames are skipped: the simulation slows down instead.
+    pub fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+        } else {
+            let directory = self.timestamped_path("recording", None);
+            self.start_recording_to(directory);
+        }
+    }
+
+    /// Like `toggle_recording`, but records to `directory` (numbering frames from 0), replacing
+    /// any recording in progress.
+    pub fn start_recording_to(&mut self, directory: PathBuf) {
+        self.stop_recording();
+        info!("Recording frames to `{}`...", directory.display());
+        self.recording = Some(Recording {
+            directory,
+            next_frame: 0,
+        });
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            info!(
+                "Stopped recording, saved {} frames to `{}`.",
+                recording.next_frame,
+                recording.directory.display()
+            );
+        }
+    }
+
+    /// A new path in the capture directory, named `<prefix>-<timestamp>` plus `.<extension>` if
+    /// one is given. The directory may not exist yet.
+    pub fn timestamped_path(&self, prefix: &str, extension: Option<&str>) -> PathBuf {
+        let mut name = format!("{}-{}", prefix, timestamp());
+        if let Some(extension) = extension {
+            name.push('.');
+            name.push_str(extension);
+        }
+        self.directory.join(name)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Called by the renderer after every rendered frame.
+    pub(crate) fn frame_rendered(&mut self, window: &Window) {
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let path = self.timestamped_path("screenshot", Some("png"));
+            match save_frame(window, &path) {
+                Ok(()) => info!("Saved screenshot `{}`.", path.display()),
+                Err(e) => error!("Failed to save screenshot: {}", e),
+            }
+        }
+
+        if let Some(recording) = self.recording.as_mut() {
+            let path = recording
+                .directory
+                .join(format!("frame-{:06}.png", recording.next_frame));
+            if let Err(e) = save_frame(window, &path) {
+                error!("Failed to record frame, stopping: {}", e);
+                self.recording = None;
+            } else {
+                recording.next_frame += 1;
+            }
+        }
+    }
+}
+
+impl<'context> InfallibleSystem<'context> for Capture {
+    type Dependencies = Dependencies<'context>;
+
+    fn debug_name() -> &'static str {
+        "capture"
+    }
+
+    fn create(deps: Dependencies) -> Self {
+        Capture {
+            directory: deps.config.directory.clone(),
+            screenshot_requested: false,
+            recording: None,
+        }
+    }
+
+    fn update(&mut self, deps: Dependencies) {
+        deps.tick.set_lockstep(self.recording.is_some());
+    }
+}
+
+fn save_frame(window: &Window, path: &Path) -> Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|e| {
+            ErrorKind::Capture(format!("Cannot create `{}`: {}", directory.display(), e))
+        })?;
+    }
+    window.read_frame()?.save_png(path)
+}
+
+/// The current UTC time as `YYYY-MM-DD_HH-MM-SS.mmm`, which sorts chronologically.
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Converts days since 1970-01-01 to a `(year, month, day)` date in the proleptic Gregorian
+/// calendar (see http://howardhinnant.github.io/date_algorithms.html#civil_from_days).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::civil_from_days;
+
+    #[test]
+    fn civil_from_days_matches_known_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
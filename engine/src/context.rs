@@ -1,4 +1,4 @@
-use super::errors::{ErrorKind, Result};
+use super::errors::{Error, ErrorKind, Result};
 use super::input::Input;
 use super::system::{BoundSystem, System};
 use super::type_list::{Cons, Nil, Peek, Pluck, PluckInto};
@@ -6,7 +6,7 @@ use super::window::Window;
 use failchain::ResultExt;
 use failure::{AsFail, Fail};
 use glium::glutin::event_loop::ControlFlow as GlutinControlFlow;
-use std::{marker::PhantomData, time::Instant};
+use std::{marker::PhantomData, process, thread, time::Instant};
 
 pub trait Context {
     fn step(&mut self) -> Result<()>;
@@ -165,7 +165,35 @@ where
     fn run(mut self) -> ! {
         let event_loop = {
             let window: &mut Window = self.systems_mut().peek_mut();
-            window.take_event_loop().expect("none event loop in window")
+            window.take_event_loop()
+        };
+        let event_loop = if let Some(event_loop) = event_loop {
+            event_loop
+        } else {
+            // Without a window (software backend) there are no events to wait for, so just step
+            // until a quit is requested.
+            loop {
+                let result = self.step().and_then(|_| {
+                    let input: &mut Input = self.systems_mut().peek_mut();
+                    input.reset();
+                    let control_flow: &mut ControlFlow = self.systems_mut().peek_mut();
+                    if let Some(sleep_until) = control_flow.sleep_until.take() {
+                        thread::sleep(sleep_until.saturating_duration_since(Instant::now()));
+                    }
+                    if !control_flow.quit_requested {
+                        return Ok(false);
+                    }
+                    self.destroy().map(|_| true)
+                });
+                match result {
+                    Ok(false) => {}
+                    Ok(true) => process::exit(0),
+                    Err(error) => {
+                        log_fatal_error(&error);
+                        process::exit(1);
+                    }
+                }
+            }
         };
 
         event_loop.run(move |event, _target, glutin_control_flow| {
@@ -192,20 +220,7 @@ where
             });
 
             if let Err(error) = result {
-                log::error!("Fatal error: {}", error);
-                let mut cause = error.as_fail();
-                while let Some(new_cause) = cause.cause() {
-                    cause = new_cause;
-                    log::error!("    caused by: {}", cause);
-                }
-                if std::env::var("RUST_BACKTRACE")
-                    .map(|value| value == "1")
-                    .unwrap_or(false)
-                {
-                    log::error!("Backtrace:\n{:?}", error.backtrace());
-                } else {
-                    log::error!("Run with RUST_BACKTRACE=1 to capture backtrace.");
-                }
+                log_fatal_error(&error);
                 *glutin_control_flow = GlutinControlFlow::Exit;
             }
         })
@@ -225,6 +240,23 @@ where
     }
 }
 
+fn log_fatal_error(error: &Error) {
+    log::error!("Fatal error: {}", error);
+    let mut cause = error.as_fail();
+    while let Some(new_cause) = cause.cause() {
+        cause = new_cause;
+        log::error!("    caused by: {}", cause);
+    }
+    if std::env::var("RUST_BACKTRACE")
+        .map(|value| value == "1")
+        .unwrap_or(false)
+    {
+        log::error!("Backtrace:\n{:?}", error.backtrace());
+    } else {
+        log::error!("Run with RUST_BACKTRACE=1 to capture backtrace.");
+    }
+}
+
 pub trait DependenciesFrom<ContextT, IndicesT>: Sized {
     fn dependencies_from(context: ContextT) -> Self;
 }
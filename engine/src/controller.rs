@@ -0,0 +1,144 @@
+use serde::Deserialize;
+
+/// A gamepad button, named after its position on the pad rather than its label, which differs
+/// between manufacturers: `South` is A on Xbox pads and cross on PlayStation ones.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum PadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum PadStick {
+    Left,
+    Right,
+}
+
+/// A change in the state of a gamepad. All connected pads are treated as one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PadEvent {
+    ButtonPressed(PadButton),
+    ButtonReleased(PadButton),
+    /// The new position of a stick, with both coordinates in `-1..1` and `y` positive upwards.
+    StickMoved(PadStick, f32, f32),
+}
+
+/// Where `Input` gets gamepad events from, polled once per update.
+///
+/// `NoControllers` ignores gamepads entirely; with the `gamepad` feature the default source reads
+/// real devices. Tests can feed synthetic events with any other implementation.
+pub trait ControllerSource {
+    /// The next pending event, or `None` if there are no more this update.
+    fn next_event(&mut self) -> Option<PadEvent>;
+}
+
+pub struct NoControllers;
+
+impl ControllerSource for NoControllers {
+    fn next_event(&mut self) -> Option<PadEvent> {
+        None
+    }
+}
+
+/// The best source available: real gamepads with the `gamepad` feature, otherwise none.
+pub fn default_source() -> Box<dyn ControllerSource> {
+    #[cfg(feature = "gamepad")]
+    {
+        match gilrs_source::GilrsSource::new() {
+            Ok(source) => return Box::new(source),
+            Err(error) => log::warn!("Gamepads unavailable: {}", error),
+        }
+    }
+    Box::new(NoControllers)
+}
+
+#[cfg(feature = "gamepad")]
+mod gilrs_source {
+    use super::{ControllerSource, PadButton, PadEvent, PadStick};
+    use gilrs::{Axis, Button, EventType, Gilrs};
+
+    pub struct GilrsSource {
+        gilrs: Gilrs,
+        sticks: [[f32; 2]; 2],
+    }
+
+    impl GilrsSource {
+        pub fn new() -> Result<Self, String> {
+            Ok(GilrsSource {
+                gilrs: Gilrs::new().map_err(|error| error.to_string())?,
+                sticks: [[0.0; 2]; 2],
+            })
+        }
+    }
+
+    impl ControllerSource for GilrsSource {
+        fn next_event(&mut self) -> Option<PadEvent> {
+            while let Some(event) = self.gilrs.next_event() {
+                let event = match event.event {
+                    EventType::ButtonPressed(button, _) => {
+                        convert_button(button).map(PadEvent::ButtonPressed)
+                    }
+                    EventType::ButtonReleased(button, _) => {
+                        convert_button(button).map(PadEvent::ButtonReleased)
+                    }
+                    EventType::AxisChanged(axis, value, _) => {
+                        let (stick, coordinate) = match axis {
+                            Axis::LeftStickX => (PadStick::Left, 0),
+                            Axis::LeftStickY => (PadStick::Left, 1),
+                            Axis::RightStickX => (PadStick::Right, 0),
+                            Axis::RightStickY => (PadStick::Right, 1),
+                            _ => continue,
+                        };
+                        let position = &mut self.sticks[stick as usize];
+                        position[coordinate] = value;
+                        Some(PadEvent::StickMoved(stick, position[0], position[1]))
+                    }
+                    _ => None,
+                };
+                if event.is_some() {
+                    return event;
+                }
+            }
+            None
+        }
+    }
+
+    fn convert_button(button: Button) -> Option<PadButton> {
+        Some(match button {
+            Button::South => PadButton::South,
+            Button::East => PadButton::East,
+            Button::North => PadButton::North,
+            Button::West => PadButton::West,
+            // gilrs calls the bumpers triggers, and the triggers second triggers.
+            Button::LeftTrigger => PadButton::LeftBumper,
+            Button::RightTrigger => PadButton::RightBumper,
+            Button::LeftTrigger2 => PadButton::LeftTrigger,
+            Button::RightTrigger2 => PadButton::RightTrigger,
+            Button::Select => PadButton::Select,
+            Button::Start => PadButton::Start,
+            Button::Mode => PadButton::Mode,
+            Button::LeftThumb => PadButton::LeftThumb,
+            Button::RightThumb => PadButton::RightThumb,
+            Button::DPadUp => PadButton::DPadUp,
+            Button::DPadDown => PadButton::DPadDown,
+            Button::DPadLeft => PadButton::DPadLeft,
+            Button::DPadRight => PadButton::DPadRight,
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use math::{Aabb3, Frustum};
+use std::ops::Range;
+
+/// A bounding volume hierarchy over the index buffer of a model, used by the renderer to only draw
+/// the index ranges whose bounds intersect the view frustum.
+///
+/// The tree is built depth first (e.g. by walking a BSP) with `begin_node` and `end_node`, passing
+/// in the number of indices pushed so far. Each node covers the indices added between its begin and
+/// end; nodes without children are leaves. Parents grow to contain their children's bounds.
+#[derive(Default)]
+pub struct CullingTree {
+    nodes: Vec<CullingNode>,
+    open: Vec<usize>,
+}
+
+impl CullingTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_node(&mut self, bounds: Aabb3, index: usize) {
+        self.open.push(self.nodes.len());
+        self.nodes.push(CullingNode {
+            bounds,
+            start: index as u32,
+            end: index as u32,
+            next: 0,
+            num_leaves: 0,
+        });
+    }
+
+    /// Grows the bounds of the node begun last to contain `bounds`, e.g. of the geometry actually
+    /// added to it.
+    pub fn include(&mut self, bounds: &Aabb3) {
+        let &node_index = self
+            .open
+            .last()
+            .expect("CullingTree::include outside of a node");
+        let node = &mut self.nodes[node_index];
+        node.bounds = node.bounds.union(bounds);
+    }
+
+    pub fn end_node(&mut self, index: usize) {
+        let node_index = self.open.pop().expect("unbalanced CullingTree::end_node");
+        let next = self.nodes.len() as u32;
+        let node = &mut self.nodes[node_index];
+        node.end = index as u32;
+        node.next = next;
+        if node.next as usize == node_index + 1 {
+            node.num_leaves = 1;
+        }
+        let (num_leaves, bounds) = (node.num_leaves, node.bounds);
+        if let Some(&parent) = self.open.last() {
+            let parent = &mut self.nodes[parent];
+            parent.num_leaves += num_leaves;
+            parent.bounds = parent.bounds.union(&bounds);
+        }
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.nodes
+            .first()
+            .map_or(0, |root| root.num_leaves as usize)
+    }
+
+    /// Appends the index ranges visible in `frustum` (merging adjacent ones) and returns the number
+    /// of culled leaves. Indices outside the tree (`num_indices` is the length of the buffer) are
+    /// always visible.
+    pub(crate) fn visible_ranges(
+        &self,
+        frustum: &Frustum,
+        num_indices: usize,
+        ranges: &mut Vec<Range<usize>>,
+    ) -> usize {
+        let mut push = |range: Range<usize>| {
+            if range.start >= range.end {
+                return;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        };
+
+        let (tree_start, tree_end) = self
+            .nodes
+            .first()
+            .map_or((num_indices, num_indices), |root| {
+                (root.start as usize, root.end as usize)
+            });
+        push(0..tree_start);
+
+        let mut num_culled = 0;
+        let mut index = 0;
+        while let Some(node) = self.nodes.get(index) {
+            if !frustum.intersects_aabb(&node.bounds) {
+                num_culled += node.num_leaves as usize;
+                index = node.next as usize;
+                continue;
+            }
+            if node.next as usize == index + 1 {
+                push(node.start as usize..node.end as usize);
+            }
+            index += 1;
+        }
+
+        push(tree_end..num_indices);
+        num_culled
+    }
+}
+
+struct CullingNode {
+    bounds: Aabb3,
+    start: u32,
+    end: u32,
+
+    // The index of the first node after this node's subtree.
+    next: u32,
+    num_leaves: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::CullingTree;
+    use math::{perspective, Aabb3, Deg, Frustum, Pnt3f};
+
+    #[test]
+    fn visible_ranges() {
+        let unit_at = |z: f32| {
+            Aabb3::new(
+                Pnt3f::new(-0.5, -0.5, z - 0.5),
+                Pnt3f::new(0.5, 0.5, z + 0.5),
+            )
+        };
+        let mut tree = CullingTree::new();
+        tree.begin_node(unit_at(-10.0).union(&unit_at(10.0)), 3);
+        tree.begin_node(unit_at(-10.0), 3);
+        tree.end_node(9);
+        tree.begin_node(unit_at(10.0), 9);
+        tree.end_node(12);
+        tree.begin_node(unit_at(-20.0), 12);
+        tree.end_node(15);
+        tree.end_node(15);
+        assert_eq!(tree.num_leaves(), 3);
+
+        // Looking down -Z, the middle leaf is behind the camera.
+        let frustum = Frustum::from_matrix(&perspective(Deg(90.0), 1.0, 0.1, 100.0));
+        let mut ranges = Vec::new();
+        assert_eq!(tree.visible_ranges(&frustum, 18, &mut ranges), 1);
+        assert_eq!(ranges, vec![0..9, 12..18]);
+    }
+
+    #[test]
+    fn included_bounds_grow_ancestors() {
+        let unit_at = |z: f32| {
+            Aabb3::new(
+                Pnt3f::new(-0.5, -0.5, z - 0.5),
+                Pnt3f::new(0.5, 0.5, z + 0.5),
+            )
+        };
+        // The leaf's geometry sticks out of its (and its parent's) bounds, in front of the camera.
+        let mut tree = CullingTree::new();
+        tree.begin_node(unit_at(10.0), 0);
+        tree.begin_node(unit_at(10.0), 0);
+        tree.include(&unit_at(-10.0));
+        tree.end_node(6);
+        tree.end_node(6);
+
+        let frustum = Frustum::from_matrix(&perspective(Deg(90.0), 1.0, 0.1, 100.0));
+        let mut ranges = Vec::new();
+        assert_eq!(tree.visible_ranges(&frustum, 6, &mut ranges), 0);
+        assert_eq!(ranges, vec![0..6]);
+    }
+}
@@ -52,6 +52,21 @@ pub enum ErrorKind {
         id: Id<()>,
     },
 
+    #[fail(
+        display = "Mesh drawn by `{}` was created for the other render backend.",
+        needed_by
+    )]
+    MeshBackendMismatch { needed_by: &'static str },
+
+    #[fail(display = "Software renderer: {}", 0)]
+    SoftwareRenderer(String),
+
+    #[fail(display = "Frame capture: {}", 0)]
+    Capture(String),
+
+    #[fail(display = "Input recording: {}", 0)]
+    InputRecording(String),
+
     #[fail(display = "Context {} error", 0)]
     Context(&'static str),
 
@@ -98,6 +113,8 @@ impl<T: UnsupportedFeature> ConvertGlium for T {
 impl UnsupportedFeature for glium::vertex::BufferCreationError {}
 impl UnsupportedFeature for glium::index::BufferCreationError {}
 impl UnsupportedFeature for glium::texture::TextureCreationError {}
+impl UnsupportedFeature for glium::framebuffer::RenderBufferCreationError {}
+impl UnsupportedFeature for glium::framebuffer::ValidationError {}
 
 impl ConvertGlium for glium::texture::buffer_texture::CreationError {
     fn convert_glium(self, needed_by: String) -> ErrorKind {
@@ -11,6 +11,10 @@ use std::time::{Duration, Instant};
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct FrameTimerId(Id<FrameTimer>);
 
+/// A handle for a frame counter, returned by `FrameTimers`.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct FrameCounterId(Id<FrameCounter>);
+
 impl FrameTimers {
     /// Creates a new frame timer, returning its id.
     ///
@@ -67,6 +71,34 @@ impl FrameTimers {
         })
     }
 
+    /// Creates a new frame counter, returning its id.
+    ///
+    /// Counters track a per-frame quantity (like the number of objects drawn) and their average and
+    /// maximum are logged in the periodic summary, alongside timers.
+    pub fn new_counter<S: Into<Cow<'static, str>>>(&mut self, debug_name: S) -> FrameCounterId {
+        FrameCounterId(self.counters.insert(FrameCounter {
+            debug_name: debug_name.into(),
+            total_since_logged: 0.0,
+            max_since_logged: 0,
+            times_since_logged: 0.0,
+        }))
+    }
+
+    /// Removes a counter, given its id.
+    pub fn remove_counter(&mut self, counter_id: FrameCounterId) {
+        self.counters
+            .remove(counter_id.0)
+            .expect("Invalid counter id.");
+    }
+
+    /// Records the value of a counter for the current frame.
+    pub fn record(&mut self, counter_id: FrameCounterId, value: u64) {
+        let counter = &mut self.counters[counter_id.0];
+        counter.total_since_logged += value as f64;
+        counter.max_since_logged = counter.max_since_logged.max(value);
+        counter.times_since_logged += 1.0;
+    }
+
     /// Queries a frame timer and returns the elapsed time in seconds.
     ///
     /// Querying a stopped timer will return `None`.
@@ -110,6 +142,27 @@ impl FrameTimers {
                 seconds_since_logged / times_since_logged * 1000.
             );
         }
+        for &mut FrameCounter {
+            ref debug_name,
+            ref mut total_since_logged,
+            ref mut max_since_logged,
+            ref mut times_since_logged,
+        } in &mut self.counters
+        {
+            let total_since_logged = mem::replace(total_since_logged, 0.0);
+            let max_since_logged = mem::replace(max_since_logged, 0);
+            let times_since_logged = mem::replace(times_since_logged, 0.0);
+            if times_since_logged == 0.0 {
+                continue;
+            }
+            let _ = write!(
+                &mut self.log_buffer,
+                "\n\t{}\tavg {:.1} (max {})",
+                debug_name,
+                total_since_logged / times_since_logged,
+                max_since_logged
+            );
+        }
         info!("Frame timer summary:{}", self.log_buffer);
         info!(
             "Drift summary: n={}, min={:.2}ms mean={:.2}ms max={:.2}ms",
@@ -145,6 +198,7 @@ impl FrameTimers {
 /// Periodically, a summary of all the timer averages is printed to the `info` log.
 pub struct FrameTimers {
     timers: IdSlab<FrameTimer>,
+    counters: IdSlab<FrameCounter>,
     last_logged: Option<Instant>,
     log_buffer: String,
 
@@ -172,6 +226,7 @@ impl<'context> InfallibleSystem<'context> for FrameTimers {
     fn create(_: &Tick) -> Self {
         let mut this = Self {
             timers: IdSlab::with_capacity(16),
+            counters: IdSlab::with_capacity(16),
             last_logged: None,
             log_buffer: String::with_capacity(512),
 
@@ -231,3 +286,11 @@ struct FrameTimer {
     seconds_since_logged: f32,
     times_since_logged: f32,
 }
+
+struct FrameCounter {
+    debug_name: Cow<'static, str>,
+
+    total_since_logged: f64,
+    max_since_logged: u64,
+    times_since_logged: f64,
+}
@@ -1,34 +1,56 @@
-use super::errors::{Error, Result};
+use super::controller::{self, ControllerSource, PadButton, PadEvent, PadStick};
+use super::errors::{Error, ErrorKind, Result};
+use super::input_recording::{InputEvent, InputReplay, InputWriter};
 use super::system::System;
+use super::tick::{Tick, TickIndex};
 use super::window::Window;
 use crate::internal_derive::DependenciesFrom;
+use failchain::bail;
 use glium::glutin::event::{
     DeviceEvent, ElementState, Event, KeyboardInput, StartCause, VirtualKeyCode, WindowEvent,
 };
+use log::{error, info};
+use math::prelude::*;
 use math::Vec2f;
 use num_traits::Zero;
+use serde::Deserialize;
+use std::path::PathBuf;
 use std::vec::Vec;
 
 pub use glium::glutin::event::{MouseButton, VirtualKeyCode as Scancode};
 
 pub type Sensitivity = f32;
 
+/// A condition on keys and buttons, polled with `Input::poll_gesture`.
+///
+/// Bindings files spell these in snake case with keys and buttons by name, e.g.
+/// `{ all_of = [{ key_hold = "LControl" }, { key_trigger = "N" }] }`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Gesture {
     NoGesture,
     KeyHold(VirtualKeyCode),
     KeyTrigger(VirtualKeyCode),
     ButtonHold(MouseButton),
     ButtonTrigger(MouseButton),
+    PadHold(PadButton),
+    PadTrigger(PadButton),
     AnyOf(Vec<Gesture>),
     AllOf(Vec<Gesture>),
     QuitTrigger,
 }
 
+/// A 2d motion polled with `Input::poll_analog2d`, deserialized like `Gesture`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Analog2d {
     NoAnalog2d,
 
     Mouse {
         sensitivity: Sensitivity,
+        /// Moving the mouse forward looks down rather than up.
+        #[serde(default)]
+        invert_y: bool,
     },
 
     Gestures {
@@ -36,14 +58,54 @@ pub enum Analog2d {
         x_negative: Gesture,
         y_positive: Gesture,
         y_negative: Gesture,
+        #[serde(default = "default_step")]
         step: Sensitivity,
     },
 
+    /// A gamepad stick, ignoring positions within `deadzone` of the centre. Like the mouse, `y`
+    /// is positive downwards.
+    PadStick {
+        stick: PadStick,
+        #[serde(default = "default_deadzone")]
+        deadzone: f32,
+        sensitivity: Sensitivity,
+    },
+
     Sum {
         analogs: Vec<Analog2d>,
     },
 }
 
+impl Analog2d {
+    /// Overrides the sensitivity and y-inversion of any `Mouse` in this analog, leaving the other
+    /// settings alone where `None`.
+    pub fn configure_mouse(&mut self, sensitivity: Option<Sensitivity>, invert_y: Option<bool>) {
+        match *self {
+            Analog2d::Mouse {
+                sensitivity: ref mut current_sensitivity,
+                invert_y: ref mut current_invert_y,
+            } => {
+                *current_sensitivity = sensitivity.unwrap_or(*current_sensitivity);
+                *current_invert_y = invert_y.unwrap_or(*current_invert_y);
+            }
+            Analog2d::Sum { ref mut analogs } => {
+                for analog in analogs {
+                    analog.configure_mouse(sensitivity, invert_y);
+                }
+            }
+            Analog2d::Gestures { .. } | Analog2d::PadStick { .. } | Analog2d::NoAnalog2d => {}
+        }
+    }
+}
+
+fn default_step() -> Sensitivity {
+    1.0
+}
+
+fn default_deadzone() -> f32 {
+    0.2
+}
+
 impl Input {
     pub(crate) fn reset(&mut self) {
         self.current_update_index += 1;
@@ -70,6 +132,13 @@ impl Input {
             } => {
                 self.quit_requested_index = self.current_update_index;
             }
+            // Minimised windows report a zero size, which has no sensible aspect ratio.
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } if size.width > 0 && size.height > 0 => {
+                self.new_size = Some((size.width, size.height));
+            }
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
@@ -82,37 +151,66 @@ impl Input {
                         ..
                     },
                 ..
-            } => {
-                self.keyboard_state[virtual_keycode as usize] = match state {
-                    ElementState::Pressed => ButtonState::Down(self.current_update_index),
-                    ElementState::Released => ButtonState::Up(self.current_update_index),
-                }
+            } if self.replay.is_none() => {
+                self.apply_event(match state {
+                    ElementState::Pressed => InputEvent::KeyDown(virtual_keycode),
+                    ElementState::Released => InputEvent::KeyUp(virtual_keycode),
+                });
             }
             Event::DeviceEvent {
                 event: DeviceEvent::Motion { axis, value },
                 ..
-            } => {
-                if self.mouse_enabled && axis < 2 {
-                    self.mouse_rel[axis as usize] += value as f32;
-                }
+            } if self.replay.is_none() && self.mouse_enabled && axis < 2 => {
+                self.mouse_rel[axis as usize] += value as f32;
             }
             Event::DeviceEvent {
                 event: DeviceEvent::Button { button, state },
                 ..
-            } => {
-                let button = button as usize;
-                if self.mouse_enabled && button < NUM_MOUSE_BUTTONS {
-                    self.mouse_button_state[button] = match state {
-                        ElementState::Pressed => ButtonState::Down(self.current_update_index),
-                        ElementState::Released => ButtonState::Up(self.current_update_index),
-                    }
-                }
+            } if self.replay.is_none()
+                && self.mouse_enabled
+                && (button as usize) < NUM_MOUSE_BUTTONS =>
+            {
+                self.apply_event(match state {
+                    ElementState::Pressed => InputEvent::ButtonDown(button),
+                    ElementState::Released => InputEvent::ButtonUp(button),
+                });
             }
             _ => {}
         }
         false
     }
 
+    /// Changes the input state for the current update, noting the change if recording.
+    fn apply_event(&mut self, event: InputEvent) {
+        let index = self.current_update_index;
+        match event {
+            InputEvent::KeyDown(key) => {
+                self.keyboard_state[key as usize] = ButtonState::Down(index)
+            }
+            InputEvent::KeyUp(key) => self.keyboard_state[key as usize] = ButtonState::Up(index),
+            InputEvent::ButtonDown(button) => {
+                self.mouse_button_state[button as usize] = ButtonState::Down(index)
+            }
+            InputEvent::ButtonUp(button) => {
+                self.mouse_button_state[button as usize] = ButtonState::Up(index)
+            }
+            InputEvent::MouseMotion(x, y) => self.mouse_rel = Vec2f::new(x, y),
+            InputEvent::Pad(PadEvent::ButtonPressed(button)) => {
+                self.pad_button_state[button as usize] = ButtonState::Down(index)
+            }
+            InputEvent::Pad(PadEvent::ButtonReleased(button)) => {
+                self.pad_button_state[button as usize] = ButtonState::Up(index)
+            }
+            InputEvent::Pad(PadEvent::StickMoved(stick, x, y)) => {
+                self.pad_sticks[stick as usize] = Vec2f::new(x, y)
+            }
+            InputEvent::Quit => self.quit_requested_index = index,
+        }
+        if self.writer.is_some() {
+            self.recorded_events.push(event);
+        }
+    }
+
     pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
         self.new_mouse_grabbed = grabbed
     }
@@ -121,6 +219,56 @@ impl Input {
         self.mouse_enabled = enable;
     }
 
+    /// Replaces where gamepad events come from, e.g. to feed synthetic ones.
+    pub fn set_controller_source(&mut self, source: Box<dyn ControllerSource>) {
+        self.controller_source = source;
+    }
+
+    fn poll_controller(&mut self) {
+        while let Some(event) = self.controller_source.next_event() {
+            self.apply_event(InputEvent::Pad(event));
+        }
+    }
+
+    /// Replaces this update's live input with the replay's, or saves it when recording.
+    fn record_or_replay(&mut self, tick: TickIndex) {
+        if let Some(replay) = self.replay.as_mut() {
+            let events = replay.take_tick(tick);
+            let finished = replay.is_finished();
+            for event in events {
+                self.apply_event(event);
+            }
+            if finished {
+                info!("Input replay finished, back to live input.");
+                self.replay = None;
+            }
+            return;
+        }
+
+        self.poll_controller();
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return,
+        };
+        // Motion is summed over the whole update, and the close button is handled separately.
+        if self.mouse_rel != Vec2f::zero() {
+            self.recorded_events
+                .push(InputEvent::MouseMotion(self.mouse_rel.x, self.mouse_rel.y));
+        }
+        if self.quit_requested_index == self.current_update_index {
+            self.recorded_events.push(InputEvent::Quit);
+        }
+        if let Err(e) = writer.write_tick(tick, &self.recorded_events) {
+            error!(
+                "Failed to record input to `{}`, stopping: {}",
+                writer.path().display(),
+                e
+            );
+            self.writer = None;
+        }
+        self.recorded_events.clear();
+    }
+
     pub fn poll_gesture(&self, gesture: &Gesture) -> bool {
         match *gesture {
             Gesture::QuitTrigger => self.quit_requested_index == self.current_update_index,
@@ -144,6 +292,14 @@ impl Input {
                     ButtonState::Up(_) => false,
                 }
             }
+            Gesture::PadHold(button) => match self.pad_button_state[button as usize] {
+                ButtonState::Down(_) => true,
+                ButtonState::Up(_) => false,
+            },
+            Gesture::PadTrigger(button) => match self.pad_button_state[button as usize] {
+                ButtonState::Down(index) => self.current_update_index == index,
+                ButtonState::Up(_) => false,
+            },
             Gesture::AnyOf(ref subgestures) => subgestures
                 .iter()
                 .any(|subgesture| self.poll_gesture(subgesture)),
@@ -160,7 +316,17 @@ impl Input {
                 .iter()
                 .map(|analog| self.poll_analog2d(analog))
                 .fold(Vec2f::zero(), |x, y| x + y),
-            Analog2d::Mouse { sensitivity } => self.mouse_rel * sensitivity,
+            Analog2d::Mouse {
+                sensitivity,
+                invert_y,
+            } => {
+                let rel = self.mouse_rel * sensitivity;
+                if invert_y {
+                    Vec2f::new(rel.x, -rel.y)
+                } else {
+                    rel
+                }
+            }
             Analog2d::Gestures {
                 ref x_positive,
                 ref x_negative,
@@ -183,13 +349,40 @@ impl Input {
                     0.0
                 },
             ),
+            Analog2d::PadStick {
+                stick,
+                deadzone,
+                sensitivity,
+            } => {
+                let position = self.pad_sticks[stick as usize];
+                let magnitude = position.magnitude().min(1.0);
+                if magnitude <= deadzone {
+                    return Vec2f::zero();
+                }
+                // Rescale so that motion starts from zero at the edge of the deadzone.
+                let scale = (magnitude - deadzone) / (1.0 - deadzone) / magnitude * sensitivity;
+                Vec2f::new(position.x * scale, -position.y * scale)
+            }
             Analog2d::NoAnalog2d => Vec2f::zero(),
         }
     }
 }
 
+/// Recording and replaying input, to reproduce a session exactly. This only works with the same
+/// `TickConfig::timestep`, starting at the same level.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Saves every tick's input to this file, as it happens.
+    pub record: Option<PathBuf>,
+    /// Replays the input saved in this file instead of live input, until it runs out. Closing the
+    /// window still works. Cannot be combined with `record`.
+    pub replay: Option<PathBuf>,
+}
+
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
+    config: &'context Config,
+    tick: &'context Tick,
     window: &'context mut Window,
 }
 
@@ -205,14 +398,21 @@ pub struct Input {
     mouse_grabbed: bool,
     new_mouse_grabbed: bool,
     mouse_rel: Vec2f,
-}
 
-impl<'context> System<'context> for Input {
-    type Dependencies = Dependencies<'context>;
-    type Error = Error;
+    controller_source: Box<dyn ControllerSource>,
+    pad_button_state: [ButtonState; NUM_PAD_BUTTONS],
+    pad_sticks: [Vec2f; NUM_PAD_STICKS],
+
+    writer: Option<InputWriter>,
+    recorded_events: Vec<InputEvent>,
+    replay: Option<InputReplay>,
+
+    new_size: Option<(u32, u32)>,
+}
 
-    fn create(_deps: Dependencies) -> Result<Self> {
-        Ok(Input {
+impl Input {
+    fn new(controller_source: Box<dyn ControllerSource>) -> Self {
+        Input {
             current_update_index: 1,
             keyboard_state: [ButtonState::Up(0); NUM_SCAN_CODES],
             mouse_button_state: [ButtonState::Up(0); NUM_MOUSE_BUTTONS],
@@ -222,7 +422,39 @@ impl<'context> System<'context> for Input {
             new_mouse_grabbed: true,
             mouse_grabbed: false,
             mouse_rel: Vec2f::zero(),
-        })
+            controller_source,
+            pad_button_state: [ButtonState::Up(0); NUM_PAD_BUTTONS],
+            pad_sticks: [Vec2f::zero(); NUM_PAD_STICKS],
+            writer: None,
+            recorded_events: Vec::new(),
+            replay: None,
+            new_size: None,
+        }
+    }
+}
+
+impl<'context> System<'context> for Input {
+    type Dependencies = Dependencies<'context>;
+    type Error = Error;
+
+    fn create(deps: Dependencies) -> Result<Self> {
+        // Replayed ticks aren't written as they happen, so they would corrupt the new recording.
+        if deps.config.record.is_some() && deps.config.replay.is_some() {
+            bail!(ErrorKind::InputRecording(
+                "cannot record and replay input at the same time".to_owned()
+            ));
+        }
+        let mut input = Input::new(controller::default_source());
+        let timestep = deps.tick.timestep();
+        if let Some(ref path) = deps.config.replay {
+            input.replay = Some(InputReplay::load(path, timestep)?);
+            info!("Replaying input from `{}`...", path.display());
+        }
+        if let Some(ref path) = deps.config.record {
+            input.writer = Some(InputWriter::create(path, timestep)?);
+            info!("Recording input to `{}`...", path.display());
+        }
+        Ok(input)
     }
 
     fn debug_name() -> &'static str {
@@ -230,29 +462,40 @@ impl<'context> System<'context> for Input {
     }
 
     fn update(&mut self, deps: Dependencies) -> Result<()> {
+        self.record_or_replay(deps.tick.index());
+        if let Some((width, height)) = self.new_size.take() {
+            deps.window.resize(width, height);
+        }
+
+        // Without a display (software backend) there is no cursor to grab.
+        let facade = if let Some(facade) = deps.window.facade() {
+            facade
+        } else {
+            return Ok(());
+        };
         if self.new_mouse_grabbed != self.mouse_grabbed {
             self.mouse_grabbed = self.new_mouse_grabbed;
-            deps.window
-                .facade()
+            facade
                 .gl_window()
                 .window()
                 .set_cursor_grab(self.mouse_grabbed)
                 .ok();
-            deps.window
-                .facade()
+            facade
                 .gl_window()
                 .window()
                 .set_cursor_visible(!self.mouse_grabbed);
         }
         if self.mouse_grabbed {
-            let _ = deps.window.facade().gl_window().window();
+            let _ = facade.gl_window().window();
         }
         Ok(())
     }
 }
 
 const NUM_SCAN_CODES: usize = 512;
-const NUM_MOUSE_BUTTONS: usize = 256;
+pub(crate) const NUM_MOUSE_BUTTONS: usize = 256;
+const NUM_PAD_BUTTONS: usize = PadButton::DPadRight as usize + 1;
+const NUM_PAD_STICKS: usize = 2;
 
 type UpdateIndex = u32;
 
@@ -270,3 +513,141 @@ fn mouse_button_to_index(button: MouseButton) -> usize {
         MouseButton::Other(index) => ((index + 4) as usize).min(NUM_MOUSE_BUTTONS - 1),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Analog2d, Gesture, Input};
+    use crate::controller::{ControllerSource, PadButton, PadEvent, PadStick};
+    use crate::input_recording::{InputEvent, InputReplay, InputWriter};
+    use crate::tick::TickIndex;
+    use math::Vec2f;
+    use std::fs;
+
+    struct Events(Vec<PadEvent>);
+
+    impl ControllerSource for Events {
+        fn next_event(&mut self) -> Option<PadEvent> {
+            if self.0.is_empty() {
+                None
+            } else {
+                Some(self.0.remove(0))
+            }
+        }
+    }
+
+    fn step(input: &mut Input, events: Vec<PadEvent>) {
+        input.reset();
+        input.set_controller_source(Box::new(Events(events)));
+        input.poll_controller();
+    }
+
+    #[test]
+    fn pad_buttons() {
+        let mut input = Input::new(Box::new(Events(vec![])));
+        let hold = Gesture::PadHold(PadButton::South);
+        let trigger = Gesture::PadTrigger(PadButton::South);
+        assert!(!input.poll_gesture(&hold));
+
+        step(&mut input, vec![PadEvent::ButtonPressed(PadButton::South)]);
+        assert!(input.poll_gesture(&hold));
+        assert!(input.poll_gesture(&trigger));
+        assert!(!input.poll_gesture(&Gesture::PadHold(PadButton::East)));
+
+        step(&mut input, vec![]);
+        assert!(input.poll_gesture(&hold));
+        assert!(!input.poll_gesture(&trigger));
+
+        step(&mut input, vec![PadEvent::ButtonReleased(PadButton::South)]);
+        assert!(!input.poll_gesture(&hold));
+    }
+
+    #[test]
+    fn pad_stick() {
+        let mut input = Input::new(Box::new(Events(vec![])));
+        let stick = Analog2d::PadStick {
+            stick: PadStick::Right,
+            deadzone: 0.2,
+            sensitivity: 2.0,
+        };
+
+        step(
+            &mut input,
+            vec![PadEvent::StickMoved(PadStick::Right, 0.1, -0.1)],
+        );
+        assert_eq!(input.poll_analog2d(&stick), Vec2f::new(0.0, 0.0));
+
+        // Pushed all the way up, which is negative `y` like the mouse.
+        step(
+            &mut input,
+            vec![
+                PadEvent::StickMoved(PadStick::Left, 1.0, 0.0),
+                PadEvent::StickMoved(PadStick::Right, 0.0, 1.0),
+            ],
+        );
+        assert_eq!(input.poll_analog2d(&stick), Vec2f::new(0.0, -2.0));
+
+        // Halfway between the deadzone and the edge.
+        step(
+            &mut input,
+            vec![PadEvent::StickMoved(PadStick::Right, 0.6, 0.0)],
+        );
+        let half = input.poll_analog2d(&stick);
+        assert!((half.x - 1.0).abs() < 1e-5 && half.y == 0.0, "{:?}", half);
+    }
+
+    #[test]
+    fn replay_overrides_live_input() {
+        let path = std::env::temp_dir().join(format!("rs_doom_replay_{}.txt", std::process::id()));
+        let timestep = 1.0 / 60.0;
+        {
+            let mut writer = InputWriter::create(&path, timestep).unwrap();
+            writer
+                .write_tick(
+                    TickIndex(1),
+                    &[
+                        InputEvent::Pad(PadEvent::ButtonPressed(PadButton::South)),
+                        InputEvent::MouseMotion(2.0, -1.0),
+                    ],
+                )
+                .unwrap();
+            writer
+                .write_tick(
+                    TickIndex(2),
+                    &[InputEvent::Pad(PadEvent::ButtonReleased(PadButton::South))],
+                )
+                .unwrap();
+        }
+        let mut input = Input::new(Box::new(Events(vec![PadEvent::ButtonPressed(
+            PadButton::East,
+        )])));
+        input.replay = Some(InputReplay::load(&path, timestep).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let south = Gesture::PadHold(PadButton::South);
+        let east = Gesture::PadHold(PadButton::East);
+        let mouse = Analog2d::Mouse {
+            sensitivity: 1.0,
+            invert_y: false,
+        };
+
+        // The live gamepad press is ignored while the recording plays.
+        input.reset();
+        input.record_or_replay(TickIndex(1));
+        assert!(input.poll_gesture(&south));
+        assert!(input.poll_gesture(&Gesture::PadTrigger(PadButton::South)));
+        assert!(!input.poll_gesture(&east));
+        assert_eq!(input.poll_analog2d(&mouse), Vec2f::new(2.0, -1.0));
+
+        input.reset();
+        input.record_or_replay(TickIndex(2));
+        assert!(!input.poll_gesture(&south));
+        assert!(!input.poll_gesture(&east));
+        assert_eq!(input.poll_analog2d(&mouse), Vec2f::new(0.0, 0.0));
+        assert!(input.replay.is_none());
+
+        // Once the recording has run out, live input is back.
+        input.reset();
+        input.record_or_replay(TickIndex(3));
+        assert!(input.poll_gesture(&east));
+    }
+}
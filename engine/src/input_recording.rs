@@ -0,0 +1,243 @@
+use super::controller::{PadButton, PadEvent, PadStick};
+use super::errors::{ErrorKind, Result};
+use super::input::{Scancode, NUM_MOUSE_BUTTONS};
+use super::tick::TickIndex;
+use failchain::{bail, ResultExt};
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
+
+/// A change to the input state within a tick, exactly as `Input` applied it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum InputEvent {
+    KeyDown(Scancode),
+    KeyUp(Scancode),
+    /// Mouse buttons are recorded by their raw device button number.
+    ButtonDown(u32),
+    ButtonUp(u32),
+    /// The mouse motion accumulated over the whole tick.
+    MouseMotion(f32, f32),
+    Pad(PadEvent),
+    Quit,
+}
+
+/// Streams the events of every tick to a file as they happen, so that a recording survives the
+/// game crashing, which is when it is most useful.
+///
+/// Files are plain text, with a `timestep SECONDS` header and one `TICK EVENT [ARGUMENTS]` line
+/// per event, e.g. `120 key_down W` or `121 mouse 3 -1.5`.
+pub(crate) struct InputWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl InputWriter {
+    pub fn create(path: &Path, timestep: f32) -> Result<Self> {
+        let io_error = || ErrorKind::InputRecording(format!("cannot write `{}`", path.display()));
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).chain_err(io_error)?;
+        }
+        let mut writer = BufWriter::new(File::create(path).chain_err(io_error)?);
+        writeln!(writer, "# rs_doom input recording\ntimestep {}", timestep).chain_err(io_error)?;
+        Ok(InputWriter {
+            path: path.to_owned(),
+            writer,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_tick(&mut self, tick: TickIndex, events: &[InputEvent]) -> IoResult<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        for event in events {
+            write!(self.writer, "{} ", tick.0)?;
+            match *event {
+                InputEvent::KeyDown(key) => writeln!(self.writer, "key_down {:?}", key),
+                InputEvent::KeyUp(key) => writeln!(self.writer, "key_up {:?}", key),
+                InputEvent::ButtonDown(button) => writeln!(self.writer, "button_down {}", button),
+                InputEvent::ButtonUp(button) => writeln!(self.writer, "button_up {}", button),
+                // `Display` prints the shortest representation that parses back to the same float.
+                InputEvent::MouseMotion(x, y) => writeln!(self.writer, "mouse {} {}", x, y),
+                InputEvent::Pad(PadEvent::ButtonPressed(button)) => {
+                    writeln!(self.writer, "pad_down {:?}", button)
+                }
+                InputEvent::Pad(PadEvent::ButtonReleased(button)) => {
+                    writeln!(self.writer, "pad_up {:?}", button)
+                }
+                InputEvent::Pad(PadEvent::StickMoved(stick, x, y)) => {
+                    writeln!(self.writer, "pad_stick {:?} {} {}", stick, x, y)
+                }
+                InputEvent::Quit => writeln!(self.writer, "quit"),
+            }?;
+        }
+        self.writer.flush()
+    }
+}
+
+/// A recording loaded by `InputWriter`, handed back one tick at a time.
+pub(crate) struct InputReplay {
+    ticks: VecDeque<(TickIndex, Vec<InputEvent>)>,
+}
+
+impl InputReplay {
+    /// Loads a recording, which must have been made with the same `timestep` to replay exactly.
+    pub fn load(path: &Path, timestep: f32) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .chain_err(|| ErrorKind::InputRecording(format!("cannot read `{}`", path.display())))?;
+        let mut recorded_timestep = None;
+        let mut ticks: VecDeque<(TickIndex, Vec<InputEvent>)> = VecDeque::new();
+        for (i_line, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                ErrorKind::InputRecording(format!(
+                    "{}:{}: invalid line `{}`",
+                    path.display(),
+                    i_line + 1,
+                    line
+                ))
+            };
+            if recorded_timestep.is_none() {
+                recorded_timestep = Some(
+                    line.strip_prefix("timestep ")
+                        .and_then(|timestep| timestep.trim().parse::<f32>().ok())
+                        .ok_or_else(invalid)?,
+                );
+                continue;
+            }
+            let (tick, event) = parse_event(line).ok_or_else(invalid)?;
+            match ticks.back_mut() {
+                Some((last_tick, events)) if *last_tick == tick => events.push(event),
+                Some((last_tick, _)) if *last_tick > tick => bail!(invalid()),
+                _ => ticks.push_back((tick, vec![event])),
+            }
+        }
+        match recorded_timestep {
+            Some(recorded_timestep) if recorded_timestep != timestep => {
+                bail!(ErrorKind::InputRecording(format!(
+                    "`{}` was recorded with a timestep of {}s, not {}s",
+                    path.display(),
+                    recorded_timestep,
+                    timestep
+                )))
+            }
+            Some(_) => {}
+            None => bail!(ErrorKind::InputRecording(format!(
+                "`{}` has no timestep",
+                path.display()
+            ))),
+        }
+        Ok(InputReplay { ticks })
+    }
+
+    /// The events recorded for `tick`; earlier ticks are skipped if they were missed.
+    pub fn take_tick(&mut self, tick: TickIndex) -> Vec<InputEvent> {
+        while let Some((next_tick, _)) = self.ticks.front() {
+            if *next_tick > tick {
+                break;
+            }
+            let (next_tick, events) = self.ticks.pop_front().expect("front was just peeked");
+            if next_tick == tick {
+                return events;
+            }
+        }
+        Vec::new()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.ticks.is_empty()
+    }
+}
+
+fn parse_event(line: &str) -> Option<(TickIndex, InputEvent)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let tick = TickIndex(fields.first()?.parse().ok()?);
+    let event = match fields[1..] {
+        ["key_down", key] => InputEvent::KeyDown(parse_name(key)?),
+        ["key_up", key] => InputEvent::KeyUp(parse_name(key)?),
+        ["button_down", button] => InputEvent::ButtonDown(parse_button(button)?),
+        ["button_up", button] => InputEvent::ButtonUp(parse_button(button)?),
+        ["mouse", x, y] => InputEvent::MouseMotion(x.parse().ok()?, y.parse().ok()?),
+        ["pad_down", button] => {
+            InputEvent::Pad(PadEvent::ButtonPressed(parse_name::<PadButton>(button)?))
+        }
+        ["pad_up", button] => {
+            InputEvent::Pad(PadEvent::ButtonReleased(parse_name::<PadButton>(button)?))
+        }
+        ["pad_stick", stick, x, y] => InputEvent::Pad(PadEvent::StickMoved(
+            parse_name::<PadStick>(stick)?,
+            x.parse().ok()?,
+            y.parse().ok()?,
+        )),
+        ["quit"] => InputEvent::Quit,
+        _ => return None,
+    };
+    Some((tick, event))
+}
+
+fn parse_button(button: &str) -> Option<u32> {
+    button
+        .parse()
+        .ok()
+        .filter(|&button| (button as usize) < NUM_MOUSE_BUTTONS)
+}
+
+/// Parses a key or button by its variant name, which is also what their `Debug` prints.
+fn parse_name<'de, T: Deserialize<'de>>(name: &'de str) -> Option<T> {
+    let deserializer: StrDeserializer<ValueError> = name.into_deserializer();
+    T::deserialize(deserializer).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputEvent, InputReplay, InputWriter};
+    use crate::controller::{PadButton, PadEvent, PadStick};
+    use crate::input::Scancode;
+    use crate::tick::TickIndex;
+    use std::fs;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("rs_doom_input_{}.txt", std::process::id()));
+        let timestep = 1.0 / 60.0;
+        let first = vec![
+            InputEvent::KeyDown(Scancode::LControl),
+            InputEvent::ButtonDown(1),
+            InputEvent::MouseMotion(0.1, -3.0),
+        ];
+        let second = vec![
+            InputEvent::KeyUp(Scancode::Key1),
+            InputEvent::ButtonUp(1),
+            InputEvent::Pad(PadEvent::ButtonPressed(PadButton::DPadLeft)),
+            InputEvent::Pad(PadEvent::ButtonReleased(PadButton::South)),
+            InputEvent::Pad(PadEvent::StickMoved(PadStick::Right, 1.0 / 3.0, -0.5)),
+            InputEvent::Quit,
+        ];
+        {
+            let mut writer = InputWriter::create(&path, timestep).unwrap();
+            writer.write_tick(TickIndex(3), &first).unwrap();
+            writer.write_tick(TickIndex(4), &[]).unwrap();
+            writer.write_tick(TickIndex(7), &second).unwrap();
+        }
+
+        assert!(InputReplay::load(&path, 1.0 / 35.0).is_err());
+        let mut replay = InputReplay::load(&path, timestep).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(replay.take_tick(TickIndex(0)), vec![]);
+        assert_eq!(replay.take_tick(TickIndex(3)), first);
+        assert_eq!(replay.take_tick(TickIndex(4)), vec![]);
+        assert!(!replay.is_finished());
+        assert_eq!(replay.take_tick(TickIndex(7)), second);
+        assert!(replay.is_finished());
+    }
+}
@@ -4,43 +4,62 @@ pub mod context;
 pub mod system;
 pub mod type_list;
 
+mod capture;
+mod controller;
+mod culling;
 mod entities;
 mod errors;
 mod frame_timers;
 mod input;
+mod input_recording;
+mod lines;
 mod materials;
 mod meshes;
 mod pipeline;
 mod platform;
 mod projections;
+mod rasteriser;
 mod renderer;
+mod screenshot;
 mod shaders;
+mod software;
 mod text;
 mod tick;
 mod transforms;
 mod uniforms;
 mod window;
 
+pub use self::capture::{Capture, Config as CaptureConfig};
 pub use self::context::{Context, ContextBuilder, ControlFlow};
+pub use self::controller::{ControllerSource, NoControllers, PadButton, PadEvent, PadStick};
+pub use self::culling::CullingTree;
 pub use self::entities::{Entities, Entity, EntityId};
 pub use self::errors::{Error, ErrorKind, Result};
-pub use self::frame_timers::{FrameTimerId, FrameTimers};
-pub use self::input::{Analog2d, Gesture, Input, MouseButton, Scancode};
+pub use self::frame_timers::{FrameCounterId, FrameTimerId, FrameTimers};
+pub use self::input::{
+    Analog2d, Config as InputConfig, Gesture, Input, MouseButton, Scancode, Sensitivity,
+};
+pub use self::lines::{LineShader, LineVertex, LINE_SHADER};
 pub use self::materials::{MaterialId, MaterialRefMut, Materials};
 pub use self::meshes::{Mesh, MeshId, Meshes};
-pub use self::pipeline::RenderPipeline;
+pub use self::pipeline::{RenderPass, RenderPipeline, TargetSize};
 pub use self::projections::{Projection, Projections};
 pub use self::renderer::Renderer;
+pub use self::screenshot::Screenshot;
 pub use self::shaders::{ShaderConfig, ShaderId, Shaders};
+pub use self::software::{
+    Framebuffer, SoftwarePixel, SoftwareSampler, SoftwareShader, SoftwareShaders, SoftwareUniforms,
+    Varyings,
+};
 pub use self::system::{InfallibleSystem, System};
-pub use self::text::{Text, TextId, TextRenderer};
+pub use self::text::{BitmapFont, BitmapGlyph, FontId, Text, TextId, TextRenderer};
 pub use self::tick::{Config as TickConfig, Tick, TickIndex};
 pub use self::transforms::Transforms;
 pub use self::uniforms::{
-    BufferTextureId, FloatUniformId, Mat4UniformId, Texture2dId, UniformId, Uniforms,
+    BufferTextureId, FloatUniformId, Mat4UniformId, Texture2dId, TextureArray, UniformId, Uniforms,
     Vec2fUniformId,
 };
-pub use self::window::{Window, WindowConfig};
+pub use self::window::{Fullscreen, RenderBackend, Window, WindowConfig};
 pub use glium::texture::buffer_texture::BufferTextureType;
 pub use glium::texture::{ClientFormat, PixelValue};
 pub use glium::uniforms::{
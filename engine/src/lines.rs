@@ -0,0 +1,40 @@
+use super::software::{SoftwareShader, SoftwareUniforms};
+use glium::implement_vertex;
+use math::{Mat4, Vec3f, Vec4f};
+
+/// The asset path of the flat-coloured line shader, drawn with `LineVertex` meshes added with
+/// `MeshAdder::lines`. Its only uniform is the `u_transform` matrix, which takes vertex positions
+/// straight to clip space, so overlays can ignore the camera.
+pub const LINE_SHADER: &str = "lines";
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct LineVertex {
+    pub a_pos: [f32; 3],
+    pub a_color: [f32; 3],
+}
+
+implement_vertex!(LineVertex, a_pos, a_color);
+
+/// The software version of the `LINE_SHADER`.
+pub struct LineShader;
+
+impl SoftwareShader for LineShader {
+    type Vertex = LineVertex;
+    type Uniforms<'a> = Mat4;
+    type Varyings = [f32; 3];
+    type Flat = ();
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<Mat4> {
+        uniforms.mat4("u_transform")
+    }
+
+    fn vertex(&self, transform: &Mat4, vertex: &LineVertex) -> (Vec4f, [f32; 3], ()) {
+        let [x, y, z] = vertex.a_pos;
+        (*transform * Vec4f::new(x, y, z, 1.0), vertex.a_color, ())
+    }
+
+    fn fragment(&self, _transform: &Mat4, color: &[f32; 3], _flat: &()) -> Option<Vec3f> {
+        Some(Vec3f::from(*color))
+    }
+}
@@ -1,15 +1,21 @@
 use super::entities::{Entities, Entity, EntityId};
 use super::errors::Result;
+use super::meshes::MeshRef;
 use super::shaders::{ShaderId, Shaders};
+use super::software::{DrawSoftware, Framebuffer, SoftwareUniforms};
 use super::system::InfallibleSystem;
 use super::uniforms::{UniformId, Uniforms};
 use glium::uniforms::{UniformValue, Uniforms as GliumUniforms};
 use glium::Program;
 use idcontain::IdMapVec;
 use log::{debug, error};
+use std::ops::Range;
 
 pub const MAX_UNIFORMS: usize = 64;
 
+/// Named uniform values of a material, terminated by the first `None`.
+pub(crate) type UniformValues<ValueT> = [Option<(&'static str, ValueT)>; MAX_UNIFORMS];
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct MaterialId(pub EntityId);
 
@@ -73,29 +79,34 @@ impl Materials {
             return None;
         };
 
-        let mut uniform_values = [None; MAX_UNIFORMS];
-        for (value, &uniform) in (&mut uniform_values[..])
-            .iter_mut()
-            .zip(&material.uniforms[..])
-        {
-            if let Some((name, id)) = uniform {
-                if let Some(uniform_value) = uniforms.get_value(id) {
-                    *value = Some((name, uniform_value));
-                } else {
-                    error!(
-                        "Missing uniform for material {:?}: name={:?} id={:?}",
-                        material_id, name, id
-                    );
-                    return None;
-                }
-            } else {
-                break;
-            }
-        }
-
         Some(MaterialRef {
             shader,
-            uniform_values,
+            uniform_values: material.resolve_uniforms(material_id, |id| uniforms.get_value(id))?,
+        })
+    }
+
+    pub(crate) fn get_software<'a>(
+        &'a self,
+        shaders: &'a Shaders,
+        uniforms: &'a Uniforms,
+        material_id: MaterialId,
+    ) -> Option<SoftwareMaterialRef<'a>> {
+        let material = self.map.get(material_id.0)?;
+        let shader = if let Some(shader) = shaders.get_software(material.shader) {
+            shader
+        } else {
+            error!(
+                "Missing software shader {:?} for material {:?}",
+                material.shader, material_id
+            );
+            return None;
+        };
+
+        Some(SoftwareMaterialRef {
+            shader,
+            uniforms: SoftwareUniforms::new(
+                material.resolve_uniforms(material_id, |id| uniforms.get_software_value(id))?,
+            ),
         })
     }
 }
@@ -132,7 +143,26 @@ impl<'a> MaterialRefMut<'a> {
 
 pub struct MaterialRef<'a> {
     shader: &'a Program,
-    uniform_values: [Option<(&'static str, UniformValue<'a>)>; MAX_UNIFORMS],
+    uniform_values: UniformValues<UniformValue<'a>>,
+}
+
+pub(crate) struct SoftwareMaterialRef<'a> {
+    shader: &'a dyn DrawSoftware,
+    uniforms: SoftwareUniforms<'a>,
+}
+
+impl<'a> SoftwareMaterialRef<'a> {
+    /// Draws `mesh` (or only the given index ranges of it) into `target`.
+    pub(crate) fn draw(
+        &self,
+        target: &mut Framebuffer,
+        mesh: &MeshRef,
+        ranges: Option<&[Range<usize>]>,
+        wireframe: bool,
+    ) -> Result<()> {
+        self.shader
+            .draw(target, &self.uniforms, mesh, ranges, wireframe)
+    }
 }
 
 impl<'context> InfallibleSystem<'context> for Materials {
@@ -193,3 +223,30 @@ struct Material {
     shader: ShaderId,
     uniforms: [Option<(&'static str, UniformId)>; MAX_UNIFORMS],
 }
+
+impl Material {
+    /// Looks up the values of all the uniforms, or returns `None` if any is missing.
+    fn resolve_uniforms<ValueT: Copy>(
+        &self,
+        material_id: MaterialId,
+        get_value: impl Fn(UniformId) -> Option<ValueT>,
+    ) -> Option<UniformValues<ValueT>> {
+        let mut uniform_values = [None; MAX_UNIFORMS];
+        for (value, &uniform) in uniform_values[..].iter_mut().zip(&self.uniforms[..]) {
+            if let Some((name, id)) = uniform {
+                if let Some(uniform_value) = get_value(id) {
+                    *value = Some((name, uniform_value));
+                } else {
+                    error!(
+                        "Missing uniform for material {:?}: name={:?} id={:?}",
+                        material_id, name, id
+                    );
+                    return None;
+                }
+            } else {
+                break;
+            }
+        }
+        Some(uniform_values)
+    }
+}
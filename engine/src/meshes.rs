@@ -3,11 +3,13 @@ use super::errors::{ErrorKind, Result};
 use super::system::InfallibleSystem;
 use super::window::Window;
 pub use glium::index::IndexBuffer;
-use glium::index::{IndicesSource, PrimitiveType};
+use glium::index::{IndexBufferSlice, IndicesSource, PrimitiveType};
 use glium::vertex::{Vertex, VertexBuffer, VerticesSource};
 pub use glium_typed_buffer_any::TypedVertexBufferAny;
 use idcontain::IdMapVec;
 use log::{debug, error};
+use std::any::Any;
+use std::ops::Range;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct MeshId(EntityId);
@@ -31,6 +33,7 @@ impl Meshes {
                 entities,
                 parent,
                 name,
+                primitives: Primitives::Triangles,
             },
             vertices: (),
             indices: (),
@@ -45,6 +48,7 @@ impl Meshes {
             } => MeshRef {
                 vertices,
                 indices: indices.as_ref(),
+                primitives: mesh.primitives,
             },
             InternalMeshData::Inherit {
                 vertices_from,
@@ -60,6 +64,7 @@ impl Meshes {
                     _ => panic!("unowned mesh in stored vertices_from"),
                 },
                 indices: Some(indices),
+                primitives: mesh.primitives,
             },
         })
     }
@@ -70,44 +75,84 @@ impl Meshes {
                 ref mut vertices,
                 ref mut indices,
             } => MeshRefMut {
-                vertices: Some(vertices),
-                indices: indices.as_mut(),
+                vertices: match vertices {
+                    Vertices::Gpu(vertices) => Some(vertices),
+                    Vertices::Software(_) => None,
+                },
+                indices: indices.as_mut().and_then(Indices::gpu_mut),
             },
             InternalMeshData::Inherit {
                 vertices_from: _vertices_from,
                 ref mut indices,
             } => MeshRefMut {
                 vertices: None,
-                indices: Some(indices),
+                indices: indices.gpu_mut(),
             },
         })
     }
 }
 
+/// Mutable access to the GPU buffers of a mesh; both are `None` with the software backend.
 pub struct MeshRefMut<'a> {
     pub vertices: Option<&'a mut TypedVertexBufferAny>,
     pub indices: Option<&'a mut IndexBuffer<u32>>,
 }
 
 pub struct MeshRef<'a> {
-    vertices: &'a TypedVertexBufferAny,
-    indices: Option<&'a IndexBuffer<u32>>,
+    vertices: &'a Vertices,
+    indices: Option<&'a Indices>,
+    primitives: Primitives,
 }
 
-impl<'a, 'b: 'a> From<&'a MeshRef<'b>> for IndicesSource<'a> {
-    fn from(mesh: &'a MeshRef<'b>) -> Self {
-        mesh.indices.map_or(
-            IndicesSource::NoIndices {
-                primitives: PrimitiveType::TrianglesList,
+impl<'a> MeshRef<'a> {
+    pub(crate) fn primitives(&self) -> Primitives {
+        self.primitives
+    }
+
+    pub(crate) fn num_indices(&self) -> Option<usize> {
+        self.indices.map(|indices| match indices {
+            Indices::Gpu(indices) => indices.len(),
+            Indices::Software(indices) => indices.len(),
+        })
+    }
+
+    pub(crate) fn index_slice(&self, range: Range<usize>) -> Option<IndexBufferSlice<'a, u32>> {
+        match self.indices? {
+            Indices::Gpu(indices) => indices.slice(range),
+            Indices::Software(_) => None,
+        }
+    }
+
+    /// The GPU buffers to draw the whole mesh from, or `None` if the mesh was created for the
+    /// software backend.
+    pub(crate) fn gpu_sources(&self) -> Option<(VerticesSource<'a>, IndicesSource<'a>)> {
+        let vertices = match self.vertices {
+            Vertices::Gpu(vertices) => vertices.into(),
+            Vertices::Software(_) => return None,
+        };
+        let indices = match self.indices {
+            Some(Indices::Gpu(indices)) => indices.into(),
+            Some(Indices::Software(_)) => return None,
+            None => IndicesSource::NoIndices {
+                primitives: self.primitives.into(),
             },
-            |indices| indices.into(),
-        )
+        };
+        Some((vertices, indices))
     }
-}
 
-impl<'a, 'b: 'a> From<&'a MeshRef<'b>> for VerticesSource<'a> {
-    fn from(mesh: &'a MeshRef<'b>) -> Self {
-        mesh.vertices.into()
+    /// The CPU copies of the vertices (a `Vec` of the vertex type) and indices, if the mesh was
+    /// created for the software backend.
+    pub(crate) fn software(&self) -> Option<(&'a dyn Any, Option<&'a [u32]>)> {
+        let vertices = match self.vertices {
+            Vertices::Software(vertices) => &**vertices,
+            Vertices::Gpu(_) => return None,
+        };
+        let indices = match self.indices {
+            Some(Indices::Software(indices)) => Some(&indices[..]),
+            Some(Indices::Gpu(_)) => return None,
+            None => None,
+        };
+        Some((vertices, indices))
     }
 }
 
@@ -118,9 +163,9 @@ pub struct MeshAdder<'a, VertexDataT, IndexDataT> {
     indices: IndexDataT,
 }
 
-pub struct OwnedVertexData(TypedVertexBufferAny);
+pub struct OwnedVertexData(Vertices);
 pub struct SharedVertexData(MeshId);
-pub struct IndexData(IndexBuffer<u32>);
+pub struct IndexData(Indices);
 
 impl<'a, IndexDataT> MeshAdder<'a, (), IndexDataT> {
     pub fn immutable<VertexT>(
@@ -130,12 +175,16 @@ impl<'a, IndexDataT> MeshAdder<'a, (), IndexDataT> {
     where
         VertexT: Vertex + Send + 'static,
     {
-        Ok(MeshAdder {
-            vertices: OwnedVertexData(
-                VertexBuffer::immutable(self.context.window.facade(), vertices)
+        let vertices = match self.context.window.facade() {
+            Some(facade) => Vertices::Gpu(
+                VertexBuffer::immutable(facade, vertices)
                     .map_err(ErrorKind::glium(self.context.name))?
                     .into(),
             ),
+            None => Vertices::Software(Box::new(vertices.to_vec())),
+        };
+        Ok(MeshAdder {
+            vertices: OwnedVertexData(vertices),
             indices: self.indices,
             context: self.context,
         })
@@ -148,12 +197,16 @@ impl<'a, IndexDataT> MeshAdder<'a, (), IndexDataT> {
     where
         VertexT: Vertex + Send + 'static,
     {
-        Ok(MeshAdder {
-            vertices: OwnedVertexData(
-                VertexBuffer::persistent(self.context.window.facade(), vertices)
+        let vertices = match self.context.window.facade() {
+            Some(facade) => Vertices::Gpu(
+                VertexBuffer::persistent(facade, vertices)
                     .map_err(ErrorKind::glium(self.context.name))?
                     .into(),
             ),
+            None => Vertices::Software(Box::new(vertices.to_vec())),
+        };
+        Ok(MeshAdder {
+            vertices: OwnedVertexData(vertices),
             indices: self.indices,
             context: self.context,
         })
@@ -182,19 +235,23 @@ impl<'a, IndexDataT> MeshAdder<'a, (), IndexDataT> {
 }
 
 impl<'a, VertexDataT> MeshAdder<'a, VertexDataT, ()> {
+    /// Draws the mesh as a list of lines (pairs of vertices) instead of triangles.
+    pub fn lines(mut self) -> Self {
+        self.context.primitives = Primitives::Lines;
+        self
+    }
+
     pub fn immutable_indices(
         self,
         indices: &[u32],
     ) -> Result<MeshAdder<'a, VertexDataT, IndexData>> {
         Ok(MeshAdder {
-            indices: IndexData(
-                IndexBuffer::persistent(
-                    self.context.window.facade(),
-                    PrimitiveType::TrianglesList,
-                    indices,
-                )
-                .map_err(ErrorKind::glium(self.context.name))?,
-            ),
+            indices: IndexData(Indices::new(
+                self.context.window,
+                self.context.name,
+                self.context.primitives,
+                indices,
+            )?),
             vertices: self.vertices,
             context: self.context,
         })
@@ -205,14 +262,12 @@ impl<'a, VertexDataT> MeshAdder<'a, VertexDataT, ()> {
         indices: &[u32],
     ) -> Result<MeshAdder<'a, VertexDataT, IndexData>> {
         Ok(MeshAdder {
-            indices: IndexData(
-                IndexBuffer::persistent(
-                    self.context.window.facade(),
-                    PrimitiveType::TrianglesList,
-                    indices,
-                )
-                .map_err(ErrorKind::glium(self.context.name))?,
-            ),
+            indices: IndexData(Indices::new(
+                self.context.window,
+                self.context.name,
+                self.context.primitives,
+                indices,
+            )?),
             vertices: self.vertices,
             context: self.context,
         })
@@ -248,6 +303,32 @@ impl<'a> MeshAdder<'a, OwnedVertexData, IndexData> {
 
 pub struct Mesh {
     data: InternalMeshData,
+    primitives: Primitives,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Primitives {
+    Triangles,
+    Lines,
+}
+
+impl Primitives {
+    /// The number of vertices (or indices) making up a single primitive.
+    pub(crate) fn num_vertices(self) -> usize {
+        match self {
+            Primitives::Triangles => 3,
+            Primitives::Lines => 2,
+        }
+    }
+}
+
+impl From<Primitives> for PrimitiveType {
+    fn from(primitives: Primitives) -> Self {
+        match primitives {
+            Primitives::Triangles => PrimitiveType::TrianglesList,
+            Primitives::Lines => PrimitiveType::LinesList,
+        }
+    }
 }
 
 impl<'context> InfallibleSystem<'context> for Meshes {
@@ -286,27 +367,71 @@ impl<'context> InfallibleSystem<'context> for Meshes {
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::large_enum_variant))]
 enum InternalMeshData {
     Owned {
-        vertices: TypedVertexBufferAny,
-        indices: Option<IndexBuffer<u32>>,
+        vertices: Vertices,
+        indices: Option<Indices>,
     },
     Inherit {
         vertices_from: MeshId,
-        indices: IndexBuffer<u32>,
+        indices: Indices,
     },
 }
 
+#[allow(clippy::large_enum_variant)]
+enum Vertices {
+    Gpu(TypedVertexBufferAny),
+    /// A `Vec` of vertices, downcast by the software shader drawing it.
+    Software(Box<dyn Any>),
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Indices {
+    Gpu(IndexBuffer<u32>),
+    Software(Vec<u32>),
+}
+
+impl Indices {
+    fn new(
+        window: &Window,
+        name: &'static str,
+        primitives: Primitives,
+        indices: &[u32],
+    ) -> Result<Self> {
+        Ok(match window.facade() {
+            Some(facade) => Indices::Gpu(
+                IndexBuffer::persistent(facade, primitives.into(), indices)
+                    .map_err(ErrorKind::glium(name))?,
+            ),
+            None => Indices::Software(indices.to_vec()),
+        })
+    }
+
+    fn gpu_mut(&mut self) -> Option<&mut IndexBuffer<u32>> {
+        match self {
+            Indices::Gpu(indices) => Some(indices),
+            Indices::Software(_) => None,
+        }
+    }
+}
+
 struct MeshAdderContext<'a> {
     meshes: &'a mut Meshes,
     window: &'a Window,
     entities: &'a mut Entities,
     parent: EntityId,
     name: &'static str,
+    primitives: Primitives,
 }
 
 impl<'a> MeshAdderContext<'a> {
     fn add(self, data: InternalMeshData) -> Result<MeshId> {
         let id = self.entities.add(self.parent, self.name)?;
-        self.meshes.map.insert(id, Mesh { data });
+        self.meshes.map.insert(
+            id,
+            Mesh {
+                data,
+                primitives: self.primitives,
+            },
+        );
         debug!(
             "Added mesh {:?} {:?} as child of {:?}.",
             self.name, id, self.parent
@@ -1,14 +1,68 @@
+use super::culling::CullingTree;
 use super::entities::{Entities, Entity, EntityId};
 use super::materials::MaterialId;
 use super::meshes::MeshId;
+use super::shaders::ShaderId;
 use super::system::InfallibleSystem;
 use super::uniforms::{Mat4UniformId, Uniforms};
 use crate::internal_derive::DependenciesFrom;
+use glium::uniforms::MagnifySamplerFilter;
 use idcontain::IdMapVec;
 use log::{debug, error};
 use math::prelude::*;
 use math::Mat4;
 
+/// The size of the offscreen target a `RenderPass` draws into.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetSize {
+    /// The size of the window.
+    Window,
+    /// The size of the window multiplied by a factor, e.g. `0.5` for half resolution.
+    Scaled(f32),
+    /// A fixed number of pixels, e.g. 320x200, stretched over the window.
+    Fixed(u32, u32),
+}
+
+impl TargetSize {
+    /// The size in pixels, given the size of the window.
+    pub fn resolve(self, window_width: u32, window_height: u32) -> (u32, u32) {
+        let (width, height) = match self {
+            TargetSize::Window => (window_width, window_height),
+            TargetSize::Scaled(scale) => (
+                (window_width as f32 * scale).round() as u32,
+                (window_height as f32 * scale).round() as u32,
+            ),
+            TargetSize::Fixed(width, height) => (width, height),
+        };
+        (width.max(1), height.max(1))
+    }
+}
+
+/// A step of the render pipeline, drawing into an offscreen colour texture. The output of the last
+/// pass is stretched over the window, before text is drawn on top.
+#[derive(Copy, Clone, Debug)]
+pub enum RenderPass {
+    /// Draws all the models into a colour and depth target.
+    Scene { size: TargetSize },
+    /// Draws a full-screen triangle with `shader`, whose `u_source` sampler is the colour of the
+    /// previous pass, sampled with `filter`. The vertex shader gets no attributes and should
+    /// derive its position from `gl_VertexID`; `u_source_size` and `u_target_size` hold the sizes
+    /// of the previous and current target in pixels. Not supported by the software backend.
+    PostProcess {
+        shader: ShaderId,
+        size: TargetSize,
+        filter: MagnifySamplerFilter,
+    },
+}
+
+impl RenderPass {
+    pub fn size(&self) -> TargetSize {
+        match *self {
+            RenderPass::Scene { size } | RenderPass::PostProcess { size, .. } => size,
+        }
+    }
+}
+
 impl RenderPipeline {
     pub fn modelview(&self) -> Mat4UniformId {
         self.modelview
@@ -18,6 +72,10 @@ impl RenderPipeline {
         self.projection
     }
 
+    pub fn camera(&self) -> Option<EntityId> {
+        self.camera
+    }
+
     pub fn set_camera(&mut self, camera: EntityId) {
         self.camera = Some(camera);
     }
@@ -35,6 +93,63 @@ impl RenderPipeline {
         }
         debug!("Attached model to entity {:?}.", entity);
     }
+
+    /// Changes the material of an entity's model, keeping its mesh.
+    pub fn set_material(&mut self, entity: EntityId, material: MaterialId) {
+        if let Some(model) = self.models.get_mut(entity) {
+            model.material = material;
+        } else {
+            error!(
+                "Entity {:?} has no model, cannot set material {:?}.",
+                entity, material
+            );
+        }
+    }
+
+    /// Draws the edges of triangles instead of filling them, for debugging geometry.
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    /// Replaces the passes models are rendered with, and the filter used to stretch the output of
+    /// the last one over the window. With no passes (the default), models are drawn straight into
+    /// the window.
+    ///
+    /// The first pass must draw the scene, since post-processing has nothing to read before it; if
+    /// it doesn't, the passes are cleared instead.
+    pub fn set_passes(&mut self, passes: Vec<RenderPass>, blit_filter: MagnifySamplerFilter) {
+        if let Some(RenderPass::PostProcess { .. }) = passes.first() {
+            error!("First render pass must draw the scene, drawing straight into the window.");
+            self.passes.clear();
+            return;
+        }
+        debug!(
+            "Render passes set to {:?}, blit filter {:?}.",
+            passes, blit_filter
+        );
+        self.passes = passes;
+        self.blit_filter = blit_filter;
+    }
+
+    pub fn passes(&self) -> &[RenderPass] {
+        &self.passes
+    }
+
+    /// Attaches a culling tree to an entity with a model, to only draw the parts of its index buffer
+    /// which are in view.
+    pub fn attach_culling(&mut self, entity: EntityId, tree: CullingTree) {
+        debug!(
+            "Attaching culling tree to entity {:?}: num_leaves={}",
+            entity,
+            tree.num_leaves()
+        );
+        if self.culling.insert(entity, tree).is_some() {
+            error!(
+                "Entity {:?} already had a culling tree attached, replacing.",
+                entity
+            );
+        }
+    }
 }
 
 #[derive(DependenciesFrom)]
@@ -45,11 +160,15 @@ pub struct Dependencies<'context> {
 
 pub struct RenderPipeline {
     pub(crate) models: IdMapVec<Entity, Model>,
+    pub(crate) culling: IdMapVec<Entity, CullingTree>,
     pub(crate) modelview: Mat4UniformId,
     pub(crate) projection: Mat4UniformId,
     pub(crate) root: EntityId,
 
     pub(crate) camera: Option<EntityId>,
+    pub(crate) wireframe: bool,
+    pub(crate) passes: Vec<RenderPass>,
+    pub(crate) blit_filter: MagnifySamplerFilter,
 }
 
 impl<'context> InfallibleSystem<'context> for RenderPipeline {
@@ -71,10 +190,14 @@ impl<'context> InfallibleSystem<'context> for RenderPipeline {
             .unwrap();
         RenderPipeline {
             models: IdMapVec::with_capacity(128),
+            culling: IdMapVec::with_capacity(16),
             root,
             projection,
             modelview,
             camera: None,
+            wireframe: false,
+            passes: Vec::new(),
+            blit_filter: MagnifySamplerFilter::Nearest,
         }
     }
 
@@ -1,19 +1,50 @@
 use super::entities::{Entities, Entity, EntityId};
 use super::system::InfallibleSystem;
+use super::window::Window;
+use crate::internal_derive::DependenciesFrom;
 use idcontain::IdMapVec;
 use log::{debug, error};
 use math::{self, Mat4, Rad};
 
 #[derive(Copy, Clone, Debug)]
-pub struct Projection {
-    pub fov: Rad<f32>,
-    pub aspect_ratio: f32,
-    pub near: f32,
-    pub far: f32,
+pub enum Projection {
+    Perspective {
+        fov: Rad<f32>,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+    },
+    /// A parallel projection showing `2 * half_height` world units vertically.
+    Orthographic {
+        half_height: f32,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+    },
 }
 
+impl Projection {
+    pub fn aspect_ratio_mut(&mut self) -> &mut f32 {
+        match self {
+            Projection::Perspective { aspect_ratio, .. }
+            | Projection::Orthographic { aspect_ratio, .. } => aspect_ratio,
+        }
+    }
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    entities: &'context Entities,
+    window: &'context Window,
+}
+
+/// Perspective and orthographic projections attached to entities.
+///
+/// When the window is resized, every aspect ratio is scaled by the change in the window's aspect
+/// ratio, so any correction applied on top of it when attaching is kept.
 pub struct Projections {
     map: IdMapVec<Entity, StoredProjection>,
+    window_aspect_ratio: f32,
 }
 
 impl Projections {
@@ -50,35 +81,54 @@ impl Projections {
             with(None)
         }
     }
+
+    fn remove_dead(&mut self, entities: &Entities) {
+        for &entity in entities.last_removed() {
+            if self.map.remove(entity).is_some() {
+                debug!("Removed projection {:?}.", entity);
+            }
+        }
+    }
+
+    fn follow_window(&mut self, window: &Window) {
+        let window_aspect_ratio = window.aspect_ratio();
+        if window_aspect_ratio == self.window_aspect_ratio {
+            return;
+        }
+        let scale = window_aspect_ratio / self.window_aspect_ratio;
+        self.window_aspect_ratio = window_aspect_ratio;
+        for stored in self.map.access_mut() {
+            *stored.projection.aspect_ratio_mut() *= scale;
+            stored.matrix = stored.projection.into();
+        }
+    }
 }
 
 impl<'context> InfallibleSystem<'context> for Projections {
-    type Dependencies = &'context Entities;
+    type Dependencies = Dependencies<'context>;
 
     fn debug_name() -> &'static str {
         "projections"
     }
 
-    fn create(_: &'context Entities) -> Self {
+    fn create(deps: Dependencies) -> Self {
         Projections {
             map: IdMapVec::with_capacity(128),
+            window_aspect_ratio: deps.window.aspect_ratio(),
         }
     }
 
-    fn update(&mut self, entities: &Entities) {
-        for &entity in entities.last_removed() {
-            if self.map.remove(entity).is_some() {
-                debug!("Removed projection {:?}.", entity);
-            }
-        }
+    fn update(&mut self, deps: Dependencies) {
+        self.remove_dead(deps.entities);
+        self.follow_window(deps.window);
     }
 
-    fn teardown(&mut self, entities: &Entities) {
-        self.update(entities);
+    fn teardown(&mut self, deps: Dependencies) {
+        self.remove_dead(deps.entities);
     }
 
-    fn destroy(mut self, entities: &Entities) {
-        self.update(entities);
+    fn destroy(mut self, deps: Dependencies) {
+        self.remove_dead(deps.entities);
         if !self.map.is_empty() {
             error!("Projections leaked, {} instances.", self.map.len());
         }
@@ -92,11 +142,29 @@ struct StoredProjection {
 
 impl From<Projection> for Mat4 {
     fn from(projection: Projection) -> Self {
-        math::perspective(
-            projection.fov,
-            projection.aspect_ratio,
-            projection.near,
-            projection.far,
-        )
+        match projection {
+            Projection::Perspective {
+                fov,
+                aspect_ratio,
+                near,
+                far,
+            } => math::perspective(fov, aspect_ratio, near, far),
+            Projection::Orthographic {
+                half_height,
+                aspect_ratio,
+                near,
+                far,
+            } => {
+                let half_width = half_height * aspect_ratio;
+                math::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
     }
 }
@@ -0,0 +1,419 @@
+use super::software::{Framebuffer, Varyings};
+use math::{Vec3f, Vec4f};
+
+/// A vertex shader output: a clip-space position and the values to interpolate.
+#[derive(Copy, Clone)]
+pub(crate) struct ClipVertex<VaryingsT> {
+    pub position: Vec4f,
+    pub varyings: VaryingsT,
+}
+
+/// Clips, culls and rasterises a triangle, with the same conventions as the GPU pipeline:
+/// counter-clockwise front faces, a less-than depth test and perspective-correct interpolation.
+pub(crate) fn draw_triangle<VaryingsT, ShadeT>(
+    target: &mut Framebuffer,
+    triangle: [ClipVertex<VaryingsT>; 3],
+    mut shade: ShadeT,
+) where
+    VaryingsT: Varyings,
+    ShadeT: FnMut(&VaryingsT) -> Option<Vec3f>,
+{
+    // Trivially reject triangles entirely outside any one of the clip planes.
+    for axis in 0..3 {
+        let outside = |sign: f32| {
+            triangle
+                .iter()
+                .all(|vertex| vertex.position[axis] * sign > vertex.position.w)
+        };
+        if outside(1.0) || outside(-1.0) {
+            return;
+        }
+    }
+
+    // Only the near plane needs actual clipping, since the division by `w` is not defined behind
+    // the camera. The other planes are handled by the bounding box and the depth range check.
+    let mut polygon = [triangle[0]; MAX_CLIPPED_VERTICES];
+    let mut num_vertices = 0;
+    for (i, current) in triangle.iter().enumerate() {
+        let next = &triangle[(i + 1) % 3];
+        let current_distance = current.position.z + current.position.w;
+        let next_distance = next.position.z + next.position.w;
+        if current_distance >= 0.0 {
+            polygon[num_vertices] = *current;
+            num_vertices += 1;
+        }
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            polygon[num_vertices] = ClipVertex {
+                position: current.position + (next.position - current.position) * t,
+                varyings: current.varyings.lerp(&next.varyings, t),
+            };
+            num_vertices += 1;
+        }
+    }
+    if num_vertices < 3 {
+        return;
+    }
+
+    let width = target.width() as f32;
+    let height = target.height() as f32;
+    let mut screen = [ScreenVertex::default(); MAX_CLIPPED_VERTICES];
+    for (screen, clip) in screen.iter_mut().zip(&polygon[..num_vertices]) {
+        let inv_w = 1.0 / clip.position.w;
+        *screen = ScreenVertex {
+            x: (clip.position.x * inv_w + 1.0) * 0.5 * width,
+            y: (1.0 - clip.position.y * inv_w) * 0.5 * height,
+            z: (clip.position.z * inv_w + 1.0) * 0.5,
+            inv_w,
+        };
+    }
+
+    for i in 1..num_vertices - 1 {
+        draw_clipped(
+            target,
+            [&screen[0], &screen[i], &screen[i + 1]],
+            [
+                &polygon[0].varyings,
+                &polygon[i].varyings,
+                &polygon[i + 1].varyings,
+            ],
+            &mut shade,
+        );
+    }
+}
+
+/// Draws the edges of a triangle as lines, like `PolygonMode::Line` on the GPU. Back faces are
+/// culled as long as the whole triangle is in front of the camera.
+pub(crate) fn draw_triangle_edges<VaryingsT, ShadeT>(
+    target: &mut Framebuffer,
+    triangle: [ClipVertex<VaryingsT>; 3],
+    mut shade: ShadeT,
+) where
+    VaryingsT: Varyings,
+    ShadeT: FnMut(&VaryingsT) -> Option<Vec3f>,
+{
+    if triangle.iter().all(|vertex| vertex.position.w > 0.0) {
+        let [a, b, c] = triangle.map(|vertex| {
+            let inv_w = 1.0 / vertex.position.w;
+            // Only the sign of the area matters, so there is no need to scale to pixels.
+            ScreenVertex {
+                x: vertex.position.x * inv_w,
+                y: -vertex.position.y * inv_w,
+                ..ScreenVertex::default()
+            }
+        });
+        if edge(&a, &b, c.x, c.y) >= 0.0 {
+            return;
+        }
+    }
+    for i in 0..3 {
+        draw_line(target, [triangle[i], triangle[(i + 1) % 3]], &mut shade);
+    }
+}
+
+/// Clips and rasterises a one pixel wide line, with the same depth test and interpolation as
+/// `draw_triangle`. Lines have no facing, so none are culled.
+pub(crate) fn draw_line<VaryingsT, ShadeT>(
+    target: &mut Framebuffer,
+    line: [ClipVertex<VaryingsT>; 2],
+    mut shade: ShadeT,
+) where
+    VaryingsT: Varyings,
+    ShadeT: FnMut(&VaryingsT) -> Option<Vec3f>,
+{
+    // Clip against all six planes, so the line is on screen and the steps below are bounded by the
+    // size of the target. Clip space is linear, so the varyings can be interpolated directly.
+    let (mut start, mut end) = (0.0f32, 1.0f32);
+    for axis in 0..3 {
+        for sign in [1.0, -1.0] {
+            let distances = line.map(|vertex| vertex.position.w + sign * vertex.position[axis]);
+            if distances[0] < 0.0 && distances[1] < 0.0 {
+                return;
+            }
+            if distances[0] < 0.0 || distances[1] < 0.0 {
+                let t = distances[0] / (distances[0] - distances[1]);
+                if distances[0] < 0.0 {
+                    start = start.max(t);
+                } else {
+                    end = end.min(t);
+                }
+            }
+        }
+    }
+    if start > end {
+        return;
+    }
+    let clip_at = |t: f32| ClipVertex {
+        position: line[0].position + (line[1].position - line[0].position) * t,
+        varyings: line[0].varyings.lerp(&line[1].varyings, t),
+    };
+    let line = [clip_at(start), clip_at(end)];
+
+    let width = target.width() as f32;
+    let height = target.height() as f32;
+    let screen = line.map(|clip| {
+        let inv_w = 1.0 / clip.position.w;
+        ScreenVertex {
+            x: (clip.position.x * inv_w + 1.0) * 0.5 * width,
+            y: (1.0 - clip.position.y * inv_w) * 0.5 * height,
+            z: (clip.position.z * inv_w + 1.0) * 0.5,
+            inv_w,
+        }
+    });
+
+    // Step one pixel at a time along the major axis.
+    let delta_x = screen[1].x - screen[0].x;
+    let delta_y = screen[1].y - screen[0].y;
+    let num_steps = delta_x.abs().max(delta_y.abs()).ceil();
+    if !num_steps.is_finite() {
+        return;
+    }
+    let num_steps = num_steps.max(1.0) as usize;
+    for step in 0..=num_steps {
+        let t = step as f32 / num_steps as f32;
+        let x = screen[0].x + delta_x * t;
+        let y = screen[0].y + delta_y * t;
+        if x < 0.0 || y < 0.0 || x >= width || y >= height {
+            continue;
+        }
+        let depth = screen[0].z + (screen[1].z - screen[0].z) * t;
+        if !(0.0..=1.0).contains(&depth) {
+            continue;
+        }
+        let index = if let Some(index) = target.depth_test(x as usize, y as usize, depth) {
+            index
+        } else {
+            continue;
+        };
+
+        let inv_w = screen[0].inv_w + (screen[1].inv_w - screen[0].inv_w) * t;
+        let interpolated = line[0]
+            .varyings
+            .lerp(&line[1].varyings, t * screen[1].inv_w / inv_w);
+        if let Some(color) = shade(&interpolated) {
+            target.write(index, color, depth);
+        }
+    }
+}
+
+fn draw_clipped<VaryingsT, ShadeT>(
+    target: &mut Framebuffer,
+    mut vertices: [&ScreenVertex; 3],
+    mut varyings: [&VaryingsT; 3],
+    shade: &mut ShadeT,
+) where
+    VaryingsT: Varyings,
+    ShadeT: FnMut(&VaryingsT) -> Option<Vec3f>,
+{
+    // With y pointing down, front-facing (counter-clockwise in OpenGL) triangles have negative
+    // area. Cull the others, then swap to positive area to make all edge functions positive.
+    let area = edge(vertices[0], vertices[1], vertices[2].x, vertices[2].y);
+    if area >= 0.0 || !area.is_finite() {
+        return;
+    }
+    vertices.swap(1, 2);
+    varyings.swap(1, 2);
+    let area = -area;
+
+    let min_x = vertices.iter().map(|v| v.x).fold(f32::INFINITY, f32::min);
+    let max_x = vertices
+        .iter()
+        .map(|v| v.x)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let min_y = vertices.iter().map(|v| v.y).fold(f32::INFINITY, f32::min);
+    let max_y = vertices
+        .iter()
+        .map(|v| v.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let x_range = pixel_range(min_x, max_x, target.width());
+    let y_range = pixel_range(min_y, max_y, target.height());
+
+    for y in y_range {
+        let center_y = y as f32 + 0.5;
+        for x in x_range.clone() {
+            let center_x = x as f32 + 0.5;
+            let weights = [
+                edge(vertices[1], vertices[2], center_x, center_y) / area,
+                edge(vertices[2], vertices[0], center_x, center_y) / area,
+                edge(vertices[0], vertices[1], center_x, center_y) / area,
+            ];
+            if weights.iter().any(|&weight| weight < 0.0) {
+                continue;
+            }
+            let depth = weights[0] * vertices[0].z
+                + weights[1] * vertices[1].z
+                + weights[2] * vertices[2].z;
+            if !(0.0..=1.0).contains(&depth) {
+                continue;
+            }
+            let index = if let Some(index) = target.depth_test(x, y, depth) {
+                index
+            } else {
+                continue;
+            };
+
+            let perspective = [
+                weights[0] * vertices[0].inv_w,
+                weights[1] * vertices[1].inv_w,
+                weights[2] * vertices[2].inv_w,
+            ];
+            let inv_sum = 1.0 / (perspective[0] + perspective[1] + perspective[2]);
+            let interpolated = VaryingsT::weighted_sum(
+                varyings,
+                [
+                    perspective[0] * inv_sum,
+                    perspective[1] * inv_sum,
+                    perspective[2] * inv_sum,
+                ],
+            );
+            if let Some(color) = shade(&interpolated) {
+                target.write(index, color, depth);
+            }
+        }
+    }
+}
+
+/// Clipping a triangle against one plane adds at most one vertex.
+const MAX_CLIPPED_VERTICES: usize = 4;
+
+#[derive(Copy, Clone, Default)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+}
+
+#[inline]
+fn edge(from: &ScreenVertex, to: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (to.x - from.x) * (y - from.y) - (to.y - from.y) * (x - from.x)
+}
+
+/// The pixels whose centres may lie between `min` and `max`, clamped to the target.
+fn pixel_range(min: f32, max: f32, size: u32) -> std::ops::Range<usize> {
+    let start = (min - 0.5).ceil().max(0.0) as usize;
+    let end = ((max - 0.5).floor() + 1.0).clamp(0.0, size as f32) as usize;
+    start..end.max(start)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{draw_line, draw_triangle, ClipVertex};
+    use crate::software::Framebuffer;
+    use math::{vec3, vec4, Vec4f};
+
+    fn vertex(position: Vec4f) -> ClipVertex<[f32; 1]> {
+        ClipVertex {
+            position,
+            varyings: [position.x],
+        }
+    }
+
+    #[test]
+    fn fills_front_faces_and_culls_back_faces() {
+        let mut target = Framebuffer::new(8, 8);
+        target.clear(vec3(0.0, 0.0, 0.0));
+        let front = [
+            vertex(vec4(-1.0, -1.0, 0.0, 1.0)),
+            vertex(vec4(1.0, -1.0, 0.0, 1.0)),
+            vertex(vec4(-1.0, 1.0, 0.0, 1.0)),
+        ];
+        draw_triangle(&mut target, [front[0], front[2], front[1]], |_| {
+            Some(vec3(0.0, 1.0, 0.0))
+        });
+        assert!(target.pixels().iter().all(|&pixel| pixel == [0, 0, 0]));
+
+        draw_triangle(&mut target, front, |_| Some(vec3(1.0, 0.0, 0.0)));
+        // Bottom-left pixel is covered, top-right is not.
+        assert_eq!(target.pixels()[7 * 8], [255, 0, 0]);
+        assert_eq!(target.pixels()[7], [0, 0, 0]);
+    }
+
+    #[test]
+    fn depth_test_keeps_nearest() {
+        let mut target = Framebuffer::new(4, 4);
+        target.clear(vec3(0.0, 0.0, 0.0));
+        let quad = |z: f32| {
+            [
+                vertex(vec4(-1.0, -1.0, z, 1.0)),
+                vertex(vec4(3.0, -1.0, z, 1.0)),
+                vertex(vec4(-1.0, 3.0, z, 1.0)),
+            ]
+        };
+        draw_triangle(&mut target, quad(-0.5), |_| Some(vec3(0.0, 0.0, 1.0)));
+        draw_triangle(&mut target, quad(0.5), |_| Some(vec3(1.0, 0.0, 0.0)));
+        assert!(target.pixels().iter().all(|&pixel| pixel == [0, 0, 255]));
+    }
+
+    #[test]
+    fn clips_against_near_plane() {
+        let mut target = Framebuffer::new(4, 4);
+        target.clear(vec3(0.0, 0.0, 0.0));
+        // One vertex is behind the camera (w < 0); the rest of the triangle is still drawn.
+        let triangle = [
+            vertex(vec4(-1.0, -1.0, 0.0, 1.0)),
+            vertex(vec4(1.0, -1.0, 0.0, 1.0)),
+            vertex(vec4(0.0, 2.0, -3.0, -1.0)),
+        ];
+        let mut num_fragments = 0;
+        draw_triangle(&mut target, triangle, |varyings| {
+            assert!(varyings[0] >= -1.0 && varyings[0] <= 1.0);
+            num_fragments += 1;
+            Some(vec3(1.0, 1.0, 1.0))
+        });
+        assert!(num_fragments > 0);
+    }
+
+    #[test]
+    fn draws_lines_in_front_of_geometry() {
+        let mut target = Framebuffer::new(8, 8);
+        target.clear(vec3(0.0, 0.0, 0.0));
+        let quad = [
+            vertex(vec4(-1.0, -1.0, 0.5, 1.0)),
+            vertex(vec4(3.0, -1.0, 0.5, 1.0)),
+            vertex(vec4(-1.0, 3.0, 0.5, 1.0)),
+        ];
+        draw_triangle(&mut target, quad, |_| Some(vec3(0.0, 0.0, 1.0)));
+
+        // A horizontal line across the middle row, from the left edge to the right edge.
+        let line = [
+            vertex(vec4(-1.0, 0.1, -0.5, 1.0)),
+            vertex(vec4(1.0, 0.1, -0.5, 1.0)),
+        ];
+        let mut num_fragments = 0;
+        draw_line(&mut target, line, |varyings| {
+            assert!(varyings[0] >= -1.0 && varyings[0] <= 1.0);
+            num_fragments += 1;
+            Some(vec3(1.0, 0.0, 0.0))
+        });
+        assert_eq!(num_fragments, 8);
+        assert!(target.pixels()[3 * 8..4 * 8]
+            .iter()
+            .all(|&pixel| pixel == [255, 0, 0]));
+        assert_eq!(target.pixels()[0], [0, 0, 255]);
+
+        // Far off screen endpoints are clipped, rather than stepped through.
+        let long = [
+            vertex(vec4(-1e7, -0.6, -0.5, 1.0)),
+            vertex(vec4(1e7, -0.6, -0.5, 1.0)),
+        ];
+        let mut num_fragments = 0;
+        draw_line(&mut target, long, |varyings| {
+            assert!(varyings[0] >= -1.0 && varyings[0] <= 1.0);
+            num_fragments += 1;
+            Some(vec3(1.0, 0.0, 0.0))
+        });
+        assert_eq!(num_fragments, 8);
+
+        // Behind the quad, nothing is drawn.
+        let hidden = [
+            vertex(vec4(-1.0, -0.6, 0.9, 1.0)),
+            vertex(vec4(1.0, -0.6, 0.9, 1.0)),
+        ];
+        draw_line(&mut target, hidden, |_| Some(vec3(1.0, 1.0, 1.0)));
+        assert!(target
+            .pixels()
+            .iter()
+            .all(|&pixel| pixel != [255, 255, 255]));
+    }
+}
@@ -1,21 +1,34 @@
+use super::capture::Capture;
 use super::errors::{Error, ErrorKind, Result};
+use super::frame_timers::{FrameCounterId, FrameTimers};
 use super::materials::Materials;
 use super::meshes::Meshes;
-use super::pipeline::{Model, RenderPipeline};
+use super::pipeline::{Model, RenderPass, RenderPipeline};
 use super::projections::Projections;
 use super::shaders::Shaders;
+use super::software::Framebuffer;
 use super::system::System;
 use super::text::TextRenderer;
 use super::tick::Tick;
 use super::transforms::Transforms;
 use super::uniforms::Uniforms;
-use super::window::Window;
+use super::window::{Window, CLEAR_COLOR};
 use crate::internal_derive::DependenciesFrom;
 use failchain::ResultExt;
-use glium::{BackfaceCullingMode, Depth, DepthTest, DrawParameters, Surface};
-use log::{error, info};
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::program::ProgramCreationInput;
+use glium::texture::{DepthFormat, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::vertex::EmptyVertexAttributes;
+use glium::{
+    uniform, BackfaceCullingMode, Depth, DepthTest, Display, DrawParameters, Frame, PolygonMode,
+    Program, Surface,
+};
+use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::Mat4;
+use math::{vec3, Frustum, Mat4, Trans3};
+use std::ops::Range;
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
@@ -24,16 +37,26 @@ pub struct Dependencies<'context> {
     materials: &'context Materials,
     shaders: &'context Shaders,
     text: &'context TextRenderer,
-    window: &'context Window,
+    window: &'context mut Window,
     transforms: &'context Transforms,
     projections: &'context Projections,
     uniforms: &'context mut Uniforms,
     tick: &'context Tick,
+    frame_timers: &'context mut FrameTimers,
+    capture: &'context mut Capture,
 }
 
 pub struct Renderer {
-    draw_parameters: DrawParameters<'static>,
-    removed: Vec<usize>,
+    drawer: ModelDrawer,
+    culled_leaves_counter: FrameCounterId,
+
+    /// Stretches the output of the render passes over the window; `None` with the software
+    /// backend.
+    blit_program: Option<Program>,
+    /// The targets of the render passes, by index, kept while their sizes don't change.
+    targets: Vec<GpuTarget>,
+    software_targets: Vec<Framebuffer>,
+    warned_post_process: bool,
 }
 
 impl<'context> System<'context> for Renderer {
@@ -44,28 +67,63 @@ impl<'context> System<'context> for Renderer {
         "renderer"
     }
 
-    fn create(_deps: Dependencies) -> Result<Self> {
+    fn create(deps: Dependencies) -> Result<Self> {
+        let blit_program = deps
+            .window
+            .facade()
+            .map(|facade| {
+                Program::new(
+                    facade,
+                    ProgramCreationInput::SourceCode {
+                        vertex_shader: BLIT_VERTEX_SRC,
+                        tessellation_control_shader: None,
+                        tessellation_evaluation_shader: None,
+                        geometry_shader: None,
+                        fragment_shader: BLIT_FRAGMENT_SRC,
+                        transform_feedback_varyings: None,
+                        // Like the shaders loaded by `Shaders`, pass colours through unchanged.
+                        outputs_srgb: true,
+                        uses_point_size: false,
+                    },
+                )
+            })
+            .transpose()
+            .map_err(ErrorKind::glium("blit"))?;
         Ok(Renderer {
-            draw_parameters: DrawParameters {
-                depth: Depth {
-                    test: DepthTest::IfLess,
-                    write: true,
-                    ..Depth::default()
+            drawer: ModelDrawer {
+                draw_parameters: DrawParameters {
+                    depth: Depth {
+                        test: DepthTest::IfLess,
+                        write: true,
+                        ..Depth::default()
+                    },
+                    backface_culling: BackfaceCullingMode::CullClockwise,
+                    ..DrawParameters::default()
                 },
-                backface_culling: BackfaceCullingMode::CullClockwise,
-                ..DrawParameters::default()
+                removed: Vec::with_capacity(32),
+                visible_ranges: Vec::with_capacity(1024),
             },
-            removed: Vec::with_capacity(32),
+            culled_leaves_counter: deps.frame_timers.new_counter("culled_leaves"),
+            blit_program,
+            targets: Vec::new(),
+            software_targets: Vec::new(),
+            warned_post_process: false,
         })
     }
 
     fn update(&mut self, deps: Dependencies) -> Result<()> {
-        // If the current tick isn't a frame, skip all rendering.
-        if !deps.tick.is_frame() {
+        // If the current tick isn't a frame, skip all rendering. Frames being recorded ignore the
+        // frame cap, so none are dropped.
+        if !deps.tick.is_frame() || !(deps.capture.is_recording() || deps.window.frame_due()) {
             return Ok(());
         }
 
         let pipe = deps.pipe;
+        self.drawer.draw_parameters.polygon_mode = if pipe.wireframe {
+            PolygonMode::Line
+        } else {
+            PolygonMode::Fill
+        };
 
         // If no camera is given, skip rendering.
         let camera_id = if let Some(camera_id) = pipe.camera {
@@ -84,19 +142,284 @@ impl<'context> System<'context> for Renderer {
             pipe.camera = None;
             return Ok(());
         };
-        let view_matrix = view_transform.into();
 
         // Set projection.
-        *deps
-            .uniforms
-            .get_mat4_mut(pipe.projection)
-            .expect("projection uniform missing") = *deps
+        let projection = *deps
             .projections
             .get_matrix(camera_id)
             .expect("camera projection missing");
+        *deps
+            .uniforms
+            .get_mat4_mut(pipe.projection)
+            .expect("projection uniform missing") = projection;
+
+        // Render all the models, either straight into the window or through the render passes.
+        let mut scene = Scene {
+            pipe: &*pipe,
+            meshes: deps.meshes,
+            materials: deps.materials,
+            shaders: deps.shaders,
+            transforms: deps.transforms,
+            uniforms: deps.uniforms,
+            view_transform,
+            projection,
+        };
+        let window = deps.window;
+        let window_size = (window.width(), window.height());
+        let num_culled_leaves = if let Some(mut frame) = window.draw() {
+            let facade = window.facade().expect("no display with the gpu backend");
+            let num_culled_leaves = if scene.pipe.passes.is_empty() {
+                self.drawer.draw(&mut scene, Target::Gpu(&mut frame))?
+            } else {
+                self.draw_passes(&mut scene, facade, window_size, &mut frame)?
+            };
+
+            // Render text. TODO(cristicbz): text should render itself :(
+            deps.text
+                .render(&mut frame)
+                .chain_err(|| ErrorKind::System("render bypass", TextRenderer::debug_name()))?;
+
+            // TODO(cristicbz): Re-architect a little bit to support rebuilding the context.
+            frame
+                .finish()
+                .expect("Cannot handle context loss currently :(");
+            num_culled_leaves
+        } else {
+            let framebuffer = window
+                .draw_software()
+                .expect("no framebuffer with the software backend");
+            let num_culled_leaves = if scene.pipe.passes.is_empty() {
+                self.drawer
+                    .draw(&mut scene, Target::<Frame>::Software(framebuffer))?
+            } else {
+                self.draw_passes_software(&mut scene, window_size, framebuffer)?
+            };
+            deps.text.render_software(framebuffer);
+            num_culled_leaves
+        };
+        deps.frame_timers
+            .record(self.culled_leaves_counter, num_culled_leaves as u64);
+        deps.capture.frame_rendered(window);
+
+        // Remove any missing models. Each scene pass finds the same ones, so remove them only once,
+        // from the highest index down.
+        self.drawer.removed.sort_unstable();
+        self.drawer.removed.dedup();
+        for &index in self.drawer.removed.iter().rev() {
+            if let Some(entity) = pipe.models.index_to_id(index) {
+                pipe.culling.remove(entity);
+            }
+            pipe.models.remove_by_index(index);
+        }
+        self.drawer.removed.clear();
+        Ok(())
+    }
+}
+
+impl Renderer {
+    /// Draws each render pass into its offscreen target, then stretches the last target over the
+    /// window. Returns the number of culled leaves.
+    fn draw_passes(
+        &mut self,
+        scene: &mut Scene,
+        facade: &Display,
+        (window_width, window_height): (u32, u32),
+        frame: &mut Frame,
+    ) -> Result<usize> {
+        let pipe = scene.pipe;
+        let (red, green, blue) = CLEAR_COLOR;
+        let mut num_culled_leaves = 0;
+        self.targets.truncate(pipe.passes.len());
+        for (index, pass) in pipe.passes.iter().enumerate() {
+            let size = pass.size().resolve(window_width, window_height);
+            let with_depth = matches!(pass, RenderPass::Scene { .. });
+            let reuse = self
+                .targets
+                .get(index)
+                .is_some_and(|target| target.size == size && target.depth.is_some() == with_depth);
+            if !reuse {
+                debug!(
+                    "Creating {}x{} target for render pass {}.",
+                    size.0, size.1, index
+                );
+                let target = GpuTarget::new(facade, size, with_depth)?;
+                if index < self.targets.len() {
+                    self.targets[index] = target;
+                } else {
+                    self.targets.push(target);
+                }
+            }
+
+            let target = &self.targets[index];
+            match *pass {
+                RenderPass::Scene { .. } => {
+                    let depth = target.depth.as_ref().expect("scene target without depth");
+                    let mut framebuffer =
+                        SimpleFrameBuffer::with_depth_buffer(facade, &target.color, depth)
+                            .map_err(ErrorKind::glium("scene pass"))?;
+                    framebuffer.clear_color_srgb_and_depth((red, green, blue, 0.0), 1.0);
+                    num_culled_leaves += self.drawer.draw(scene, Target::Gpu(&mut framebuffer))?;
+                }
+                RenderPass::PostProcess { shader, filter, .. } => {
+                    let program = if let Some(program) = scene.shaders.get(shader) {
+                        program
+                    } else {
+                        error!(
+                            "Shader {:?} missing in post-process pass, skipping.",
+                            shader
+                        );
+                        continue;
+                    };
+                    // `set_passes` makes sure the first pass draws the scene.
+                    let source = &self.targets[index - 1];
+                    let uniforms = uniform! {
+                        u_source: sampled(&source.color, filter),
+                        u_source_size: [source.size.0 as f32, source.size.1 as f32],
+                        u_target_size: [size.0 as f32, size.1 as f32],
+                    };
+                    SimpleFrameBuffer::new(facade, &target.color)
+                        .map_err(ErrorKind::glium("post-process pass"))?
+                        .draw(
+                            EmptyVertexAttributes { len: 3 },
+                            NoIndices(PrimitiveType::TrianglesList),
+                            program,
+                            &uniforms,
+                            &DrawParameters::default(),
+                        )
+                        .map_err(ErrorKind::glium("post-process pass"))?;
+                }
+            }
+        }
+
+        let (last, program) = match (self.targets.last(), self.blit_program.as_ref()) {
+            (Some(last), Some(program)) => (last, program),
+            _ => return Ok(num_culled_leaves),
+        };
+        frame
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                NoIndices(PrimitiveType::TrianglesList),
+                program,
+                &uniform! { u_source: sampled(&last.color, pipe.blit_filter) },
+                &DrawParameters::default(),
+            )
+            .map_err(ErrorKind::glium("blit"))?;
+        Ok(num_culled_leaves)
+    }
+
+    /// The software version of `draw_passes`, which only supports scene passes.
+    fn draw_passes_software(
+        &mut self,
+        scene: &mut Scene,
+        (window_width, window_height): (u32, u32),
+        window_framebuffer: &mut Framebuffer,
+    ) -> Result<usize> {
+        let pipe = scene.pipe;
+        let (red, green, blue) = CLEAR_COLOR;
+        let mut num_culled_leaves = 0;
+        let mut last = None;
+        self.software_targets.truncate(pipe.passes.len());
+        for (index, pass) in pipe.passes.iter().enumerate() {
+            let size = if let RenderPass::Scene { size } = *pass {
+                size.resolve(window_width, window_height)
+            } else {
+                if !self.warned_post_process {
+                    warn!("Post-processing is not supported by the software renderer, skipping.");
+                    self.warned_post_process = true;
+                }
+                continue;
+            };
+            if index >= self.software_targets.len() {
+                self.software_targets
+                    .resize_with(index + 1, || Framebuffer::new(1, 1));
+            }
+            let target = &mut self.software_targets[index];
+            if (target.width(), target.height()) != size {
+                *target = Framebuffer::new(size.0, size.1);
+            }
+            target.clear(vec3(red, green, blue));
+            num_culled_leaves += self.drawer.draw(scene, Target::<Frame>::Software(target))?;
+            last = Some(index);
+        }
+        if let Some(last) = last {
+            self.software_targets[last].stretch_into(window_framebuffer, pipe.blit_filter);
+        }
+        Ok(num_culled_leaves)
+    }
+}
+
+/// The colour (and depth, for scene passes) textures a render pass draws into.
+struct GpuTarget {
+    size: (u32, u32),
+    color: Texture2d,
+    depth: Option<DepthRenderBuffer>,
+}
+
+impl GpuTarget {
+    fn new(facade: &Display, (width, height): (u32, u32), with_depth: bool) -> Result<Self> {
+        let color = Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .map_err(ErrorKind::glium("render pass target"))?;
+        let depth = if with_depth {
+            Some(
+                DepthRenderBuffer::new(facade, DepthFormat::I24, width, height)
+                    .map_err(ErrorKind::glium("render pass target"))?,
+            )
+        } else {
+            None
+        };
+        Ok(GpuTarget {
+            size: (width, height),
+            color,
+            depth,
+        })
+    }
+}
 
-        // Render all the models in turn.
-        let mut frame = deps.window.draw();
+/// Samples the output of a render pass with `filter`, clamped at the edges.
+fn sampled(texture: &Texture2d, filter: MagnifySamplerFilter) -> Sampler<'_, Texture2d> {
+    let minify_filter = match filter {
+        MagnifySamplerFilter::Nearest => MinifySamplerFilter::Nearest,
+        MagnifySamplerFilter::Linear => MinifySamplerFilter::Linear,
+    };
+    texture
+        .sampled()
+        .magnify_filter(filter)
+        .minify_filter(minify_filter)
+        .wrap_function(SamplerWrapFunction::Clamp)
+}
+
+/// The models to draw and how to view them, borrowed from the renderer's dependencies.
+struct Scene<'a> {
+    pipe: &'a RenderPipeline,
+    meshes: &'a Meshes,
+    materials: &'a Materials,
+    shaders: &'a Shaders,
+    transforms: &'a Transforms,
+    uniforms: &'a mut Uniforms,
+    view_transform: Trans3,
+    projection: Mat4,
+}
+
+/// Draws the models of the pipeline into a target, remembering which ones are missing parts so
+/// they can be removed afterwards.
+struct ModelDrawer {
+    draw_parameters: DrawParameters<'static>,
+    removed: Vec<usize>,
+    visible_ranges: Vec<Range<usize>>,
+}
+
+impl ModelDrawer {
+    /// Draws all the models, returning the number of culled leaves.
+    fn draw<S: Surface>(&mut self, scene: &mut Scene, mut target: Target<S>) -> Result<usize> {
+        let pipe = scene.pipe;
+        let view_matrix = Mat4::from(scene.view_transform);
+        let mut num_culled_leaves = 0;
         for (index, &Model { mesh, material }) in pipe.models.access().iter().enumerate() {
             // For each model we need to assemble three things to render it: transform, mesh and
             // material. We get the entity id and query the corresponding systems for it.
@@ -107,7 +430,7 @@ impl<'context> System<'context> for Renderer {
 
             // If the mesh is missing, the entity was (probably) removed. So we add it to the
             // removed stack and continue.
-            let mesh = if let Some(mesh) = deps.meshes.get(mesh) {
+            let mesh = if let Some(mesh) = scene.meshes.get(mesh) {
                 mesh
             } else {
                 info!(
@@ -121,56 +444,117 @@ impl<'context> System<'context> for Renderer {
             // If the model has a transform, then multiply it with the view transform to get the
             // modelview matrix. If there is no transform, model is assumed to be in world space, so
             // modelview = view.
-            *deps
+            let modelview = if let Some(model_transform) = scene.transforms.get_absolute(entity) {
+                Mat4::from(scene.view_transform.concat(model_transform))
+            } else {
+                view_matrix
+            };
+            *scene
                 .uniforms
                 .get_mat4_mut(pipe.modelview)
-                .expect("modelview uniform missing") =
-                if let Some(model_transform) = deps.transforms.get_absolute(entity) {
-                    Mat4::from(view_transform.concat(model_transform))
-                } else {
-                    view_matrix
-                };
+                .expect("modelview uniform missing") = modelview;
 
-            let material =
-                if let Some(material) = deps.materials.get(deps.shaders, deps.uniforms, material) {
-                    material
-                } else {
-                    // If there is a mesh but no material, the model is badly set up. This is an
-                    // error.
-                    error!(
-                        "Material missing {:?} in model for entity {:?}, removing.",
-                        material, entity
-                    );
-                    self.removed.push(index);
-                    continue;
-                };
+            // If the model has a culling tree, only draw the index ranges in the view frustum.
+            let culling = match (pipe.culling.get(entity), mesh.num_indices()) {
+                (Some(culling), Some(num_indices)) => Some((culling, num_indices)),
+                _ => None,
+            };
+            self.visible_ranges.clear();
+            let culled = if let Some((culling, num_indices)) = culling {
+                let frustum = Frustum::from_matrix(&(scene.projection * modelview));
+                num_culled_leaves +=
+                    culling.visible_ranges(&frustum, num_indices, &mut self.visible_ranges);
+                true
+            } else {
+                false
+            };
+            let ranges = if culled {
+                Some(&self.visible_ranges[..])
+            } else {
+                None
+            };
 
-            frame
-                .draw(
-                    &mesh,
-                    &mesh,
-                    material.shader(),
-                    &material,
-                    &self.draw_parameters,
-                )
-                .map_err(ErrorKind::glium("renderer"))?;
+            let drawn = match target {
+                Target::Gpu(ref mut frame) => scene
+                    .materials
+                    .get(scene.shaders, scene.uniforms, material)
+                    .map(|material| -> Result<()> {
+                        let (vertices, all_indices) =
+                            mesh.gpu_sources().ok_or(ErrorKind::MeshBackendMismatch {
+                                needed_by: "renderer",
+                            })?;
+                        let ranges = if let Some(ranges) = ranges {
+                            ranges
+                        } else {
+                            return frame
+                                .draw(
+                                    vertices,
+                                    all_indices,
+                                    material.shader(),
+                                    &material,
+                                    &self.draw_parameters,
+                                )
+                                .map_err(ErrorKind::glium("renderer"));
+                        };
+                        for range in ranges {
+                            let indices = mesh
+                                .index_slice(range.clone())
+                                .expect("culling range out of bounds");
+                            frame
+                                .draw(
+                                    vertices.clone(),
+                                    indices,
+                                    material.shader(),
+                                    &material,
+                                    &self.draw_parameters,
+                                )
+                                .map_err(ErrorKind::glium("renderer"))?;
+                        }
+                        Ok(())
+                    }),
+                Target::Software(ref mut framebuffer) => scene
+                    .materials
+                    .get_software(scene.shaders, scene.uniforms, material)
+                    .map(|material| material.draw(framebuffer, &mesh, ranges, pipe.wireframe)),
+            };
+            if let Some(result) = drawn {
+                result?;
+            } else {
+                // If there is a mesh but no material, the model is badly set up. This is an
+                // error.
+                error!(
+                    "Material missing {:?} in model for entity {:?}, removing.",
+                    material, entity
+                );
+                self.removed.push(index);
+            }
         }
+        Ok(num_culled_leaves)
+    }
+}
 
-        // Render text. TODO(cristicbz): text should render itself :(
-        deps.text
-            .render(&mut frame)
-            .chain_err(|| ErrorKind::System("render bypass", TextRenderer::debug_name()))?;
+/// What models are drawn into, depending on the window's backend.
+enum Target<'a, S> {
+    Gpu(&'a mut S),
+    Software(&'a mut Framebuffer),
+}
 
-        // TODO(cristicbz): Re-architect a little bit to support rebuilding the context.
-        frame
-            .finish()
-            .expect("Cannot handle context loss currently :(");
+/// Stretches the output of the last render pass over the window, with a triangle covering it.
+const BLIT_VERTEX_SRC: &str = r#"
+    #version 140
+    out vec2 v_uv;
+    void main() {
+        v_uv = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+        gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+    }
+"#;
 
-        // Remove any missing models.
-        for &index in self.removed.iter().rev() {
-            pipe.models.remove_by_index(index);
-        }
-        self.removed.clear();
-        Ok(())
+const BLIT_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_source;
+    in vec2 v_uv;
+    out vec4 color;
+    void main() {
+        color = vec4(texture(u_source, v_uv).rgb, 1.0);
     }
-}
+"#;
@@ -0,0 +1,52 @@
+use super::errors::{ErrorKind, Result};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// An RGB image read back from a rendered frame.
+pub struct Screenshot {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Screenshot {
+    /// Creates a screenshot from `width * height` pixels, with rows from top to bottom.
+    pub(crate) fn new(width: u32, height: u32, pixels: Vec<[u8; 3]>) -> Self {
+        assert_eq!(pixels.len(), width as usize * height as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pixels, with rows from top to bottom.
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+
+    /// Encodes the screenshot as a PNG file at `path`.
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        let error = |error: &dyn std::fmt::Display| {
+            ErrorKind::Capture(format!("Cannot write `{}`: {}", path.display(), error))
+        };
+        let file = File::create(path).map_err(|e| error(&e))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+        let data: Vec<u8> = self.pixels.iter().flatten().cloned().collect();
+        writer.write_image_data(&data).map_err(|e| error(&e))?;
+        writer.finish().map_err(|e| error(&e))?;
+        Ok(())
+    }
+}
@@ -1,11 +1,12 @@
 use super::entities::{Entities, Entity, EntityId};
 use super::errors::{ErrorKind, Result};
 use super::platform;
+use super::software::{DrawSoftware, SoftwareShaders};
 use super::system::InfallibleSystem;
 use super::window::Window;
 use crate::internal_derive::DependenciesFrom;
 
-use failchain::ResultExt;
+use failchain::{bail, ResultExt};
 use glium::program::{Program, ProgramCreationInput};
 use idcontain::IdMapVec;
 use log::{debug, error};
@@ -13,17 +14,21 @@ use std::fs::File;
 use std::io::Read;
 use std::io::Result as IoResult;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct ShaderId(pub EntityId);
 
 pub struct ShaderConfig {
     pub root_path: PathBuf,
+    /// CPU implementations of the shaders, used with the software backend.
+    pub software: SoftwareShaders,
 }
 
 pub struct Shaders {
     map: IdMapVec<Entity, Shader>,
     root: PathBuf,
+    software: SoftwareShaders,
 }
 
 impl Shaders {
@@ -35,6 +40,26 @@ impl Shaders {
         name: &'static str,
         asset_path: &'static str,
     ) -> Result<ShaderId> {
+        let facade = if let Some(facade) = window.facade() {
+            facade
+        } else {
+            let software = if let Some(software) = self.software.get(asset_path) {
+                software
+            } else {
+                bail!(ErrorKind::SoftwareRenderer(format!(
+                    "no implementation of shader {:?} (from {})",
+                    name, asset_path
+                )));
+            };
+            let id = entities.add(parent, name)?;
+            self.map.insert(id, Shader::Software(software));
+            debug!(
+                "Added software shader {:?} {:?} as child of {:?}.",
+                name, id, parent
+            );
+            return Ok(ShaderId(id));
+        };
+
         let mut fragment_path = self.root.clone();
         fragment_path.push(asset_path);
 
@@ -55,7 +80,7 @@ impl Shaders {
             .chain_err(|| ErrorKind::ResourceIo("vertex shader", name))?;
 
         let program = Program::new(
-            window.facade(),
+            facade,
             ProgramCreationInput::SourceCode {
                 vertex_shader: &vertex_source,
                 tessellation_control_shader: None,
@@ -71,18 +96,30 @@ impl Shaders {
         .map_err(ErrorKind::glium(name))?;
         debug!("Shader {:?} loaded successfully", name);
         let id = entities.add(parent, name)?;
-        self.map.insert(id, Shader { program });
+        self.map.insert(id, Shader::Gpu(program));
         debug!("Added shader {:?} {:?} as child of {:?}.", name, id, parent);
         Ok(ShaderId(id))
     }
 
     pub fn get(&self, shader_id: ShaderId) -> Option<&Program> {
-        self.map.get(shader_id.0).map(|shader| &shader.program)
+        match self.map.get(shader_id.0)? {
+            Shader::Gpu(program) => Some(program),
+            Shader::Software(_) => None,
+        }
+    }
+
+    pub(crate) fn get_software(&self, shader_id: ShaderId) -> Option<&dyn DrawSoftware> {
+        match self.map.get(shader_id.0)? {
+            Shader::Software(software) => Some(&**software),
+            Shader::Gpu(_) => None,
+        }
     }
 }
 
-pub struct Shader {
-    program: Program,
+#[allow(clippy::large_enum_variant)]
+enum Shader {
+    Gpu(Program),
+    Software(Rc<dyn DrawSoftware>),
 }
 
 #[derive(DependenciesFrom)]
@@ -102,6 +139,7 @@ impl<'context> InfallibleSystem<'context> for Shaders {
         Shaders {
             map: IdMapVec::with_capacity(32),
             root: deps.config.root_path.clone(),
+            software: deps.config.software.clone(),
         }
     }
 
@@ -0,0 +1,579 @@
+use super::errors::{ErrorKind, Result};
+use super::materials::UniformValues;
+use super::meshes::{MeshRef, Primitives};
+use super::rasteriser::{self, ClipVertex};
+use failchain::bail;
+use glium::texture::ClientFormat;
+use glium::uniforms::{MagnifySamplerFilter, SamplerBehavior, SamplerWrapFunction};
+use math::{vec4, Mat4, Vec2, Vec2f, Vec3f, Vec4f};
+use std::any;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// A CPU implementation of a GLSL shader program, used by the software backend.
+///
+/// Register implementations with `SoftwareShaders`, keyed by the same asset path the GLSL
+/// version is loaded from.
+pub trait SoftwareShader: 'static {
+    /// The vertex type of the meshes drawn with this shader.
+    type Vertex: 'static;
+
+    /// Uniform values looked up once per draw call.
+    type Uniforms<'a>;
+
+    /// Values interpolated (perspective-correctly) across a triangle or line.
+    type Varyings: Varyings;
+
+    /// Values taken from the last vertex of each primitive, like GLSL `flat` outputs.
+    type Flat: Copy;
+
+    /// Looks up the uniforms needed by the shader, or returns `None` if any is missing.
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<Self::Uniforms<'a>>;
+
+    /// Returns the clip-space position of the vertex along with its outputs.
+    fn vertex(
+        &self,
+        uniforms: &Self::Uniforms<'_>,
+        vertex: &Self::Vertex,
+    ) -> (Vec4f, Self::Varyings, Self::Flat);
+
+    /// Returns the colour of a fragment, or `None` to discard it.
+    fn fragment(
+        &self,
+        uniforms: &Self::Uniforms<'_>,
+        varyings: &Self::Varyings,
+        flat: &Self::Flat,
+    ) -> Option<Vec3f>;
+}
+
+/// Vertex shader outputs which can be interpolated across a triangle.
+pub trait Varyings: Copy {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+    fn weighted_sum(values: [&Self; 3], weights: [f32; 3]) -> Self;
+}
+
+impl<const N: usize> Varyings for [f32; N] {
+    #[inline]
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mut result = *self;
+        for (value, &other) in result.iter_mut().zip(other) {
+            *value += (other - *value) * t;
+        }
+        result
+    }
+
+    #[inline]
+    fn weighted_sum(values: [&Self; 3], weights: [f32; 3]) -> Self {
+        let mut result = [0.0; N];
+        for (i, value) in result.iter_mut().enumerate() {
+            *value =
+                values[0][i] * weights[0] + values[1][i] * weights[1] + values[2][i] * weights[2];
+        }
+        result
+    }
+}
+
+/// The software implementations of shaders, keyed by asset path.
+#[derive(Clone, Default)]
+pub struct SoftwareShaders {
+    map: HashMap<&'static str, Rc<dyn DrawSoftware>>,
+}
+
+impl SoftwareShaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<ShaderT: SoftwareShader>(
+        mut self,
+        asset_path: &'static str,
+        shader: ShaderT,
+    ) -> Self {
+        self.map.insert(asset_path, Rc::new(shader));
+        self
+    }
+
+    pub(crate) fn get(&self, asset_path: &str) -> Option<Rc<dyn DrawSoftware>> {
+        self.map.get(asset_path).cloned()
+    }
+}
+
+/// An RGB framebuffer with a depth buffer, drawn into by the software backend.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 3]; len],
+            depth: vec![1.0; len],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pixels of the framebuffer, in rows from top to bottom.
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+
+    pub(crate) fn clear(&mut self, color: Vec3f) {
+        let color = to_rgb(color);
+        for pixel in &mut self.pixels {
+            *pixel = color;
+        }
+        for depth in &mut self.depth {
+            *depth = 1.0;
+        }
+    }
+
+    /// Returns the index of the pixel at `(x, y)` if `depth` passes the depth test there.
+    #[inline]
+    pub(crate) fn depth_test(&self, x: usize, y: usize, depth: f32) -> Option<usize> {
+        let index = y * self.width as usize + x;
+        if depth < self.depth[index] {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn write(&mut self, index: usize, color: Vec3f, depth: f32) {
+        self.pixels[index] = to_rgb(color);
+        self.depth[index] = depth;
+    }
+
+    /// Alpha-blends `color` over the pixel at `(x, y)`, ignoring depth.
+    pub(crate) fn blend(&mut self, x: usize, y: usize, color: Vec3f, alpha: f32) {
+        if x >= self.width as usize || y >= self.height as usize {
+            return;
+        }
+        let pixel = &mut self.pixels[y * self.width as usize + x];
+        let blended = Vec3f::new(
+            f32::from(pixel[0]) / 255.0,
+            f32::from(pixel[1]) / 255.0,
+            f32::from(pixel[2]) / 255.0,
+        ) * (1.0 - alpha)
+            + color * alpha;
+        *pixel = to_rgb(blended);
+    }
+
+    /// Stretches the colour of this framebuffer over all of `target`, for the software version of
+    /// the final blit of the render passes.
+    pub(crate) fn stretch_into(&self, target: &mut Framebuffer, filter: MagnifySamplerFilter) {
+        let (source_width, source_height) = (self.width as usize, self.height as usize);
+        let scale = (
+            self.width as f32 / target.width as f32,
+            self.height as f32 / target.height as f32,
+        );
+        // The source texel coordinates sampled for each target row or column, measured between
+        // texel centres.
+        let source_coordinate = |target: usize, scale: f32| (target as f32 + 0.5) * scale - 0.5;
+        let texel = |x: usize, y: usize| {
+            let [red, green, blue] =
+                self.pixels[y.min(source_height - 1) * source_width + x.min(source_width - 1)];
+            Vec3f::new(f32::from(red), f32::from(green), f32::from(blue)) / 255.0
+        };
+        let target_width = target.width as usize;
+        for (y, row) in target.pixels.chunks_mut(target_width).enumerate() {
+            let source_y = source_coordinate(y, scale.1).max(0.0);
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let source_x = source_coordinate(x, scale.0).max(0.0);
+                *pixel = match filter {
+                    MagnifySamplerFilter::Nearest => {
+                        self.pixels[(source_y.round() as usize).min(source_height - 1)
+                            * source_width
+                            + (source_x.round() as usize).min(source_width - 1)]
+                    }
+                    MagnifySamplerFilter::Linear => {
+                        let (x0, y0) = (source_x as usize, source_y as usize);
+                        let (tx, ty) = (source_x.fract(), source_y.fract());
+                        let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1, y0) * tx;
+                        let bottom = texel(x0, y0 + 1) * (1.0 - tx) + texel(x0 + 1, y0 + 1) * tx;
+                        to_rgb(top * (1.0 - ty) + bottom * ty)
+                    }
+                };
+            }
+        }
+    }
+}
+
+/// Pixel types which can be uploaded to a software texture.
+pub trait SoftwarePixel {
+    /// Appends the channels of the pixel, in `ClientFormat` order.
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+impl SoftwarePixel for u8 {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self);
+    }
+}
+
+impl SoftwarePixel for u16 {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        // Matches how OpenGL reads a `U8U8` texture from `u16`-s on little-endian machines.
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl<const N: usize> SoftwarePixel for [u8; N] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self);
+    }
+}
+
+impl SoftwarePixel for (u8, u8) {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.0, self.1]);
+    }
+}
+
+impl SoftwarePixel for (u8, u8, u8) {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.0, self.1, self.2]);
+    }
+}
+
+impl SoftwarePixel for (u8, u8, u8, u8) {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.0, self.1, self.2, self.3]);
+    }
+}
+
+/// The CPU copy of a 2D texture (or array of `layers` 2D textures), with 8-bit normalised
+/// channels.
+pub(crate) struct SoftwareTexture {
+    size: Vec2<usize>,
+    layers: usize,
+    channels: usize,
+    texels: Vec<u8>,
+}
+
+impl SoftwareTexture {
+    pub(crate) fn new<PixelT: SoftwarePixel>(
+        name: &'static str,
+        pixels: &[PixelT],
+        size: Vec2<usize>,
+        layers: usize,
+        format: ClientFormat,
+    ) -> Result<Self> {
+        let channels = match format {
+            ClientFormat::U8 => 1,
+            ClientFormat::U8U8 => 2,
+            ClientFormat::U8U8U8 => 3,
+            ClientFormat::U8U8U8U8 => 4,
+            _ => bail!(ErrorKind::UnsupportedFeature {
+                needed_by: format!("software texture {:?} with format {:?}", name, format),
+            }),
+        };
+        let len = size[0] * size[1] * layers * channels;
+        let mut texels = Vec::with_capacity(len);
+        for pixel in pixels {
+            pixel.write_bytes(&mut texels);
+        }
+        if texels.len() != len {
+            bail!(ErrorKind::SoftwareRenderer(format!(
+                "texture {:?} has {} bytes, expected {}x{}x{}x{}",
+                name,
+                texels.len(),
+                size[0],
+                size[1],
+                layers,
+                channels
+            )));
+        }
+        Ok(Self {
+            size,
+            layers,
+            channels,
+            texels,
+        })
+    }
+
+    pub(crate) fn size(&self) -> Vec2<usize> {
+        self.size
+    }
+}
+
+/// A texture uniform, sampled with nearest filtering.
+#[derive(Copy, Clone)]
+pub struct SoftwareSampler<'a> {
+    texture: &'a SoftwareTexture,
+    wrap: (SamplerWrapFunction, SamplerWrapFunction),
+}
+
+impl<'a> SoftwareSampler<'a> {
+    pub(crate) fn new(texture: &'a SoftwareTexture, sampler: Option<SamplerBehavior>) -> Self {
+        Self {
+            texture,
+            // Without a sampler, OpenGL's default texture state repeats.
+            wrap: sampler.map_or(
+                (SamplerWrapFunction::Repeat, SamplerWrapFunction::Repeat),
+                |sampler| (sampler.wrap_function.0, sampler.wrap_function.1),
+            ),
+        }
+    }
+
+    pub fn size(&self) -> Vec2f {
+        Vec2f::new(self.texture.size[0] as f32, self.texture.size[1] as f32)
+    }
+
+    /// Samples the texture at normalised coordinates, like GLSL `texture`.
+    #[inline]
+    pub fn sample(&self, uv: Vec2f) -> Vec4f {
+        self.sample_layer(uv, 0)
+    }
+
+    /// Samples a layer of a texture array at normalised coordinates, like GLSL `texture` with a
+    /// `sampler2DArray`. Out of range layers are clamped.
+    #[inline]
+    pub fn sample_layer(&self, uv: Vec2f, layer: usize) -> Vec4f {
+        let texture = self.texture;
+        let x = wrap_texel(uv[0], texture.size[0], self.wrap.0);
+        let y = wrap_texel(uv[1], texture.size[1], self.wrap.1);
+        let (x, y) = match (x, y) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return vec4(0.0, 0.0, 0.0, 0.0),
+        };
+        let y = layer.min(texture.layers.saturating_sub(1)) * texture.size[1] + y;
+        let start = (y * texture.size[0] + x) * texture.channels;
+        let mut texel = vec4(0.0, 0.0, 0.0, 1.0);
+        for (i, &channel) in texture.texels[start..start + texture.channels]
+            .iter()
+            .enumerate()
+        {
+            texel[i] = f32::from(channel) / 255.0;
+        }
+        texel
+    }
+}
+
+/// Maps a normalised coordinate to a texel index; `None` means the border colour.
+#[inline]
+fn wrap_texel(coordinate: f32, size: usize, wrap: SamplerWrapFunction) -> Option<usize> {
+    let size = size as isize;
+    let texel = (coordinate * size as f32).floor() as isize;
+    Some(match wrap {
+        SamplerWrapFunction::Repeat => texel.rem_euclid(size),
+        SamplerWrapFunction::Mirror => {
+            let texel = texel.rem_euclid(size * 2);
+            if texel < size {
+                texel
+            } else {
+                size * 2 - 1 - texel
+            }
+        }
+        SamplerWrapFunction::Clamp => texel.clamp(0, size - 1),
+        SamplerWrapFunction::MirrorClamp => {
+            let texel = if texel < 0 { -texel - 1 } else { texel };
+            texel.min(size - 1)
+        }
+        SamplerWrapFunction::BorderClamp => {
+            if texel < 0 || texel >= size {
+                return None;
+            }
+            texel
+        }
+    } as usize)
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum SoftwareUniformValue<'a> {
+    Float(f32),
+    Vec2f(Vec2f),
+    Mat4(Mat4),
+    Texture2d(SoftwareSampler<'a>),
+    BufferU8(&'a [u8]),
+}
+
+/// The uniforms of a material, as seen by a `SoftwareShader`.
+pub struct SoftwareUniforms<'a> {
+    values: UniformValues<SoftwareUniformValue<'a>>,
+}
+
+impl<'a> SoftwareUniforms<'a> {
+    pub(crate) fn new(values: UniformValues<SoftwareUniformValue<'a>>) -> Self {
+        Self { values }
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            SoftwareUniformValue::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn vec2f(&self, name: &str) -> Option<Vec2f> {
+        match self.get(name)? {
+            SoftwareUniformValue::Vec2f(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn mat4(&self, name: &str) -> Option<Mat4> {
+        match self.get(name)? {
+            SoftwareUniformValue::Mat4(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn texture_2d(&self, name: &str) -> Option<SoftwareSampler<'a>> {
+        match self.get(name)? {
+            SoftwareUniformValue::Texture2d(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn buffer_texture_u8(&self, name: &str) -> Option<&'a [u8]> {
+        match self.get(name)? {
+            SoftwareUniformValue::BufferU8(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<SoftwareUniformValue<'a>> {
+        self.values
+            .iter()
+            .map_while(|value| *value)
+            .find(|&(value_name, _)| value_name == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Type-erased `SoftwareShader`, so shaders for different vertex types can be stored together.
+pub(crate) trait DrawSoftware {
+    fn draw(
+        &self,
+        target: &mut Framebuffer,
+        uniforms: &SoftwareUniforms,
+        mesh: &MeshRef,
+        ranges: Option<&[Range<usize>]>,
+        wireframe: bool,
+    ) -> Result<()>;
+}
+
+impl<ShaderT: SoftwareShader> DrawSoftware for ShaderT {
+    fn draw(
+        &self,
+        target: &mut Framebuffer,
+        uniforms: &SoftwareUniforms,
+        mesh: &MeshRef,
+        ranges: Option<&[Range<usize>]>,
+        wireframe: bool,
+    ) -> Result<()> {
+        let (vertices, indices) = if let Some(software) = mesh.software() {
+            software
+        } else {
+            bail!(ErrorKind::SoftwareRenderer(
+                "GPU mesh drawn with the software backend".to_owned()
+            ));
+        };
+        let primitives = mesh.primitives();
+        let vertices = if let Some(vertices) = vertices.downcast_ref::<Vec<ShaderT::Vertex>>() {
+            vertices
+        } else {
+            bail!(ErrorKind::SoftwareRenderer(format!(
+                "mesh vertices are not `{}`",
+                any::type_name::<ShaderT::Vertex>()
+            )));
+        };
+        let uniforms = if let Some(uniforms) = self.uniforms(uniforms) {
+            uniforms
+        } else {
+            bail!(ErrorKind::SoftwareRenderer(format!(
+                "missing uniforms for `{}`",
+                any::type_name::<ShaderT>()
+            )));
+        };
+
+        let mut draw_range = |range: Range<usize>| {
+            let num_vertices = primitives.num_vertices();
+            for start in range.step_by(num_vertices) {
+                let mut flat = None;
+                let mut primitive = [None; 3];
+                for (offset, clip_vertex) in primitive[..num_vertices].iter_mut().enumerate() {
+                    let index = indices.map_or(Some(start + offset), |indices| {
+                        indices.get(start + offset).map(|&index| index as usize)
+                    });
+                    if let Some(vertex) = index.and_then(|index| vertices.get(index)) {
+                        let (position, varyings, vertex_flat) = self.vertex(&uniforms, vertex);
+                        *clip_vertex = Some(ClipVertex { position, varyings });
+                        flat = Some(vertex_flat);
+                    }
+                }
+                let shade = |varyings: &ShaderT::Varyings, flat: &ShaderT::Flat| {
+                    self.fragment(&uniforms, varyings, flat)
+                };
+                match (primitives, primitive, flat) {
+                    (Primitives::Triangles, [Some(a), Some(b), Some(c)], Some(flat)) => {
+                        let shade = |varyings: &ShaderT::Varyings| shade(varyings, &flat);
+                        if wireframe {
+                            rasteriser::draw_triangle_edges(target, [a, b, c], shade);
+                        } else {
+                            rasteriser::draw_triangle(target, [a, b, c], shade);
+                        }
+                    }
+                    (Primitives::Lines, [Some(a), Some(b), None], Some(flat)) => {
+                        rasteriser::draw_line(target, [a, b], |varyings| shade(varyings, &flat));
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        match ranges {
+            Some(ranges) => {
+                for range in ranges {
+                    draw_range(range.clone());
+                }
+            }
+            None => draw_range(0..indices.map_or(vertices.len(), <[u32]>::len)),
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn to_rgb(color: Vec3f) -> [u8; 3] {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color[0]), channel(color[1]), channel(color[2])]
+}
+
+#[cfg(test)]
+mod test {
+    use super::Framebuffer;
+    use glium::uniforms::MagnifySamplerFilter;
+
+    #[test]
+    fn stretches_low_resolution_frames() {
+        let mut source = Framebuffer::new(2, 1);
+        source.pixels = vec![[255, 0, 0], [0, 0, 255]];
+
+        let mut target = Framebuffer::new(4, 2);
+        source.stretch_into(&mut target, MagnifySamplerFilter::Nearest);
+        let row = [[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 0, 255]];
+        assert_eq!(target.pixels(), &[row, row].concat()[..]);
+
+        source.stretch_into(&mut target, MagnifySamplerFilter::Linear);
+        assert_eq!(target.pixels()[0], [255, 0, 0]);
+        assert_eq!(target.pixels()[1], [191, 0, 64]);
+        assert_eq!(target.pixels()[2], [64, 0, 191]);
+        assert_eq!(target.pixels()[3], [0, 0, 255]);
+    }
+}
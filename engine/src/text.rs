@@ -1,19 +1,24 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::forget_copy))]
 
+use super::software::Framebuffer;
 use super::system::System;
 use super::window::Window;
 use failchain::{ChainErrorKind, ResultExt, UnboxedError};
 use failure::Fail;
 use glium::index::{NoIndices, PrimitiveType};
+use glium::program::ProgramCreationInput;
 use glium::texture::{ClientFormat, RawImage2d, Texture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
 use glium::{
-    implement_vertex, uniform, Blend, DrawParameters, Frame, Program, Surface, VertexBuffer,
+    implement_vertex, uniform, Blend, Display, DrawParameters, Frame, Program, Surface,
+    VertexBuffer,
 };
 use idcontain::{Id, IdSlab};
 use log::{debug, error};
-use math::Pnt2f;
+use math::{vec3, Pnt2f};
 use rusttype::{self, Font, GlyphId, Point as FontPoint, PositionedGlyph, Scale};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::ops::{Index, IndexMut};
@@ -25,13 +30,78 @@ use unicode_normalization::{Recompositions, UnicodeNormalization};
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct TextId(Id<Text>);
 
+/// A handle to a font text can be rendered with: either the default TTF font or a `BitmapFont`
+/// added to the `TextRenderer`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FontId(usize);
+
+/// A font made of paletted images, one per character, like the fonts in game data files.
+pub struct BitmapFont {
+    glyphs: HashMap<char, BitmapGlyph>,
+    line_height: u32,
+    space_width: u32,
+    scale: u32,
+}
+
+/// The image of a character in a `BitmapFont`.
+pub struct BitmapGlyph {
+    pub width: u32,
+    pub height: u32,
+    /// Palette indices in rows from top to bottom, with the high byte set for transparent pixels
+    /// like in game data images.
+    pub pixels: Vec<u16>,
+}
+
+impl BitmapFont {
+    /// An empty font, drawn with each pixel covering `scale` by `scale` pixels on screen.
+    /// Characters without a glyph (and spaces) advance by `space_width` font pixels.
+    pub fn new(space_width: u32, scale: u32) -> Self {
+        BitmapFont {
+            glyphs: HashMap::new(),
+            line_height: 0,
+            space_width,
+            scale: scale.max(1),
+        }
+    }
+
+    pub fn add_glyph(&mut self, character: char, glyph: BitmapGlyph) {
+        self.line_height = self.line_height.max(glyph.height);
+        self.glyphs.insert(character, glyph);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    /// The glyph of a character, falling back to its upper case version since most game fonts
+    /// only have capitals.
+    fn glyph(&self, character: char) -> Option<&BitmapGlyph> {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&character.to_ascii_uppercase()))
+    }
+
+    /// The width of a character in font pixels.
+    fn advance(&self, character: char) -> u32 {
+        self.glyph(character)
+            .map_or(self.space_width, |glyph| glyph.width)
+    }
+}
+
 /// Handles rendering of debug text to `OpenGL`.
 pub struct TextRenderer {
     font: Font<'static>,
+    bitmap_fonts: Vec<BitmapFont>,
     slab: IdSlab<Text>,
-    program: Program,
+    program: Option<Program>,
+    bitmap_program: Option<Program>,
     draw_params: DrawParameters<'static>,
     pixel_buffer: Vec<u16>,
+    window_size: (u32, u32),
+
+    /// RGB triples bitmap fonts are coloured with, and their GPU copy.
+    palette: Vec<u8>,
+    palette_texture: Option<Texture2d>,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Fail)]
@@ -46,36 +116,79 @@ impl ChainErrorKind for ErrorKind {
 }
 
 impl TextRenderer {
+    /// The TTF font text is rendered with by `insert`.
+    pub fn default_font(&self) -> FontId {
+        FontId(0)
+    }
+
+    pub fn add_bitmap_font(&mut self, font: BitmapFont) -> FontId {
+        self.bitmap_fonts.push(font);
+        FontId(self.bitmap_fonts.len())
+    }
+
+    /// Sets the RGB triples bitmap font palette indices are looked up in when text is drawn.
+    pub fn set_palette(&mut self, win: &Window, colors: &[u8]) {
+        let len = colors.len().min(PALETTE_BYTES);
+        self.palette[..len].copy_from_slice(&colors[..len]);
+        self.palette_texture = win
+            .facade()
+            .map(|facade| palette_texture(facade, &self.palette));
+    }
+
+    /// Creates text with the default font, with its top left corner at `pos` (as a fraction of
+    /// the window size).
     pub fn insert(&mut self, win: &Window, text: &str, pos: Pnt2f, padding: u32) -> TextId {
+        self.insert_with_font(win, self.default_font(), text, pos, padding)
+    }
+
+    pub fn insert_with_font(
+        &mut self,
+        win: &Window,
+        font: FontId,
+        text: &str,
+        pos: Pnt2f,
+        padding: u32,
+    ) -> TextId {
         debug!("Creating text...");
-        let (width, height) = self.rasterise(text, padding);
-        let texture = Texture2d::new(
-            win.facade(),
-            RawImage2d {
-                data: Cow::Borrowed(&self.pixel_buffer),
-                width,
-                height,
-                format: ClientFormat::U8U8,
-            },
-        )
-        .unwrap();
-        let (w, h) = (
-            width as f32 / win.width() as f32 * 2.0,
-            height as f32 / win.height() as f32 * 2.0,
-        );
-        let (x, y) = (pos.x * 2.0 - 1.0, 1.0 - pos.y * 2.0 - h);
-        let text = Text {
-            buffer: VertexBuffer::immutable(
-                win.facade(),
-                &[
-                    vertex(x, y, 0.0, 1.0),
-                    vertex(x, y + h, 0.0, 0.0),
-                    vertex(x + w, y, 1.0, 1.0),
-                    vertex(x + w, y + h, 1.0, 0.0),
-                ],
+        let (width, height) = match font.0.checked_sub(1) {
+            None => self.rasterise(text, padding),
+            Some(index) => rasterise_bitmap(
+                &self.bitmap_fonts[index],
+                text,
+                padding,
+                &mut self.pixel_buffer,
+            ),
+        };
+        let data = if let Some(facade) = win.facade() {
+            let texture = Texture2d::new(
+                facade,
+                RawImage2d {
+                    data: Cow::Borrowed(&self.pixel_buffer),
+                    width,
+                    height,
+                    format: ClientFormat::U8U8,
+                },
             )
-            .unwrap(),
-            texture,
+            .unwrap();
+            TextData::Gpu {
+                buffer: VertexBuffer::dynamic(facade, &quad(win, pos, width, height)).unwrap(),
+                texture,
+            }
+        } else {
+            let (left, top) = top_left(win, pos);
+            TextData::Software {
+                pixels: self.pixel_buffer.clone(),
+                width: width as usize,
+                left,
+                top,
+            }
+        };
+        let text = Text {
+            data,
+            paletted: font != self.default_font(),
+            pos,
+            width,
+            height,
             visible: true,
         };
         let id = self.slab.insert(text);
@@ -97,26 +210,95 @@ impl TextRenderer {
     }
 
     pub fn render(&self, frame: &mut Frame) -> Result<()> {
+        let program = self
+            .program
+            .as_ref()
+            .ok_or_else(|| ErrorKind("No text program with the software backend.".to_owned()))?;
         for text in &self.slab {
             if !text.visible {
                 continue;
             }
-            let uniforms = uniform! {
-                u_tex: &text.texture,
+            let (texture, buffer) = match text.data {
+                TextData::Gpu {
+                    ref texture,
+                    ref buffer,
+                } => (texture, buffer),
+                TextData::Software { .. } => continue,
             };
-            frame
-                .draw(
-                    &text.buffer,
-                    NoIndices(PrimitiveType::TriangleStrip),
-                    &self.program,
-                    &uniforms,
-                    &self.draw_params,
-                )
-                .unwrap();
+            let indices = NoIndices(PrimitiveType::TriangleStrip);
+            if text.paletted {
+                let (bitmap_program, palette) = self
+                    .bitmap_program
+                    .as_ref()
+                    .zip(self.palette_texture.as_ref())
+                    .ok_or_else(|| ErrorKind("No bitmap text program or palette.".to_owned()))?;
+                // Palette indices must not be interpolated.
+                let uniforms = uniform! {
+                    u_tex: texture
+                        .sampled()
+                        .magnify_filter(MagnifySamplerFilter::Nearest)
+                        .minify_filter(MinifySamplerFilter::Nearest),
+                    u_palette: palette
+                        .sampled()
+                        .magnify_filter(MagnifySamplerFilter::Nearest)
+                        .minify_filter(MinifySamplerFilter::Nearest),
+                };
+                frame
+                    .draw(
+                        buffer,
+                        indices,
+                        bitmap_program,
+                        &uniforms,
+                        &self.draw_params,
+                    )
+                    .unwrap();
+            } else {
+                let uniforms = uniform! {
+                    u_tex: texture,
+                };
+                frame
+                    .draw(buffer, indices, program, &uniforms, &self.draw_params)
+                    .unwrap();
+            }
         }
         Ok(())
     }
 
+    /// Blends all visible text over a software framebuffer.
+    pub(crate) fn render_software(&self, target: &mut Framebuffer) {
+        for text in &self.slab {
+            if !text.visible {
+                continue;
+            }
+            if let TextData::Software {
+                ref pixels,
+                width,
+                left,
+                top,
+            } = text.data
+            {
+                for (i, &pixel) in pixels.iter().enumerate() {
+                    let (color, alpha) = if text.paletted {
+                        // Same as the bitmap fragment shader: look the colour up in the palette.
+                        if pixel & 0xff00 != 0 {
+                            continue;
+                        }
+                        let index = usize::from(pixel) * 3;
+                        let rgb = &self.palette[index..index + 3];
+                        let color = vec3(f32::from(rgb[0]), f32::from(rgb[1]), f32::from(rgb[2]));
+                        (color / 255.0, 1.0)
+                    } else {
+                        // Like the fragment shader: the low byte is alpha, the high byte is grey.
+                        let alpha = f32::from(pixel & 0xff) / 255.0;
+                        let grey = f32::from(pixel >> 8) / 255.0;
+                        (vec3(grey, grey, grey), alpha)
+                    };
+                    target.blend(left + i % width, top + i / width, color, alpha);
+                }
+            }
+        }
+    }
+
     fn rasterise(&mut self, text: &str, padding: u32) -> (u32, u32) {
         debug!("Rasterising text {:?}...", text);
         let scale = Scale::uniform(POINT_SIZE);
@@ -217,16 +399,52 @@ impl<'context> System<'context> for TextRenderer {
         Ok(Self {
             font: Font::try_from_vec_and_index(font_bytes, 0)
                 .ok_or_else(|| ErrorKind(format!("Failed to parse font at {:?}.", FONT_PATH)))?,
+            bitmap_fonts: Vec::new(),
             slab: IdSlab::with_capacity(16),
-            program: Program::from_source(window.facade(), VERTEX_SRC, FRAGMENT_SRC, None).unwrap(),
+            program: window.facade().map(|facade| {
+                Program::from_source(facade, VERTEX_SRC, FRAGMENT_SRC, None).unwrap()
+            }),
+            bitmap_program: window.facade().map(|facade| {
+                Program::new(
+                    facade,
+                    ProgramCreationInput::SourceCode {
+                        vertex_shader: VERTEX_SRC,
+                        tessellation_control_shader: None,
+                        tessellation_evaluation_shader: None,
+                        geometry_shader: None,
+                        fragment_shader: BITMAP_FRAGMENT_SRC,
+                        transform_feedback_varyings: None,
+                        // The game palette is already sRGB.
+                        outputs_srgb: true,
+                        uses_point_size: false,
+                    },
+                )
+                .unwrap()
+            }),
             draw_params: DrawParameters {
                 blend: Blend::alpha_blending(),
                 ..DrawParameters::default()
             },
             pixel_buffer: Vec::new(),
+            window_size: (window.width(), window.height()),
+            palette: grey_palette(),
+            palette_texture: window
+                .facade()
+                .map(|facade| palette_texture(facade, &grey_palette())),
         })
     }
 
+    fn update(&mut self, window: &Window) -> Result<()> {
+        let window_size = (window.width(), window.height());
+        if window_size != self.window_size {
+            self.window_size = window_size;
+            for text in &mut self.slab {
+                text.relayout(window);
+            }
+        }
+        Ok(())
+    }
+
     fn destroy(self, _window: &Window) -> Result<()> {
         if !self.slab.is_empty() {
             error!("Text leaked, {} instances.", self.slab.len());
@@ -240,15 +458,51 @@ impl<'context> System<'context> for TextRenderer {
 }
 
 pub struct Text {
-    texture: Texture2d,
-    buffer: VertexBuffer<TextVertex>,
+    data: TextData,
+    /// Whether the pixels are bitmap font palette indices, rather than grey and alpha.
+    paletted: bool,
+    pos: Pnt2f,
+    width: u32,
+    height: u32,
     visible: bool,
 }
 
+#[allow(clippy::large_enum_variant)]
+enum TextData {
+    Gpu {
+        texture: Texture2d,
+        buffer: VertexBuffer<TextVertex>,
+    },
+    Software {
+        pixels: Vec<u16>,
+        width: usize,
+        left: usize,
+        top: usize,
+    },
+}
+
 impl Text {
     pub fn set_visible(&mut self, visible: bool) {
         self.visible = visible;
     }
+
+    /// Keeps the text at the same relative position and pixel size after a window resize.
+    fn relayout(&mut self, win: &Window) {
+        match self.data {
+            TextData::Gpu { ref buffer, .. } => {
+                buffer.write(&quad(win, self.pos, self.width, self.height));
+            }
+            TextData::Software {
+                ref mut left,
+                ref mut top,
+                ..
+            } => {
+                let (new_left, new_top) = top_left(win, self.pos);
+                *left = new_left;
+                *top = new_top;
+            }
+        }
+    }
 }
 
 struct LayoutIter<'a> {
@@ -283,6 +537,12 @@ const FONT_PATH: &str = "assets/ttf/OpenSans-Regular.ttf";
 /// Hard-coded font size.
 const POINT_SIZE: f32 = 24.0;
 
+/// The size of a palette of 256 RGB triples.
+const PALETTE_BYTES: usize = 256 * 3;
+
+/// Bitmap font pixels not covered by any glyph.
+const TRANSPARENT: u16 = 0xff00;
+
 const VERTEX_SRC: &str = r#"
     #version 140
     in vec2 a_pos;
@@ -305,6 +565,21 @@ const FRAGMENT_SRC: &str = r#"
     }
 "#;
 
+const BITMAP_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_tex;
+    uniform sampler2D u_palette;
+    in vec2 v_uv;
+    out vec4 color;
+    void main() {
+        vec4 index = texture(u_tex, v_uv);
+        if (index.g > 0.5) {
+            discard;
+        }
+        color = vec4(texture(u_palette, vec2(index.r, 0.0)).rgb, 1.0);
+    }
+"#;
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 struct TextVertex {
@@ -314,9 +589,164 @@ struct TextVertex {
 
 implement_vertex!(TextVertex, a_pos, a_uv);
 
+/// Lays out bitmap `text` into `pixel_buffer`, one palette index per pixel, and returns its size.
+fn rasterise_bitmap(
+    font: &BitmapFont,
+    text: &str,
+    padding: u32,
+    pixel_buffer: &mut Vec<u16>,
+) -> (u32, u32) {
+    debug!("Rasterising bitmap text {:?}...", text);
+    let scale = font.scale;
+    let lines = text.split('\n');
+    let text_width = lines
+        .clone()
+        .map(|line| line.chars().map(|c| font.advance(c)).sum::<u32>())
+        .max()
+        .unwrap_or(0);
+    let num_lines = lines.count() as u32;
+    let (width, height) = (
+        text_width * scale + padding * 2,
+        num_lines * font.line_height * scale + padding * 2,
+    );
+    debug!("Computed dimensions {}x{}...", width, height);
+
+    pixel_buffer.clear();
+    pixel_buffer.resize((width * height) as usize, TRANSPARENT);
+    for (i_line, line) in text.split('\n').enumerate() {
+        let top = padding + i_line as u32 * font.line_height * scale;
+        let mut left = padding;
+        for character in line.chars() {
+            let glyph = if let Some(glyph) = font.glyph(character) {
+                glyph
+            } else {
+                left += font.space_width * scale;
+                continue;
+            };
+            for (i_pixel, &pixel) in glyph.pixels.iter().enumerate() {
+                if pixel & 0xff00 != 0 {
+                    continue;
+                }
+                let x = left + (i_pixel as u32 % glyph.width) * scale;
+                let y = top + (i_pixel as u32 / glyph.width) * scale;
+                for row in y..y + scale {
+                    let start = (row * width + x) as usize;
+                    for target in &mut pixel_buffer[start..start + scale as usize] {
+                        *target = pixel;
+                    }
+                }
+            }
+            left += glyph.width * scale;
+        }
+    }
+    (width, height)
+}
+
+/// A grey ramp, so bitmap text is visible before a game palette is set.
+fn grey_palette() -> Vec<u8> {
+    (0..=255u8).flat_map(|value| vec![value; 3]).collect()
+}
+
+fn palette_texture(facade: &Display, colors: &[u8]) -> Texture2d {
+    Texture2d::new(
+        facade,
+        RawImage2d {
+            data: Cow::Borrowed(colors),
+            width: 256,
+            height: 1,
+            format: ClientFormat::U8U8U8,
+        },
+    )
+    .unwrap()
+}
+
+/// The quad covering `width` by `height` pixels at `pos`, in normalised device coordinates.
+fn quad(win: &Window, pos: Pnt2f, width: u32, height: u32) -> [TextVertex; 4] {
+    let (w, h) = (
+        width as f32 / win.width() as f32 * 2.0,
+        height as f32 / win.height() as f32 * 2.0,
+    );
+    let (x, y) = (pos.x * 2.0 - 1.0, 1.0 - pos.y * 2.0 - h);
+    [
+        vertex(x, y, 0.0, 1.0),
+        vertex(x, y + h, 0.0, 0.0),
+        vertex(x + w, y, 1.0, 1.0),
+        vertex(x + w, y + h, 1.0, 0.0),
+    ]
+}
+
+/// The pixel at the top left of text at `pos`.
+fn top_left(win: &Window, pos: Pnt2f) -> (usize, usize) {
+    (
+        (pos.x * win.width() as f32) as usize,
+        (pos.y * win.height() as f32) as usize,
+    )
+}
+
 fn vertex(x: f32, y: f32, u: f32, v: f32) -> TextVertex {
     TextVertex {
         a_pos: [x, y],
         a_uv: [u, v],
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{rasterise_bitmap, BitmapFont, BitmapGlyph, TRANSPARENT};
+
+    /// A font whose `A` is a two pixel wide glyph: palette index 7, then a transparent pixel.
+    fn font(scale: u32) -> BitmapFont {
+        let mut font = BitmapFont::new(3, scale);
+        font.add_glyph(
+            'A',
+            BitmapGlyph {
+                width: 2,
+                height: 1,
+                pixels: vec![7, TRANSPARENT],
+            },
+        );
+        font
+    }
+
+    /// The coordinates of the pixels which aren't transparent.
+    fn opaque(pixels: &[u16], width: u32) -> Vec<(u32, u32, u16)> {
+        pixels
+            .iter()
+            .enumerate()
+            .filter(|&(_, &pixel)| pixel != TRANSPARENT)
+            .map(|(i, &pixel)| (i as u32 % width, i as u32 / width, pixel))
+            .collect()
+    }
+
+    #[test]
+    fn bitmap_glyphs_keep_palette_indices() {
+        let mut pixels = Vec::new();
+        let (width, height) = rasterise_bitmap(&font(1), "AA", 1, &mut pixels);
+        assert_eq!((width, height), (6, 3));
+        assert_eq!(pixels.len(), 18);
+        assert_eq!(opaque(&pixels, width), vec![(1, 1, 7), (3, 1, 7)]);
+    }
+
+    #[test]
+    fn bitmap_glyphs_are_scaled() {
+        let mut pixels = Vec::new();
+        let (width, height) = rasterise_bitmap(&font(2), "A", 0, &mut pixels);
+        assert_eq!((width, height), (4, 2));
+        assert_eq!(
+            opaque(&pixels, width),
+            vec![(0, 0, 7), (1, 0, 7), (0, 1, 7), (1, 1, 7)]
+        );
+    }
+
+    #[test]
+    fn bitmap_text_falls_back_to_capitals_and_spaces() {
+        let mut pixels = Vec::new();
+        // `a` is drawn as `A`, while `?` has no glyph and advances like a space.
+        let (width, height) = rasterise_bitmap(&font(1), "a?A\nA", 0, &mut pixels);
+        assert_eq!((width, height), (7, 2));
+        assert_eq!(
+            opaque(&pixels, width),
+            vec![(0, 0, 7), (5, 0, 7), (0, 1, 7)]
+        );
+    }
+}
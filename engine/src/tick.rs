@@ -42,6 +42,20 @@ impl Tick {
         self.slept
     }
 
+    /// While set, every tick renders a frame and the simulation falls behind real time instead of
+    /// skipping frames to catch up.
+    pub fn set_lockstep(&mut self, lockstep: bool) {
+        self.lockstep = lockstep;
+    }
+
+    /// Forgets any accumulated drift, so the next update renders a frame even after a long stall
+    /// (like loading a level), instead of skipping frames to catch up.
+    pub fn resync(&mut self) {
+        self.drift = 0.0;
+        self.last_time = None;
+        self.is_frame = true;
+    }
+
     #[inline]
     pub fn seconds_since_tick(&self, index: TickIndex) -> f32 {
         if index.0 < self.index.0 {
@@ -60,6 +74,7 @@ pub struct Tick {
     slept: f32,
     last_time: Option<Instant>,
     is_frame: bool,
+    lockstep: bool,
 }
 
 impl<'context> InfallibleSystem<'context> for Tick {
@@ -78,6 +93,7 @@ impl<'context> InfallibleSystem<'context> for Tick {
             slept: 0.0,
             last_time: None,
             is_frame: true,
+            lockstep: false,
         }
     }
 
@@ -93,6 +109,10 @@ impl<'context> InfallibleSystem<'context> for Tick {
         // Accumulate drift: real_time - simulation_time
         let real_delta = duration_to_seconds(current_time.duration_since(last_time));
         self.drift += real_delta - self.timestep;
+        if self.lockstep {
+            // Keep the drift just low enough for every tick to be a frame.
+            self.drift = self.drift.min(self.timestep * 0.99);
+        }
 
         // If we just renderered a frame, but simulation is still ahead of real time by more than
         // one timestep, sleep to get back in sync.
@@ -1,11 +1,15 @@
 use super::entities::{Entities, Entity, EntityId};
 use super::errors::{ErrorKind, Result};
+use super::software::{SoftwarePixel, SoftwareSampler, SoftwareTexture, SoftwareUniformValue};
 use super::system::InfallibleSystem;
 use super::window::Window;
 use failchain::bail;
 use glium::buffer::Content as BufferContent;
 use glium::texture::buffer_texture::{BufferTexture, BufferTextureType};
-use glium::texture::{ClientFormat, PixelValue, RawImage2d, Texture2d as GliumTexture2d};
+use glium::texture::{
+    ClientFormat, PixelValue, RawImage2d, Texture2d as GliumTexture2d,
+    Texture2dArray as GliumTexture2dArray,
+};
 use glium::uniforms::{AsUniformValue, SamplerBehavior, UniformValue};
 use idcontain::IdMapVec;
 use log::{debug, error};
@@ -44,11 +48,20 @@ pub enum UniformId {
     Vec2f(Vec2fUniformId),
 }
 
+/// The contents of a texture array, for `Uniforms::add_texture_2d_array`.
+pub struct TextureArray<'a, PixelT> {
+    /// Each layer in turn.
+    pub pixels: &'a [PixelT],
+    pub size: Vec2<usize>,
+    pub layers: usize,
+    pub format: ClientFormat,
+}
+
 pub struct Uniforms {
     // TODO(cristicbz): Textures should be their own resource!
     texture2ds: IdMapVec<Entity, Texture2d>,
     floats: IdMapVec<Entity, f32>,
-    buffer_textures_u8: IdMapVec<Entity, BufferTexture<u8>>,
+    buffer_textures_u8: IdMapVec<Entity, BufferTextureU8>,
     mat4s: IdMapVec<Entity, Mat4>,
     vec2fs: IdMapVec<Entity, Vec2f>,
 }
@@ -143,7 +156,7 @@ impl Uniforms {
         self.vec2fs.get_mut(id.0)
     }
 
-    pub fn add_texture_2d<'a, PixelT: PixelValue>(
+    pub fn add_texture_2d<'a, PixelT: PixelValue + SoftwarePixel>(
         &mut self,
         window: &Window,
         entities: &mut Entities,
@@ -162,19 +175,10 @@ impl Uniforms {
             format,
             sampler,
         );
-        let gl = GliumTexture2d::new(
-            window.facade(),
-            RawImage2d {
-                data: Cow::Borrowed(pixels),
-                width: size[0] as u32,
-                height: size[1] as u32,
-                format,
-            },
-        )
-        .map_err(ErrorKind::glium(name))?;
+        let data = TextureData::new(window, name, pixels, size, None, format)?;
         debug!("Texture {:?} created successfully", name);
         let id = entities.add(parent, name)?;
-        self.texture2ds.insert(id, Texture2d { gl, sampler });
+        self.texture2ds.insert(id, Texture2d { data, sampler });
         debug!(
             "Added texture {:?} {:?} as child of {:?}.",
             name, id, parent
@@ -182,6 +186,43 @@ impl Uniforms {
         Ok(Texture2dId(id))
     }
 
+    /// Like `add_texture_2d`, but creates an array of textures (a `sampler2DArray` in GLSL).
+    pub fn add_texture_2d_array<PixelT: PixelValue + SoftwarePixel>(
+        &mut self,
+        window: &Window,
+        entities: &mut Entities,
+        parent: EntityId,
+        name: &'static str,
+        array: TextureArray<PixelT>,
+        sampler: Option<SamplerBehavior>,
+    ) -> Result<Texture2dId> {
+        let TextureArray {
+            pixels,
+            size,
+            layers,
+            format,
+        } = array;
+        debug!(
+            "Creating texture array {:?}: pixels={}, size={:?}, layers={}, format={:?}, \
+             sampler={:?}",
+            name,
+            pixels.len(),
+            size,
+            layers,
+            format,
+            sampler,
+        );
+        let data = TextureData::new(window, name, pixels, size, Some(layers), format)?;
+        debug!("Texture array {:?} created successfully", name);
+        let id = entities.add(parent, name)?;
+        self.texture2ds.insert(id, Texture2d { data, sampler });
+        debug!(
+            "Added texture array {:?} {:?} as child of {:?}.",
+            name, id, parent
+        );
+        Ok(Texture2dId(id))
+    }
+
     pub fn get_texture_2d_mut(&mut self, texture_id: Texture2dId) -> Option<Texture2dRefMut> {
         self.texture2ds
             .get_mut(texture_id.0)
@@ -205,8 +246,13 @@ impl Uniforms {
             "Creating persistent buffer_texture<u7> {:?}, size={:?}, type={:?}",
             name, size, texture_type
         );
-        let texture = BufferTexture::empty_persistent(window.facade(), size, texture_type)
-            .map_err(ErrorKind::glium(name))?;
+        let texture = match window.facade() {
+            Some(facade) => BufferTextureU8::Gpu(
+                BufferTexture::empty_persistent(facade, size, texture_type)
+                    .map_err(ErrorKind::glium(name))?,
+            ),
+            None => BufferTextureU8::Software(vec![0; size]),
+        };
         debug!("Buffer texture {:?} created successfully", name);
         let id = entities.add(parent, name)?;
         self.buffer_textures_u8.insert(id, texture);
@@ -225,8 +271,10 @@ impl Uniforms {
         F: FnOnce(&mut [u8]),
     {
         // TODO(cristicbz): Handle missing.
-        if let Some(buffer) = self.buffer_textures_u8.get_mut(id.id) {
-            writer(&mut *buffer.map());
+        match self.buffer_textures_u8.get_mut(id.id) {
+            Some(BufferTextureU8::Gpu(buffer)) => writer(&mut buffer.map()),
+            Some(BufferTextureU8::Software(buffer)) => writer(buffer),
+            None => {}
         }
     }
 
@@ -236,13 +284,23 @@ impl Uniforms {
         name: &'static str,
         texture: Texture2dId,
     ) -> Result<Vec2fUniformId> {
-        let size = self.texture2ds.get(texture.0).map(|texture| {
-            let texture = &texture.gl;
-            Vec2::new(
-                texture.get_width() as f32,
-                texture.get_height().unwrap_or(1) as f32,
-            )
-        });
+        let size = self
+            .texture2ds
+            .get(texture.0)
+            .map(|texture| match texture.data {
+                TextureData::Gpu(ref texture) => Vec2::new(
+                    texture.get_width() as f32,
+                    texture.get_height().unwrap_or(1) as f32,
+                ),
+                TextureData::GpuArray(ref texture) => Vec2::new(
+                    texture.get_width() as f32,
+                    texture.get_height().unwrap_or(1) as f32,
+                ),
+                TextureData::Software(ref texture) => {
+                    let size = texture.size();
+                    Vec2::new(size[0] as f32, size[1] as f32)
+                }
+            });
         let size = if let Some(size) = size {
             size
         } else {
@@ -258,10 +316,15 @@ impl Uniforms {
     #[inline]
     pub fn get_value(&self, id: UniformId) -> Option<UniformValue> {
         match id {
-            UniformId::Texture2d(id) => self
-                .texture2ds
-                .get(id.0)
-                .map(|texture| UniformValue::Texture2d(&texture.gl, texture.sampler)),
+            UniformId::Texture2d(id) => self.texture2ds.get(id.0).and_then(|texture| match texture
+                .data
+            {
+                TextureData::Gpu(ref gl) => Some(UniformValue::Texture2d(gl, texture.sampler)),
+                TextureData::GpuArray(ref gl) => {
+                    Some(UniformValue::Texture2dArray(gl, texture.sampler))
+                }
+                TextureData::Software(_) => None,
+            }),
             UniformId::Float(id) => self
                 .floats
                 .get(id.0)
@@ -278,10 +341,49 @@ impl Uniforms {
                     [mat4[3][0], mat4[3][1], mat4[3][2], mat4[3][3]],
                 ])
             }),
-            UniformId::BufferTextureU8(id) => self
-                .buffer_textures_u8
-                .get(id.id)
-                .map(AsUniformValue::as_uniform_value),
+            UniformId::BufferTextureU8(id) => {
+                self.buffer_textures_u8
+                    .get(id.id)
+                    .and_then(|buffer| match buffer {
+                        BufferTextureU8::Gpu(buffer) => Some(buffer.as_uniform_value()),
+                        BufferTextureU8::Software(_) => None,
+                    })
+            }
+        }
+    }
+
+    pub(crate) fn get_software_value(&self, id: UniformId) -> Option<SoftwareUniformValue<'_>> {
+        match id {
+            UniformId::Texture2d(id) => self.texture2ds.get(id.0).and_then(|texture| match texture
+                .data
+            {
+                TextureData::Software(ref software) => Some(SoftwareUniformValue::Texture2d(
+                    SoftwareSampler::new(software, texture.sampler),
+                )),
+                TextureData::Gpu(_) | TextureData::GpuArray(_) => None,
+            }),
+            UniformId::Float(id) => self
+                .floats
+                .get(id.0)
+                .map(|&value| SoftwareUniformValue::Float(value)),
+            UniformId::Vec2f(id) => self
+                .vec2fs
+                .get(id.0)
+                .map(|&vec2| SoftwareUniformValue::Vec2f(vec2)),
+            UniformId::Mat4(id) => self
+                .mat4s
+                .get(id.0)
+                .map(|&mat4| SoftwareUniformValue::Mat4(mat4)),
+            UniformId::BufferTextureU8(id) => {
+                self.buffer_textures_u8
+                    .get(id.id)
+                    .and_then(|buffer| match buffer {
+                        BufferTextureU8::Software(buffer) => {
+                            Some(SoftwareUniformValue::BufferU8(buffer))
+                        }
+                        BufferTextureU8::Gpu(_) => None,
+                    })
+            }
         }
     }
 }
@@ -296,7 +398,7 @@ impl<'uniforms> Texture2dRefMut<'uniforms> {
         &mut self.texture.sampler
     }
 
-    pub fn replace_pixels<'pixels, PixelT: PixelValue>(
+    pub fn replace_pixels<'pixels, PixelT: PixelValue + SoftwarePixel>(
         &mut self,
         window: &Window,
         pixels: &'pixels [PixelT],
@@ -312,16 +414,14 @@ impl<'uniforms> Texture2dRefMut<'uniforms> {
             format,
             sampler,
         );
-        self.texture.gl = GliumTexture2d::new(
-            window.facade(),
-            RawImage2d {
-                data: Cow::Borrowed(pixels),
-                width: size[0] as u32,
-                height: size[1] as u32,
-                format,
-            },
-        )
-        .map_err(ErrorKind::glium("texture2d.replace_pixels"))?;
+        self.texture.data = TextureData::new(
+            window,
+            "texture2d.replace_pixels",
+            pixels,
+            size,
+            None,
+            format,
+        )?;
 
         debug!("Replaced texture {:?} successfully.", self.texture_id,);
         Ok(())
@@ -409,10 +509,70 @@ impl<'context> InfallibleSystem<'context> for Uniforms {
 }
 
 struct Texture2d {
-    gl: GliumTexture2d,
+    data: TextureData,
     sampler: Option<SamplerBehavior>,
 }
 
+enum TextureData {
+    Gpu(GliumTexture2d),
+    GpuArray(GliumTexture2dArray),
+    Software(SoftwareTexture),
+}
+
+impl TextureData {
+    /// Creates a plain 2D texture if `layers` is `None`, otherwise a texture array.
+    fn new<PixelT: PixelValue + SoftwarePixel>(
+        window: &Window,
+        name: &'static str,
+        pixels: &[PixelT],
+        size: Vec2<usize>,
+        layers: Option<usize>,
+        format: ClientFormat,
+    ) -> Result<Self> {
+        Ok(match (window.facade(), layers) {
+            (Some(facade), Some(layers)) => {
+                let layer_len = size[0] * size[1];
+                let images = (0..layers)
+                    .map(|layer| RawImage2d {
+                        data: Cow::Borrowed(&pixels[layer * layer_len..][..layer_len]),
+                        width: size[0] as u32,
+                        height: size[1] as u32,
+                        format,
+                    })
+                    .collect();
+                TextureData::GpuArray(
+                    GliumTexture2dArray::new(facade, images).map_err(ErrorKind::glium(name))?,
+                )
+            }
+            (Some(facade), None) => TextureData::Gpu(
+                GliumTexture2d::new(
+                    facade,
+                    RawImage2d {
+                        data: Cow::Borrowed(pixels),
+                        width: size[0] as u32,
+                        height: size[1] as u32,
+                        format,
+                    },
+                )
+                .map_err(ErrorKind::glium(name))?,
+            ),
+            (None, layers) => TextureData::Software(SoftwareTexture::new(
+                name,
+                pixels,
+                size,
+                layers.unwrap_or(1),
+                format,
+            )?),
+        })
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum BufferTextureU8 {
+    Gpu(BufferTexture<u8>),
+    Software(Vec<u8>),
+}
+
 impl From<Texture2dId> for UniformId {
     fn from(other: Texture2dId) -> Self {
         UniformId::Texture2d(other)
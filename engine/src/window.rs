@@ -1,27 +1,72 @@
 use super::errors::{Error, ErrorKind, Result};
 use super::platform;
+use super::screenshot::Screenshot;
+use super::software::Framebuffer;
 use super::system::System;
 use glium::{
     glutin::{
-        dpi::PhysicalSize, event_loop::EventLoop, window::WindowBuilder, Api, ContextBuilder,
-        GlProfile, GlRequest,
+        dpi::PhysicalSize,
+        event_loop::EventLoop,
+        monitor::MonitorHandle,
+        window::{Fullscreen as GlutinFullscreen, WindowBuilder},
+        Api, ContextBuilder, GlProfile, GlRequest,
     },
     Display, Frame, Surface,
 };
+use log::{debug, info, warn};
+use math::vec3;
+use std::time::{Duration, Instant};
 
 const OPENGL_DEPTH_SIZE: u8 = 24;
 
+/// How early a frame may be rendered without counting as going over the frame cap.
+const FRAME_CAP_TOLERANCE: Duration = Duration::from_millis(1);
+
+/// The sRGB colour frames are cleared to.
+pub(crate) const CLEAR_COLOR: (f32, f32, f32) = (0.06, 0.07, 0.09);
+
+/// Selects what frames are drawn with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RenderBackend {
+    /// OpenGL, into a window on screen.
+    Gpu,
+    /// The CPU rasteriser, into an in-memory framebuffer. No window or display is needed.
+    Software,
+}
+
+/// How the window covers the screen.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fullscreen {
+    Windowed,
+    /// A window without decorations, covering the monitor at its current video mode.
+    Borderless,
+    /// Takes over the monitor, switching to the video mode closest to the window size.
+    Exclusive,
+}
+
 pub struct WindowConfig {
     pub width: u32,
     pub height: u32,
     pub title: String,
+    pub backend: RenderBackend,
+    pub fullscreen: Fullscreen,
+    pub vsync: bool,
+    /// Number of samples per pixel for multisample anti-aliasing; zero disables it.
+    pub msaa_samples: u16,
+    /// Upper bound on frames rendered per second, if any.
+    pub max_fps: Option<f32>,
 }
 
 pub struct Window {
-    display: Display,
+    display: Option<Display>,
+    framebuffer: Option<Framebuffer>,
     event_loop: Option<EventLoop<()>>,
     width: u32,
     height: u32,
+    fullscreen: Fullscreen,
+    fullscreen_mode: Fullscreen,
+    min_frame_interval: Option<Duration>,
+    next_frame_time: Option<Instant>,
 }
 
 impl Window {
@@ -37,14 +82,135 @@ impl Window {
         self.width as f32 / self.height as f32
     }
 
-    pub fn draw(&self) -> Frame {
-        let mut frame = self.display.draw();
-        frame.clear_all_srgb((0.06, 0.07, 0.09, 0.0), 1.0, 0);
-        frame
+    pub fn backend(&self) -> RenderBackend {
+        if self.display.is_some() {
+            RenderBackend::Gpu
+        } else {
+            RenderBackend::Software
+        }
+    }
+
+    pub fn fullscreen(&self) -> Fullscreen {
+        self.fullscreen
+    }
+
+    /// Switches the window in or out of fullscreen. Does nothing with the software backend.
+    pub fn set_fullscreen(&mut self, fullscreen: Fullscreen) {
+        let display = if let Some(display) = self.display.as_ref() {
+            display
+        } else {
+            return;
+        };
+        let gl_window = display.gl_window();
+        let window = gl_window.window();
+        window.set_fullscreen(glutin_fullscreen(
+            fullscreen,
+            window.current_monitor(),
+            self.width,
+            self.height,
+        ));
+        if fullscreen != Fullscreen::Windowed {
+            self.fullscreen_mode = fullscreen;
+        }
+        self.fullscreen = fullscreen;
+    }
+
+    /// Switches between a window and the last fullscreen mode used (borderless by default).
+    pub fn toggle_fullscreen(&mut self) {
+        self.set_fullscreen(match self.fullscreen {
+            Fullscreen::Windowed => self.fullscreen_mode,
+            Fullscreen::Borderless | Fullscreen::Exclusive => Fullscreen::Windowed,
+        });
+    }
+
+    /// Whether enough time has passed since the last frame to render another one without going
+    /// over the configured frame cap.
+    pub(crate) fn frame_due(&mut self) -> bool {
+        let min_frame_interval = if let Some(interval) = self.min_frame_interval {
+            interval
+        } else {
+            return true;
+        };
+        let now = Instant::now();
+        // Frames are only rendered on ticks, so allow for a little scheduling jitter.
+        let next_frame_time = match self.next_frame_time {
+            Some(next) if now + FRAME_CAP_TOLERANCE < next => return false,
+            Some(next) if now < next + min_frame_interval => next,
+            _ => now,
+        };
+        self.next_frame_time = Some(next_frame_time + min_frame_interval);
+        true
+    }
+
+    /// Starts a cleared GPU frame, or returns `None` with the software backend.
+    pub fn draw(&self) -> Option<Frame> {
+        let mut frame = self.display.as_ref()?.draw();
+        let (red, green, blue) = CLEAR_COLOR;
+        frame.clear_all_srgb((red, green, blue, 0.0), 1.0, 0);
+        Some(frame)
+    }
+
+    /// Clears and returns the software framebuffer, or returns `None` with the GPU backend.
+    pub(crate) fn draw_software(&mut self) -> Option<&mut Framebuffer> {
+        let framebuffer = self.framebuffer.as_mut()?;
+        let (red, green, blue) = CLEAR_COLOR;
+        framebuffer.clear(vec3(red, green, blue));
+        Some(framebuffer)
     }
 
-    pub fn facade(&self) -> &Display {
-        &self.display
+    /// The display, or `None` with the software backend.
+    pub fn facade(&self) -> Option<&Display> {
+        self.display.as_ref()
+    }
+
+    /// The last frame drawn by the software backend, or `None` with the GPU backend.
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        self.framebuffer.as_ref()
+    }
+
+    /// Reads back the last finished frame.
+    pub fn read_frame(&self) -> Result<Screenshot> {
+        if let Some(framebuffer) = self.framebuffer.as_ref() {
+            return Ok(Screenshot::new(
+                framebuffer.width(),
+                framebuffer.height(),
+                framebuffer.pixels().to_vec(),
+            ));
+        }
+        let display = self
+            .display
+            .as_ref()
+            .expect("window has neither a display nor a framebuffer");
+        let rows: Vec<Vec<(u8, u8, u8, u8)>> = display
+            .read_front_buffer()
+            .map_err(|error| ErrorKind::Capture(format!("Cannot read front buffer: {}", error)))?;
+        let height = rows.len() as u32;
+        let width = rows.first().map_or(0, |row| row.len() as u32);
+        // OpenGL returns rows from bottom to top.
+        let pixels = rows
+            .iter()
+            .rev()
+            .flatten()
+            .map(|&(red, green, blue, _)| [red, green, blue])
+            .collect();
+        Ok(Screenshot::new(width, height, pixels))
+    }
+
+    /// Changes the size of the window's drawable area, following a resize by the user.
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        debug!("Window resized to {}x{}.", width, height);
+        self.width = width;
+        self.height = height;
+        if let Some(display) = self.display.as_ref() {
+            // Some platforms (e.g. Wayland) need the context resized along with the window.
+            display.gl_window().resize(PhysicalSize { width, height });
+        }
+        if let Some(framebuffer) = self.framebuffer.as_mut() {
+            *framebuffer = Framebuffer::new(width, height);
+        }
     }
 
     pub(crate) fn take_event_loop(&mut self) -> Option<EventLoop<()>> {
@@ -57,6 +223,20 @@ impl<'context> System<'context> for Window {
     type Error = Error;
 
     fn create(config: &'context WindowConfig) -> Result<Self> {
+        if config.backend == RenderBackend::Software {
+            return Ok(Window {
+                display: None,
+                framebuffer: Some(Framebuffer::new(config.width, config.height)),
+                event_loop: None,
+                width: config.width,
+                height: config.height,
+                fullscreen: Fullscreen::Windowed,
+                fullscreen_mode: Fullscreen::Windowed,
+                min_frame_interval: min_frame_interval(config.max_fps),
+                next_frame_time: None,
+            });
+        }
+
         let events = EventLoop::new();
 
         let window = WindowBuilder::new()
@@ -64,6 +244,12 @@ impl<'context> System<'context> for Window {
                 width: config.width,
                 height: config.height,
             })
+            .with_fullscreen(glutin_fullscreen(
+                config.fullscreen,
+                events.primary_monitor(),
+                config.width,
+                config.height,
+            ))
             .with_title(config.title.clone());
 
         let context = ContextBuilder::new()
@@ -72,16 +258,26 @@ impl<'context> System<'context> for Window {
                 Api::OpenGl,
                 (platform::GL_MAJOR_VERSION, platform::GL_MINOR_VERSION),
             ))
-            .with_depth_buffer(OPENGL_DEPTH_SIZE);
+            .with_depth_buffer(OPENGL_DEPTH_SIZE)
+            .with_vsync(config.vsync)
+            .with_multisampling(config.msaa_samples);
 
         let display = Display::new(window, context, &events)
             .map_err(ErrorKind::create_window(config.width, config.height))?;
 
         Ok(Window {
-            display,
+            display: Some(display),
+            framebuffer: None,
             event_loop: Some(events),
             width: config.width,
             height: config.height,
+            fullscreen: config.fullscreen,
+            fullscreen_mode: match config.fullscreen {
+                Fullscreen::Windowed => Fullscreen::Borderless,
+                fullscreen => fullscreen,
+            },
+            min_frame_interval: min_frame_interval(config.max_fps),
+            next_frame_time: None,
         })
     }
 
@@ -89,3 +285,40 @@ impl<'context> System<'context> for Window {
         "window"
     }
 }
+
+/// Picks the glutin fullscreen setting for `fullscreen` on `monitor`. Exclusive fullscreen uses
+/// the video mode closest to `width` by `height`, with the highest refresh rate.
+fn glutin_fullscreen(
+    fullscreen: Fullscreen,
+    monitor: MonitorHandle,
+    width: u32,
+    height: u32,
+) -> Option<GlutinFullscreen> {
+    match fullscreen {
+        Fullscreen::Windowed => None,
+        Fullscreen::Borderless => Some(GlutinFullscreen::Borderless(monitor)),
+        Fullscreen::Exclusive => {
+            let video_mode = monitor.video_modes().min_by_key(|mode| {
+                let size = mode.size();
+                (
+                    (i64::from(size.width) - i64::from(width)).abs()
+                        + (i64::from(size.height) - i64::from(height)).abs(),
+                    std::cmp::Reverse((mode.refresh_rate(), mode.bit_depth())),
+                )
+            });
+            if let Some(video_mode) = video_mode {
+                info!("Exclusive fullscreen video mode: {}", video_mode);
+                Some(GlutinFullscreen::Exclusive(video_mode))
+            } else {
+                warn!("No video modes for exclusive fullscreen, using borderless instead.");
+                Some(GlutinFullscreen::Borderless(monitor))
+            }
+        }
+    }
+}
+
+fn min_frame_interval(max_fps: Option<f32>) -> Option<Duration> {
+    max_fps
+        .filter(|&max_fps| max_fps > 0.0)
+        .map(|max_fps| Duration::from_secs_f32(1.0 / max_fps))
+}
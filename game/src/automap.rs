@@ -0,0 +1,390 @@
+use super::map_view::MapView;
+use super::player::Player;
+use super::wad_system::WadSystem;
+use engine::{
+    Analog2d, DependenciesFrom, Entities, EntityId, Error, Gesture, Input, LineVertex,
+    Mat4UniformId, Materials, Meshes, PadButton, RenderPipeline, Result, Scancode, ShaderId,
+    Shaders, System, Tick, Transforms, Uniforms, Window, LINE_SHADER,
+};
+use log::info;
+use math::prelude::*;
+use math::{vec3, Mat4, Pnt2f, Vec2f};
+use serde::Deserialize;
+use wad::types::{SpecialType, WadLinedef};
+use wad::Level as WadLevel;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    pub toggle: Gesture,
+    pub zoom_in: Gesture,
+    pub zoom_out: Gesture,
+    pub pan: Analog2d,
+    pub follow: Gesture,
+    pub reveal_all: Gesture,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            toggle: Gesture::AnyOf(vec![
+                Gesture::KeyTrigger(Scancode::Tab),
+                Gesture::PadTrigger(PadButton::Select),
+            ]),
+            zoom_in: Gesture::KeyHold(Scancode::Equals),
+            zoom_out: Gesture::KeyHold(Scancode::Minus),
+            pan: Analog2d::Gestures {
+                x_positive: Gesture::KeyHold(Scancode::L),
+                x_negative: Gesture::KeyHold(Scancode::J),
+                y_positive: Gesture::KeyHold(Scancode::I),
+                y_negative: Gesture::KeyHold(Scancode::K),
+                step: 1.0,
+            },
+            follow: Gesture::KeyTrigger(Scancode::Key0),
+            reveal_all: Gesture::AllOf(vec![
+                Gesture::KeyHold(Scancode::LControl),
+                Gesture::KeyTrigger(Scancode::M),
+            ]),
+        }
+    }
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    bindings: &'context Bindings,
+
+    tick: &'context Tick,
+    window: &'context Window,
+    input: &'context Input,
+    entities: &'context mut Entities,
+    transforms: &'context Transforms,
+    shaders: &'context mut Shaders,
+    uniforms: &'context mut Uniforms,
+    meshes: &'context mut Meshes,
+    materials: &'context mut Materials,
+    render: &'context mut RenderPipeline,
+
+    wad: &'context WadSystem,
+    player: &'context Player,
+}
+
+/// A 2D map of the level's linedefs, drawn over the 3D view.
+///
+/// Only lines bordering sectors the player has been in are shown, unless `reveal_all` is toggled
+/// on, which (like the vanilla map cheat) shows every line and highlights secrets.
+pub struct Automap {
+    root: EntityId,
+    shader: ShaderId,
+    models: Option<Models>,
+    dirty: bool,
+
+    visible: bool,
+    reveal_all: bool,
+    view: MapView,
+
+    visited: Vec<bool>,
+}
+
+/// The entities drawn while the map is visible, rebuilt when the set of shown lines changes.
+struct Models {
+    id: EntityId,
+    lines_transform: Mat4UniformId,
+    arrow_transform: Mat4UniformId,
+}
+
+impl Automap {
+    fn build_models(&self, deps: &mut Dependencies) -> Result<Models> {
+        let id = deps.entities.add(self.root, "automap_models")?;
+
+        let level = &deps.wad.level;
+        let vertices: Vec<LineVertex> = level
+            .linedefs
+            .iter()
+            .filter(|linedef| self.revealed(level, linedef))
+            .filter_map(|linedef| {
+                let color = line_color(level, linedef, self.reveal_all)?;
+                let start = level.vertex(linedef.start_vertex)?;
+                let end = level.vertex(linedef.end_vertex)?;
+                Some([start, end].map(|point| LineVertex {
+                    a_pos: [point.x, point.y, LINES_DEPTH],
+                    a_color: color,
+                }))
+            })
+            .flatten()
+            .collect();
+        let lines_transform = self.add_model(deps, id, "automap_lines", &vertices)?;
+
+        let arrow: Vec<LineVertex> = ARROW
+            .iter()
+            .map(|&[forward, left]| LineVertex {
+                a_pos: [forward, left, ARROW_DEPTH],
+                a_color: ARROW_COLOR,
+            })
+            .collect();
+        let arrow_transform = self.add_model(deps, id, "automap_arrow", &arrow)?;
+
+        Ok(Models {
+            id,
+            lines_transform,
+            arrow_transform,
+        })
+    }
+
+    fn add_model(
+        &self,
+        deps: &mut Dependencies,
+        parent: EntityId,
+        name: &'static str,
+        vertices: &[LineVertex],
+    ) -> Result<Mat4UniformId> {
+        let entity = deps.entities.add(parent, name)?;
+        let transform =
+            deps.uniforms
+                .add_mat4(deps.entities, entity, "transform_uniform", Mat4::one())?;
+        if vertices.is_empty() {
+            return Ok(transform);
+        }
+        let mesh = deps
+            .meshes
+            .add(deps.window, deps.entities, entity, "mesh")
+            .lines()
+            .immutable(vertices)?
+            .build_unindexed()?;
+        let material = deps
+            .materials
+            .add(deps.entities, entity, self.shader, "material")?
+            .add_uniform("u_transform", transform)
+            .id();
+        deps.render.attach_model(entity, mesh, material);
+        Ok(transform)
+    }
+
+    fn remove_models(&mut self, entities: &mut Entities) {
+        if let Some(models) = self.models.take() {
+            entities.remove(models.id);
+        }
+    }
+
+    fn revealed(&self, level: &WadLevel, linedef: &WadLinedef) -> bool {
+        if self.reveal_all || linedef.always_shown_on_map() {
+            return true;
+        }
+        [level.right_sidedef(linedef), level.left_sidedef(linedef)]
+            .iter()
+            .flatten()
+            .any(|sidedef| {
+                self.visited
+                    .get(usize::from(sidedef.sector))
+                    .copied()
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Maps positions on the horizontal plane of the world (`x` and `z`) to clip space, with the
+    /// level's north pointing up.
+    fn map_transform(&self, aspect_ratio: f32) -> Mat4 {
+        let scale = 1.0 / self.view.half_height;
+        let scale_x = scale / aspect_ratio;
+        // World -z is east and -x is north, see `wad::util::from_wad_coords`.
+        #[rustfmt::skip]
+        let transform = Mat4::new(
+            0.0, -scale, 0.0, 0.0,
+            -scale_x, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            self.view.center.y * scale_x, self.view.center.x * scale, 0.0, 1.0,
+        );
+        transform
+    }
+}
+
+impl<'context> System<'context> for Automap {
+    type Dependencies = Dependencies<'context>;
+    type Error = Error;
+
+    fn debug_name() -> &'static str {
+        "automap"
+    }
+
+    fn create(deps: Dependencies) -> Result<Self> {
+        let root = deps.entities.add_root("automap");
+        let shader = deps.shaders.add(
+            deps.window,
+            deps.entities,
+            root,
+            "automap_shader",
+            LINE_SHADER,
+        )?;
+        Ok(Automap {
+            root,
+            shader,
+            models: None,
+            dirty: true,
+            visible: false,
+            reveal_all: false,
+            view: MapView::default(),
+            visited: vec![false; deps.wad.level.sectors.len()],
+        })
+    }
+
+    fn update(&mut self, mut deps: Dependencies) -> Result<()> {
+        if deps.wad.level_changed() {
+            self.visited = vec![false; deps.wad.level.sectors.len()];
+            self.dirty = true;
+        }
+
+        let bindings = deps.bindings;
+        if deps.input.poll_gesture(&bindings.toggle) {
+            self.visible = !self.visible;
+        }
+        if deps.input.poll_gesture(&bindings.reveal_all) {
+            self.reveal_all = !self.reveal_all;
+            self.dirty = true;
+            info!(
+                "Automap reveal all {}.",
+                if self.reveal_all { "on" } else { "off" }
+            );
+        }
+        if deps.input.poll_gesture(&bindings.follow) {
+            self.view.follow = !self.view.follow;
+        }
+
+        // The player's position and heading on the map come from their camera, which may not be
+        // the one rendering (see `TopDownCamera`).
+        let camera = deps.transforms.get_absolute(deps.player.camera());
+        let (position, heading) = if let Some(camera) = camera {
+            let forward = camera.rot.rotate_vector(vec3(0.0, 0.0, -1.0));
+            (
+                Pnt2f::new(camera.disp.x, camera.disp.z),
+                Vec2f::new(forward.x, forward.z),
+            )
+        } else {
+            (self.view.center, Vec2f::new(-1.0, 0.0))
+        };
+
+        let level = &deps.wad.level;
+        if let Some(sector) = level.sector_at(position) {
+            let visited = &mut self.visited[usize::from(level.sector_id(sector))];
+            if !*visited {
+                *visited = true;
+                self.dirty = true;
+            }
+        }
+
+        if !self.visible {
+            self.remove_models(deps.entities);
+            return Ok(());
+        }
+
+        self.view.update(
+            deps.input,
+            deps.tick.timestep(),
+            (&bindings.zoom_in, &bindings.zoom_out),
+            &bindings.pan,
+            position,
+        );
+
+        if self.dirty || self.models.is_none() {
+            self.remove_models(deps.entities);
+            self.models = Some(self.build_models(&mut deps)?);
+            self.dirty = false;
+        }
+        let models = self.models.as_ref().expect("automap models missing");
+
+        let map_transform = self.map_transform(deps.window.aspect_ratio());
+        let heading = if heading.magnitude2() > 0.0 {
+            heading.normalize() * ARROW_SIZE
+        } else {
+            Vec2f::new(-ARROW_SIZE, 0.0)
+        };
+        #[rustfmt::skip]
+        let player_transform = Mat4::new(
+            heading.x, heading.y, 0.0, 0.0,
+            heading.y, -heading.x, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            position.x, position.y, 0.0, 1.0,
+        );
+        *deps
+            .uniforms
+            .get_mat4_mut(models.lines_transform)
+            .expect("missing automap lines transform") = map_transform;
+        *deps
+            .uniforms
+            .get_mat4_mut(models.arrow_transform)
+            .expect("missing automap arrow transform") = map_transform * player_transform;
+        Ok(())
+    }
+
+    fn teardown(&mut self, deps: Dependencies) -> Result<()> {
+        self.models = None;
+        deps.entities.remove(self.root);
+        Ok(())
+    }
+}
+
+/// The colour of a revealed line, or `None` if it should not be drawn.
+fn line_color(level: &WadLevel, linedef: &WadLinedef, reveal_all: bool) -> Option<[f32; 3]> {
+    if linedef.never_shown_on_map() && !reveal_all {
+        return None;
+    }
+    if let Some(color) = key_color(linedef.special_type) {
+        return Some(color);
+    }
+    let front = level
+        .right_sidedef(linedef)
+        .and_then(|side| level.sidedef_sector(side));
+    let back = level
+        .left_sidedef(linedef)
+        .and_then(|side| level.sidedef_sector(side));
+    let (front, back) = match (front, back) {
+        (Some(front), Some(back)) => (front, back),
+        _ => return Some(WALL_COLOR),
+    };
+    // Secret lines look like plain walls unless revealing everything.
+    if linedef.secret() {
+        return Some(if reveal_all { SECRET_COLOR } else { WALL_COLOR });
+    }
+    if front.floor_height != back.floor_height {
+        Some(FLOOR_CHANGE_COLOR)
+    } else if front.ceiling_height != back.ceiling_height {
+        Some(CEILING_CHANGE_COLOR)
+    } else if reveal_all {
+        Some(NO_CHANGE_COLOR)
+    } else {
+        None
+    }
+}
+
+/// The colour of the key a locked door special needs, if any.
+fn key_color(special_type: SpecialType) -> Option<[f32; 3]> {
+    match special_type {
+        26 | 32 | 99 | 133 => Some(BLUE_KEY_COLOR),
+        27 | 34 | 136 | 137 => Some(YELLOW_KEY_COLOR),
+        28 | 33 | 134 | 135 => Some(RED_KEY_COLOR),
+        _ => None,
+    }
+}
+
+const WALL_COLOR: [f32; 3] = [0.75, 0.0, 0.0];
+const FLOOR_CHANGE_COLOR: [f32; 3] = [0.6, 0.4, 0.2];
+const CEILING_CHANGE_COLOR: [f32; 3] = [0.85, 0.75, 0.25];
+const NO_CHANGE_COLOR: [f32; 3] = [0.4, 0.4, 0.4];
+const SECRET_COLOR: [f32; 3] = [0.75, 0.25, 0.75];
+const BLUE_KEY_COLOR: [f32; 3] = [0.3, 0.45, 1.0];
+const YELLOW_KEY_COLOR: [f32; 3] = [1.0, 1.0, 0.3];
+const RED_KEY_COLOR: [f32; 3] = [1.0, 0.45, 0.45];
+const ARROW_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// The player arrow, as pairs of (forward, left) points scaled by `ARROW_SIZE`.
+const ARROW: [[f32; 2]; 6] = [
+    [-1.0, 0.0],
+    [1.0, 0.0],
+    [1.0, 0.0],
+    [0.4, 0.5],
+    [1.0, 0.0],
+    [0.4, -0.5],
+];
+const ARROW_SIZE: f32 = 0.24;
+
+/// Clip space depths, in front of everything in the 3D view. The arrow is drawn over the lines.
+const LINES_DEPTH: f32 = -0.99;
+const ARROW_DEPTH: f32 = -0.995;
@@ -0,0 +1,153 @@
+use super::automap::Bindings as AutomapBindings;
+use super::camera_path::Bindings as CameraPathBindings;
+use super::errors::{ErrorKind, Result};
+use super::hud::Bindings as HudBindings;
+use super::player::Bindings as PlayerBindings;
+use super::top_down::Bindings as TopDownBindings;
+use engine::Sensitivity;
+use failchain::ResultExt;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Every system's bindings, as read from a TOML bindings file.
+///
+/// The file has one table per system, e.g. `[player]` or `[automap]`, whose keys are the fields of
+/// that system's `Bindings`. Anything left out keeps its default, so a file only needs to list the
+/// bindings it changes:
+///
+/// ```toml
+/// [player]
+/// movement = { gestures = { x_positive = { key_hold = "D" }, x_negative = { key_hold = "Q" },
+///                           y_positive = { key_hold = "S" }, y_negative = { key_hold = "Z" } } }
+/// jump = { any_of = [{ key_hold = "Space" }, { button_hold = "Right" }] }
+///
+/// [mouse]
+/// sensitivity = 0.002
+/// invert_y = true
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BindingsFile {
+    pub player: PlayerBindings,
+    pub hud: HudBindings,
+    pub automap: AutomapBindings,
+    pub top_down: TopDownBindings,
+    pub camera_path: CameraPathBindings,
+    /// Applied on top of the `player` table's `look`.
+    mouse: Mouse,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Mouse {
+    sensitivity: Option<Sensitivity>,
+    invert_y: Option<bool>,
+}
+
+impl BindingsFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .chain_err(|| ErrorKind(format!("cannot read bindings `{}`", path.display())))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Parses the `contents` of a bindings file, naming it `path` in errors.
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let mut bindings: BindingsFile = toml::from_str(contents)
+            .chain_err(|| ErrorKind(format!("invalid bindings `{}`", path.display())))?;
+        bindings
+            .player
+            .look
+            .configure_mouse(bindings.mouse.sensitivity, bindings.mouse.invert_y);
+        Ok(bindings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BindingsFile;
+    use engine::Analog2d;
+    use std::fs;
+    use std::path::Path;
+
+    /// The sensitivity and y-inversion of every mouse in `analog`.
+    fn mice(analog: &Analog2d) -> Vec<(f32, bool)> {
+        match *analog {
+            Analog2d::Mouse {
+                sensitivity,
+                invert_y,
+            } => vec![(sensitivity, invert_y)],
+            Analog2d::Sum { ref analogs } => analogs.iter().flat_map(mice).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn bundled_bindings_load() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/bindings");
+        let paths: Vec<_> = fs::read_dir(&directory)
+            .expect("cannot list bundled bindings")
+            .map(|entry| entry.expect("cannot list bundled bindings").path())
+            .collect();
+        assert!(!paths.is_empty());
+        for path in paths {
+            if let Err(error) = BindingsFile::load(&path) {
+                panic!("{}: {}", path.display(), error);
+            }
+        }
+    }
+
+    #[test]
+    fn mouse_table_configures_look() {
+        let path = Path::new("test.toml");
+        let bindings = BindingsFile::parse(
+            r#"
+            [player]
+            look = { sum = { analogs = [
+                { mouse = { sensitivity = 0.001 } },
+                { pad_stick = { stick = "Right", sensitivity = 0.04 } },
+            ] } }
+
+            [mouse]
+            sensitivity = 0.002
+            invert_y = true
+            "#,
+            path,
+        )
+        .expect("bindings not parsed");
+        assert_eq!(mice(&bindings.player.look), vec![(0.002, true)]);
+        match bindings.player.look {
+            Analog2d::Sum { ref analogs } => match analogs[1] {
+                Analog2d::PadStick { sensitivity, .. } => assert_eq!(sensitivity, 0.04),
+                ref other => panic!("pad stick replaced by {:?}", other),
+            },
+            ref other => panic!("look replaced by {:?}", other),
+        }
+
+        // Settings left out of `[mouse]` keep those of `look`.
+        let bindings = BindingsFile::parse(
+            r#"
+            [player]
+            look = { mouse = { sensitivity = 0.001, invert_y = true } }
+
+            [mouse]
+            sensitivity = 0.003
+            "#,
+            path,
+        )
+        .expect("bindings not parsed");
+        assert_eq!(mice(&bindings.player.look), vec![(0.003, true)]);
+
+        // Without a `[player]` table, the default `look` is configured.
+        let default_look = BindingsFile::default().player.look;
+        let bindings =
+            BindingsFile::parse("[mouse]\ninvert_y = true\n", path).expect("bindings not parsed");
+        let expected: Vec<_> = mice(&default_look)
+            .into_iter()
+            .map(|(sensitivity, _)| (sensitivity, true))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(mice(&bindings.player.look), expected);
+    }
+}
@@ -0,0 +1,473 @@
+use super::errors::{Error, ErrorKind, Result};
+use super::player::{CameraPose, Player};
+use engine::{
+    Capture, ControlFlow, DependenciesFrom, Gesture, Input, Scancode, System, Tick, Transforms,
+};
+use failchain::{bail, ResultExt};
+use log::{error, info};
+use math::{Deg, Pnt3f, Rad};
+use serde::Deserialize;
+use std::f32::consts::PI;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    pub toggle_recording: Gesture,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            toggle_recording: Gesture::KeyTrigger(Scancode::F10),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// A path file to play as soon as the game starts, quitting when it ends.
+    pub play: Option<PathBuf>,
+    /// Saves every frame of the `play` path as numbered PNG files in this directory.
+    pub frames: Option<PathBuf>,
+}
+
+/// A camera flythrough: poses at increasing times, smoothly interpolated in between.
+///
+/// Path files are plain text so keyframes are easy to edit, with one `SECONDS X,Y,Z YAW PITCH`
+/// keyframe per line, in the units of the `screenshot` command's poses.
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Keyframe {
+    time: f32,
+    pose: CameraPose,
+}
+
+impl CameraPath {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .chain_err(|| ErrorKind(format!("cannot read camera path `{}`", path.display())))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Parses the `contents` of a path file, naming it `path` in errors.
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for (i_line, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let keyframe = match parse_keyframe(line) {
+                Some(keyframe) => keyframe,
+                None => bail!(
+                    ErrorKind,
+                    "{}:{}: keyframe format must be `SECONDS X,Y,Z YAW PITCH`",
+                    path.display(),
+                    i_line + 1
+                ),
+            };
+            if let Some(last) = keyframes.last() {
+                if keyframe.time <= last.time {
+                    bail!(
+                        ErrorKind,
+                        "{}:{}: keyframe times must increase",
+                        path.display(),
+                        i_line + 1
+                    );
+                }
+            }
+            keyframes.push(keyframe);
+        }
+        if keyframes.is_empty() {
+            bail!(ErrorKind, "camera path `{}` is empty", path.display());
+        }
+        Ok(CameraPath { keyframes })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = String::from("# SECONDS X,Y,Z YAW PITCH\n");
+        for keyframe in &self.keyframes {
+            let ([x, y, z], yaw, pitch) = keyframe.pose.to_wad();
+            writeln!(
+                contents,
+                "{:.3} {:.1},{:.1},{:.1} {:.2} {:.2}",
+                keyframe.time, x, y, z, yaw.0, pitch.0
+            )
+            .expect("formatting to a string failed");
+        }
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).chain_err(|| {
+                ErrorKind(format!("cannot create directory `{}`", directory.display()))
+            })?;
+        }
+        fs::write(path, contents)
+            .chain_err(|| ErrorKind(format!("cannot write camera path `{}`", path.display())))
+    }
+
+    /// The time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The pose at `time`, on a Catmull-Rom spline through the keyframes' positions and angles.
+    /// Before the first keyframe or after the last, it is held in place.
+    pub fn sample(&self, time: f32) -> CameraPose {
+        let keyframes = &self.keyframes;
+        let next = keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(keyframes.len());
+        if next == 0 {
+            return keyframes[0].pose;
+        } else if next == keyframes.len() {
+            return keyframes[next - 1].pose;
+        }
+        let [before, start, end, after] = [
+            next.saturating_sub(2),
+            next - 1,
+            next,
+            (next + 1).min(keyframes.len() - 1),
+        ]
+        .map(|index| keyframes[index].pose);
+        let t =
+            (time - keyframes[next - 1].time) / (keyframes[next].time - keyframes[next - 1].time);
+        let spline = |[p0, p1, p2, p3]: [f32; 4]| {
+            0.5 * (2.0 * p1
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+        };
+
+        // Turn the shortest way around between keyframes.
+        let start_yaw = start.yaw.0;
+        let end_yaw = start_yaw + wrap_angle(end.yaw.0 - start_yaw);
+        let before_yaw = start_yaw - wrap_angle(start_yaw - before.yaw.0);
+        let after_yaw = end_yaw + wrap_angle(after.yaw.0 - end_yaw);
+
+        CameraPose {
+            position: Pnt3f::new(
+                spline([before, start, end, after].map(|pose| pose.position.x)),
+                spline([before, start, end, after].map(|pose| pose.position.y)),
+                spline([before, start, end, after].map(|pose| pose.position.z)),
+            ),
+            yaw: Rad(spline([before_yaw, start_yaw, end_yaw, after_yaw])),
+            pitch: Rad(spline([before, start, end, after].map(|pose| pose.pitch.0))),
+        }
+    }
+}
+
+/// Wraps an angle difference to `-PI..PI`.
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+fn parse_keyframe(line: &str) -> Option<Keyframe> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (time, position, yaw, pitch) = match fields[..] {
+        [time, position, yaw, pitch] => (time, position, yaw, pitch),
+        _ => return None,
+    };
+    let coordinates = position
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (x, y, z) = match coordinates[..] {
+        [x, y, z] => (x, y, z),
+        _ => return None,
+    };
+    Some(Keyframe {
+        time: time.parse().ok()?,
+        pose: CameraPose::from_wad(x, y, z, Deg(yaw.parse().ok()?), Deg(pitch.parse().ok()?)),
+    })
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    bindings: &'context Bindings,
+    config: &'context Config,
+
+    tick: &'context Tick,
+    input: &'context Input,
+    transforms: &'context Transforms,
+    capture: &'context mut Capture,
+    control_flow: &'context mut ControlFlow,
+
+    player: &'context mut Player,
+}
+
+/// Records the player's camera into path files, and plays back the path in the `Config`, posing
+/// the camera with player input disabled and quitting when it ends.
+pub struct CameraPaths {
+    recording: Option<Recording>,
+    playback: Option<Playback>,
+}
+
+struct Recording {
+    keyframes: Vec<Keyframe>,
+    time: f32,
+}
+
+struct Playback {
+    path: CameraPath,
+    time: f32,
+    /// Whether to save every rendered frame with `Capture`.
+    frames: bool,
+}
+
+impl CameraPaths {
+    fn toggle_recording(&mut self, deps: &Dependencies) {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => {
+                info!("Recording camera path...");
+                self.recording = Some(Recording {
+                    keyframes: Vec::new(),
+                    time: 0.0,
+                });
+                return;
+            }
+        };
+        if recording.keyframes.is_empty() {
+            info!("Stopped recording camera path, nothing recorded.");
+            return;
+        }
+        let path = deps.capture.timestamped_path("camera-path", Some("txt"));
+        let camera_path = CameraPath {
+            keyframes: recording.keyframes,
+        };
+        match camera_path.save(&path) {
+            Ok(()) => info!(
+                "Saved camera path with {} keyframes to `{}`.",
+                camera_path.keyframes.len(),
+                path.display()
+            ),
+            Err(e) => error!("Failed to save camera path: {}", e),
+        }
+    }
+
+    fn record(&mut self, deps: &Dependencies) {
+        let recording = match self.recording.as_mut() {
+            Some(recording) => recording,
+            None => return,
+        };
+        let transform = match deps.transforms.get_absolute(deps.player.camera()) {
+            Some(transform) => transform,
+            None => return,
+        };
+        let keyframe_due = recording.keyframes.last().is_none_or(|last| {
+            recording.time - last.time >= RECORD_INTERVAL - deps.tick.timestep() / 2.0
+        });
+        if keyframe_due {
+            recording.keyframes.push(Keyframe {
+                time: recording.time,
+                pose: CameraPose::from_transform(transform),
+            });
+        }
+        recording.time += deps.tick.timestep();
+    }
+
+    fn play(&mut self, deps: &mut Dependencies) {
+        let playback = match self.playback.as_mut() {
+            Some(playback) => playback,
+            None => return,
+        };
+        // A pose set now is only rendered `RENDER_LAG_TICKS` later: the player applies it next
+        // tick, and its transform is propagated the tick after that.
+        let rendered_time = playback.time - RENDER_LAG_TICKS * deps.tick.timestep();
+        if rendered_time > playback.path.duration() {
+            info!("Camera path finished.");
+            if playback.frames {
+                deps.capture.stop_recording();
+            }
+            deps.control_flow.quit_requested = true;
+            deps.player.set_pose(None);
+            self.playback = None;
+            return;
+        }
+        if playback.frames && rendered_time >= 0.0 && !deps.capture.is_recording() {
+            if let Some(ref frames) = deps.config.frames {
+                deps.capture.start_recording_to(frames.clone());
+            }
+        }
+        deps.player
+            .set_pose(Some(playback.path.sample(playback.time)));
+        playback.time += deps.tick.timestep();
+    }
+}
+
+impl<'context> System<'context> for CameraPaths {
+    type Dependencies = Dependencies<'context>;
+    type Error = Error;
+
+    fn debug_name() -> &'static str {
+        "camera_paths"
+    }
+
+    fn create(deps: Dependencies) -> Result<Self> {
+        let playback = match deps.config.play {
+            Some(ref path) => {
+                let path = CameraPath::load(path)?;
+                info!("Playing camera path of {:.1}s...", path.duration());
+                Some(Playback {
+                    path,
+                    time: 0.0,
+                    frames: deps.config.frames.is_some(),
+                })
+            }
+            None => None,
+        };
+        Ok(CameraPaths {
+            recording: None,
+            playback,
+        })
+    }
+
+    fn update(&mut self, mut deps: Dependencies) -> Result<()> {
+        if deps.input.poll_gesture(&deps.bindings.toggle_recording) {
+            self.toggle_recording(&deps);
+        }
+        self.record(&deps);
+        self.play(&mut deps);
+        Ok(())
+    }
+}
+
+/// Seconds between recorded keyframes; the spline smooths out the motion in between.
+const RECORD_INTERVAL: f32 = 0.25;
+
+const RENDER_LAG_TICKS: f32 = 2.0;
+
+#[cfg(test)]
+mod test {
+    use super::{parse_keyframe, wrap_angle, CameraPath, CameraPose, Keyframe};
+    use math::Deg;
+    use std::f32::consts::PI;
+    use std::path::Path;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// A path through `(seconds, [x, y, z], yaw)` keyframes, in WAD units, without pitch.
+    fn path(keyframes: &[(f32, [f32; 3], f32)]) -> CameraPath {
+        CameraPath {
+            keyframes: keyframes
+                .iter()
+                .map(|&(time, [x, y, z], yaw)| Keyframe {
+                    time,
+                    pose: CameraPose::from_wad(x, y, z, Deg(yaw), Deg(0.0)),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn samples_pass_through_keyframes() {
+        let path = path(&[
+            (0.0, [0.0, 0.0, 41.0], 0.0),
+            (1.0, [100.0, 50.0, 41.0], 45.0),
+            (3.0, [300.0, -20.0, 80.0], 90.0),
+            (4.0, [250.0, 0.0, 60.0], 30.0),
+        ]);
+        for keyframe in &path.keyframes {
+            let sample = path.sample(keyframe.time);
+            assert_close(sample.position.x, keyframe.pose.position.x);
+            assert_close(sample.position.y, keyframe.pose.position.y);
+            assert_close(sample.position.z, keyframe.pose.position.z);
+            assert_close(sample.yaw.0, keyframe.pose.yaw.0);
+        }
+        // The first and last poses are held outside of the path.
+        assert_close(
+            path.sample(-1.0).position.z,
+            path.keyframes[0].pose.position.z,
+        );
+        assert_close(
+            path.sample(5.0).position.z,
+            path.keyframes[3].pose.position.z,
+        );
+    }
+
+    #[test]
+    fn samples_interpolate_between_keyframes() {
+        // Evenly spaced keyframes on a line stay on it, at a constant speed.
+        let path = path(&[
+            (0.0, [0.0, 0.0, 0.0], 0.0),
+            (1.0, [100.0, 0.0, 0.0], 10.0),
+            (2.0, [200.0, 0.0, 0.0], 20.0),
+            (3.0, [300.0, 0.0, 0.0], 30.0),
+        ]);
+        let ([x, y, z], yaw, _) = path.sample(1.25).to_wad();
+        assert_close(x, 125.0);
+        assert_close(y, 0.0);
+        assert_close(z, 0.0);
+        assert_close(yaw.0, 12.5);
+    }
+
+    #[test]
+    fn yaw_turns_the_shortest_way() {
+        let path = path(&[(0.0, [0.0; 3], 170.0), (1.0, [0.0; 3], -170.0)]);
+        let yaw = path.sample(0.5).yaw.0;
+        assert_close(wrap_angle(yaw - PI), 0.0);
+    }
+
+    #[test]
+    fn angles_wrap_to_half_turns() {
+        assert_close(wrap_angle(0.5), 0.5);
+        assert_close(wrap_angle(1.5 * PI), -0.5 * PI);
+        assert_close(wrap_angle(-1.5 * PI), 0.5 * PI);
+        assert_close(wrap_angle(4.0 * PI + 0.25), 0.25);
+    }
+
+    #[test]
+    fn keyframes_are_parsed() {
+        let keyframe = parse_keyframe("1.5 100,-200,41 90 -10").expect("keyframe not parsed");
+        assert_close(keyframe.time, 1.5);
+        let ([x, y, z], yaw, pitch) = keyframe.pose.to_wad();
+        assert_close(x, 100.0);
+        assert_close(y, -200.0);
+        assert_close(z, 41.0);
+        assert_close(yaw.0, 90.0);
+        assert_close(pitch.0, -10.0);
+
+        assert!(parse_keyframe("1.5 100,-200,41 90").is_none());
+        assert!(parse_keyframe("1.5 100,-200 90 -10").is_none());
+        assert!(parse_keyframe("1.5 100,-200,41,0 90 -10").is_none());
+        assert!(parse_keyframe("soon 100,-200,41 90 -10").is_none());
+        assert!(parse_keyframe("1.5 100,north,41 90 -10").is_none());
+    }
+
+    #[test]
+    fn bad_paths_are_rejected() {
+        let file = Path::new("test.txt");
+        let parse = |contents| CameraPath::parse(contents, file).map(|path| path.keyframes.len());
+        assert_eq!(
+            parse("# SECONDS X,Y,Z YAW PITCH\n\n0 0,0,0 0 0\n1 1,1,1 0 0\n").ok(),
+            Some(2)
+        );
+        assert!(parse("# SECONDS X,Y,Z YAW PITCH\n").is_err());
+        assert!(parse("0 0,0,0 0 0\n0 1,1,1 0 0\n").is_err());
+        assert!(parse("0 0,0,0 0 0\n1 1,1,1\n").is_err());
+    }
+
+    #[test]
+    fn poses_round_trip_through_wad_units() {
+        let pose = CameraPose::from_wad(1056.0, -3616.0, 41.0, Deg(270.0), Deg(-12.5));
+        let ([x, y, z], yaw, pitch) = pose.to_wad();
+        assert_close(x, 1056.0);
+        assert_close(y, -3616.0);
+        assert_close(z, 41.0);
+        assert_close(yaw.0, 270.0);
+        assert_close(pitch.0, -12.5);
+    }
+}
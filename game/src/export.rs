@@ -0,0 +1,964 @@
+//! Exports the geometry of a level as a textured mesh, for use in modelling tools like Blender.
+//!
+//! Walls, flats and decorations are baked into a single RGBA texture atlas with the default
+//! palette. Skies are exported untextured, with the average colour of the level's sky texture. The
+//! static geometry and each dynamic sector (doors, lifts etc.) end up in separate nodes named after
+//! their `ObjectId`, in world units with Y up (one unit is a hundred map units).
+
+use super::errors::{ErrorKind, Result};
+use super::game::GameConfig;
+use failchain::{bail, ResultExt};
+use log::{info, warn};
+use math::{Pnt2f, Pnt3f, Vec2f, Vec3f};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use wad::{
+    Archive, Decor, DecorRotation, Level as WadLevel, LevelAnalysis, LevelVisitor, LevelWalker,
+    ObjectId, Result as WadResult, SkyPoly, SkyQuad, StaticPoly, StaticQuad, TextureDirectory,
+    WadName,
+};
+
+/// Flats are always 64x64 texels.
+const FLAT_SIZE: f32 = 64.0;
+
+/// Texels of padding around each atlas tile, copied from the tile's edges so filtering in other
+/// tools doesn't bleed neighbouring textures in.
+const ATLAS_PADDING: usize = 1;
+
+/// The colour of sky surfaces when the level's sky texture cannot be found.
+const DEFAULT_SKY_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A `.gltf` file, with the buffers in a `.bin` and the atlas in a `.png` next to it.
+    Gltf,
+    /// A Wavefront `.obj` file, with its materials in a `.mtl` and the atlas in a `.png` next to it.
+    Obj,
+}
+
+/// Loads the level picked by `config` and writes its geometry to `out` in the given format.
+pub fn export_level(config: &GameConfig, format: ExportFormat, out: &Path) -> Result<()> {
+    let (archive, textures) = (|| -> WadResult<_> {
+        let archive = Archive::open(&config.wad_file, &config.metadata_file)?;
+        let textures = TextureDirectory::from_archive(&archive)?;
+        Ok((archive, textures))
+    })()
+    .chain_err(|| ErrorKind(format!("WAD setup failed for {:?}", config.wad_file)))?;
+
+    let level_index = config.initial_level_index;
+    if level_index >= archive.num_levels() {
+        bail!(
+            ErrorKind,
+            "Level index {} is not in valid range 0..{}, see --list-levels for level names.",
+            level_index,
+            archive.num_levels()
+        );
+    }
+    let level_name = archive
+        .level_lump(level_index)
+        .map(|lump| lump.name())
+        .chain_err(|| ErrorKind(format!("when reading name of level {}", level_index)))?;
+    info!("Exporting level {:?} ({})...", level_name, level_index);
+    let level = WadLevel::from_archive(&archive, level_index)
+        .chain_err(|| ErrorKind(format!("when loading WAD level {}", level_index)))?;
+    let analysis = LevelAnalysis::new(&level, archive.metadata());
+
+    let mut collector = Collector::new(&textures);
+    LevelWalker::new(
+        &level,
+        &analysis,
+        &textures,
+        archive.metadata(),
+        &mut collector,
+    )
+    .with_skill(config.skill, config.mode)
+    .walk();
+
+    let atlas = Atlas::build(&textures, &collector.texture_keys());
+    let sky_color = archive
+        .metadata()
+        .sky_for(level_name)
+        .and_then(|sky| textures.texture(sky.texture_name))
+        .map_or(DEFAULT_SKY_COLOR, |image| {
+            average_color(&textures, image.pixels())
+        });
+    let meshes = collector.into_meshes(&atlas);
+    info!(
+        "Collected {} nodes, {} triangles and a {}x{} atlas.",
+        meshes.len(),
+        meshes
+            .iter()
+            .map(|mesh| (mesh.textured.indices.len() + mesh.sky.indices.len()) / 3)
+            .sum::<usize>(),
+        atlas.width,
+        atlas.height
+    );
+
+    let atlas_path = out.with_extension("png");
+    atlas.save_png(&atlas_path)?;
+    match format {
+        ExportFormat::Gltf => write_gltf(
+            out,
+            level_name.as_ref().trim_end_matches('\0'),
+            &meshes,
+            &atlas_path,
+            sky_color,
+        )?,
+        ExportFormat::Obj => write_obj(out, &meshes, &atlas_path, sky_color)?,
+    }
+    info!("Exported level to {}.", out.display());
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum TextureKey {
+    /// A wall texture or sprite, looked up with `TextureDirectory::texture`.
+    Image(WadName),
+    Flat(WadName),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Surface {
+    Textured(TextureKey),
+    Sky,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct FaceVertex {
+    pos: Pnt3f,
+    /// Texel coordinates, within a single repetition of the face's texture.
+    uv: Vec2f,
+}
+
+impl FaceVertex {
+    fn new(xz: Pnt2f, y: f32, u: f32, v: f32) -> Self {
+        FaceVertex {
+            pos: Pnt3f::new(xz[0], y, xz[1]),
+            uv: Vec2f::new(u, v),
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        FaceVertex {
+            pos: self.pos + (other.pos - self.pos) * t,
+            uv: self.uv + (other.uv - self.uv) * t,
+        }
+    }
+}
+
+/// A convex polygon, wound counter-clockwise when seen from the front.
+struct Face {
+    surface: Surface,
+    vertices: Vec<FaceVertex>,
+}
+
+/// A `LevelVisitor` which collects the faces of every object in the level.
+struct Collector<'a> {
+    textures: &'a TextureDirectory,
+    objects: BTreeMap<u32, Vec<Face>>,
+}
+
+impl<'a> Collector<'a> {
+    fn new(textures: &'a TextureDirectory) -> Self {
+        Collector {
+            textures,
+            objects: BTreeMap::new(),
+        }
+    }
+
+    /// The textures used by the collected faces, in the order they were first used.
+    fn texture_keys(&self) -> Vec<TextureKey> {
+        let mut seen = HashSet::new();
+        self.objects
+            .values()
+            .flatten()
+            .filter_map(|face| match face.surface {
+                Surface::Textured(key) if seen.insert(key) => Some(key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn texture_size(&self, key: TextureKey) -> Option<Vec2f> {
+        match key {
+            TextureKey::Image(name) => self
+                .textures
+                .texture(name)
+                .map(|image| Vec2f::new(image.width() as f32, image.height() as f32)),
+            TextureKey::Flat(name) => self
+                .textures
+                .flat(name)
+                .map(|_| Vec2f::new(FLAT_SIZE, FLAT_SIZE)),
+        }
+    }
+
+    /// Adds a textured face, split at the texture's edges so that each piece maps to a single copy
+    /// of the texture in the atlas.
+    fn add_textured(&mut self, object_id: ObjectId, key: TextureKey, vertices: Vec<FaceVertex>) {
+        let size = if let Some(size) = self.texture_size(key) {
+            size
+        } else {
+            warn!("No such texture {:?}.", key);
+            return;
+        };
+        if size[0] < 1.0 || size[1] < 1.0 {
+            warn!("Empty texture {:?}, skipping face.", key);
+            return;
+        }
+        let faces = self.objects.entry(object_id.0).or_default();
+        for vertices in split_into_tiles(vertices, size) {
+            faces.push(Face {
+                surface: Surface::Textured(key),
+                vertices,
+            });
+        }
+    }
+
+    fn add_sky(&mut self, object_id: ObjectId, vertices: Vec<FaceVertex>) {
+        self.objects.entry(object_id.0).or_default().push(Face {
+            surface: Surface::Sky,
+            vertices,
+        });
+    }
+
+    fn add_flat(&mut self, poly: &StaticPoly, vertices: impl Iterator<Item = Pnt2f>) {
+        let vertices = vertices
+            .map(|xz| FaceVertex::new(xz, poly.height, -xz[0] * 100.0, -xz[1] * 100.0))
+            .collect();
+        self.add_textured(poly.object_id, TextureKey::Flat(poly.tex_name), vertices);
+    }
+
+    fn add_sky_poly(&mut self, poly: &SkyPoly, vertices: impl Iterator<Item = Pnt2f>) {
+        let vertices = vertices
+            .map(|xz| FaceVertex::new(xz, poly.height, 0.0, 0.0))
+            .collect();
+        self.add_sky(poly.object_id, vertices);
+    }
+
+    /// Triangulates the collected faces, mapping their texture coordinates into the atlas.
+    fn into_meshes(self, atlas: &Atlas) -> Vec<Mesh> {
+        self.objects
+            .into_iter()
+            .map(|(object_id, faces)| {
+                let mut mesh = Mesh {
+                    name: format!("object_{}", object_id),
+                    textured: Primitive::default(),
+                    sky: Primitive::default(),
+                };
+                for face in faces {
+                    match face.surface {
+                        Surface::Textured(key) => {
+                            let offset = atlas.offsets[&key];
+                            let scale =
+                                Vec2f::new(1.0 / atlas.width as f32, 1.0 / atlas.height as f32);
+                            mesh.textured.add_face(face.vertices.iter().map(|vertex| {
+                                let uv = offset + vertex.uv;
+                                (vertex.pos, [uv[0] * scale[0], uv[1] * scale[1]])
+                            }));
+                        }
+                        Surface::Sky => mesh
+                            .sky
+                            .add_face(face.vertices.iter().map(|vertex| (vertex.pos, [0.0; 2]))),
+                    }
+                }
+                mesh
+            })
+            .collect()
+    }
+}
+
+impl<'a> LevelVisitor for Collector<'a> {
+    fn visit_wall_quad(&mut self, quad: &StaticQuad) {
+        let &StaticQuad {
+            object_id,
+            tex_name,
+            vertices: (v1, v2),
+            height_range: (low, high),
+            tex_start: (s1, t1),
+            tex_end: (s2, t2),
+            ..
+        } = quad;
+        let tex_name = if let Some(tex_name) = tex_name {
+            tex_name
+        } else {
+            return;
+        };
+        let vertices = vec![
+            FaceVertex::new(v1, low, s1, t1),
+            FaceVertex::new(v2, low, s2, t1),
+            FaceVertex::new(v2, high, s2, t2),
+            FaceVertex::new(v1, high, s1, t2),
+        ];
+        self.add_textured(object_id, TextureKey::Image(tex_name), vertices);
+    }
+
+    fn visit_floor_poly(&mut self, poly: &StaticPoly) {
+        self.add_flat(poly, poly.vertices.iter().cloned());
+    }
+
+    fn visit_ceil_poly(&mut self, poly: &StaticPoly) {
+        self.add_flat(poly, poly.vertices.iter().rev().cloned());
+    }
+
+    fn visit_floor_sky_poly(&mut self, poly: &SkyPoly) {
+        self.add_sky_poly(poly, poly.vertices.iter().cloned());
+    }
+
+    fn visit_ceil_sky_poly(&mut self, poly: &SkyPoly) {
+        self.add_sky_poly(poly, poly.vertices.iter().rev().cloned());
+    }
+
+    fn visit_sky_quad(&mut self, quad: &SkyQuad) {
+        let &SkyQuad {
+            object_id,
+            vertices: (v1, v2),
+            height_range: (low, high),
+        } = quad;
+        let vertices = vec![
+            FaceVertex::new(v1, low, 0.0, 0.0),
+            FaceVertex::new(v2, low, 0.0, 0.0),
+            FaceVertex::new(v2, high, 0.0, 0.0),
+            FaceVertex::new(v1, high, 0.0, 0.0),
+        ];
+        self.add_sky(object_id, vertices);
+    }
+
+    fn visit_decor(&mut self, decor: &Decor) {
+        // Sprites become a single quad showing the thing's front, facing the way the thing does.
+        let front = DecorRotation {
+            low: decor.low,
+            high: decor.high,
+            half_width: decor.half_width,
+            tex_name: decor.tex_name,
+            mirrored: false,
+        };
+        let &DecorRotation {
+            low,
+            high,
+            half_width,
+            tex_name,
+            mirrored,
+        } = decor.rotations.first().unwrap_or(&front);
+        let size = if let Some(size) = self.texture_size(TextureKey::Image(tex_name)) {
+            size
+        } else {
+            warn!("No such decor texture {}.", tex_name);
+            return;
+        };
+
+        // The right-hand direction of someone standing in front of the thing, looking at it.
+        let yaw = decor.yaw.0;
+        let right = Vec3f::new(-yaw.cos(), 0.0, yaw.sin()) * half_width;
+        let (left_u, right_u) = if mirrored {
+            (size[0], 0.0)
+        } else {
+            (0.0, size[0])
+        };
+        let vertex = |pos: Pnt3f, u: f32, v: f32| FaceVertex {
+            pos,
+            uv: Vec2f::new(u, v),
+        };
+        let vertices = vec![
+            vertex(low - right, left_u, size[1]),
+            vertex(low + right, right_u, size[1]),
+            vertex(high + right, right_u, 0.0),
+            vertex(high - right, left_u, 0.0),
+        ];
+        self.add_textured(decor.object_id, TextureKey::Image(tex_name), vertices);
+    }
+}
+
+/// Splits a face into pieces which each cover a single repetition of a texture of the given size,
+/// with texture coordinates relative to that repetition.
+fn split_into_tiles(vertices: Vec<FaceVertex>, size: Vec2f) -> Vec<Vec<FaceVertex>> {
+    let mut pieces = vec![vertices];
+    for axis in 0..2 {
+        pieces = pieces
+            .into_iter()
+            .flat_map(|piece| split_along(&piece, axis, size[axis].max(1.0)))
+            .collect();
+    }
+    pieces
+}
+
+fn split_along(vertices: &[FaceVertex], axis: usize, size: f32) -> Vec<Vec<FaceVertex>> {
+    if vertices.is_empty() {
+        return Vec::new();
+    }
+    let (min, max) = vertices
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v.uv[axis]), max.max(v.uv[axis]))
+        });
+    let first = (min / size).floor() as i64;
+    let last = ((max / size).ceil() as i64 - 1).max(first);
+    (first..=last)
+        .filter_map(|tile| {
+            let start = tile as f32 * size;
+            let piece = clip(vertices, |v| v.uv[axis] - start);
+            let mut piece = clip(&piece, |v| start + size - v.uv[axis]);
+            if piece.len() < 3 {
+                return None;
+            }
+            for vertex in &mut piece {
+                vertex.uv[axis] = (vertex.uv[axis] - start).max(0.0).min(size);
+            }
+            Some(piece)
+        })
+        .collect()
+}
+
+/// Clips a convex polygon to the part where `distance` is not negative.
+fn clip(vertices: &[FaceVertex], distance: impl Fn(&FaceVertex) -> f32) -> Vec<FaceVertex> {
+    let mut clipped = Vec::with_capacity(vertices.len() + 1);
+    for (index, current) in vertices.iter().enumerate() {
+        let next = &vertices[(index + 1) % vertices.len()];
+        let (current_distance, next_distance) = (distance(current), distance(next));
+        if current_distance >= 0.0 {
+            clipped.push(*current);
+        }
+        if (current_distance > 0.0 && next_distance < 0.0)
+            || (current_distance < 0.0 && next_distance > 0.0)
+        {
+            let t = current_distance / (current_distance - next_distance);
+            clipped.push(current.lerp(next, t));
+        }
+    }
+    clipped
+}
+
+/// The RGBA colours of all the textures used by a level, packed into rows.
+struct Atlas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    /// The top-left corner of each texture in the atlas, in texels.
+    offsets: HashMap<TextureKey, Vec2f>,
+}
+
+impl Atlas {
+    fn build(textures: &TextureDirectory, keys: &[TextureKey]) -> Self {
+        let palette = &textures.palette(0).0;
+        let color = |index: u8| {
+            let index = usize::from(index) * 3;
+            [palette[index], palette[index + 1], palette[index + 2], 255]
+        };
+        let mut images: Vec<(TextureKey, usize, usize, Vec<[u8; 4]>)> = keys
+            .iter()
+            .filter_map(|&key| match key {
+                TextureKey::Image(name) => textures.texture(name).map(|image| {
+                    let pixels = image
+                        .pixels()
+                        .iter()
+                        .map(|&pixel| {
+                            if pixel & 0xff00 == 0 {
+                                color(pixel as u8)
+                            } else {
+                                [0; 4]
+                            }
+                        })
+                        .collect();
+                    (key, image.width(), image.height(), pixels)
+                }),
+                TextureKey::Flat(name) => textures.flat(name).map(|flat| {
+                    let size = FLAT_SIZE as usize;
+                    (
+                        key,
+                        size,
+                        size,
+                        flat.iter().map(|&index| color(index)).collect(),
+                    )
+                }),
+            })
+            // Empty images cannot be clamped to their edges, and are never drawn anyway.
+            .filter(|&(_, width, height, _)| width > 0 && height > 0)
+            .collect();
+        images.sort_by_key(|&(_, _, height, _)| std::cmp::Reverse(height));
+
+        // Pack the padded images into shelves, in a power-of-two wide atlas big enough to keep it
+        // roughly square.
+        let padded = |size: usize| size + 2 * ATLAS_PADDING;
+        let area: usize = images
+            .iter()
+            .map(|&(_, width, height, _)| padded(width) * padded(height))
+            .sum();
+        let widest = images
+            .iter()
+            .map(|&(_, width, _, _)| padded(width))
+            .max()
+            .unwrap_or(1);
+        let width = ((area as f64).sqrt().ceil() as usize)
+            .max(widest)
+            .next_power_of_two();
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        let mut positions = Vec::with_capacity(images.len());
+        for &(_, image_width, image_height, _) in &images {
+            if x + padded(image_width) > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            positions.push((x + ATLAS_PADDING, y + ATLAS_PADDING));
+            x += padded(image_width);
+            shelf_height = shelf_height.max(padded(image_height));
+        }
+        let height = (y + shelf_height).max(1).next_power_of_two();
+
+        let mut pixels = vec![0; width * height * 4];
+        let mut offsets = HashMap::with_capacity(images.len());
+        for ((key, image_width, image_height, image), &(left, top)) in images.iter().zip(&positions)
+        {
+            // Copy the image along with its padding, clamping to its edges.
+            for y in 0..padded(*image_height) {
+                let source_y = y.saturating_sub(ATLAS_PADDING).min(image_height - 1);
+                for x in 0..padded(*image_width) {
+                    let source_x = x.saturating_sub(ATLAS_PADDING).min(image_width - 1);
+                    let target = ((top - ATLAS_PADDING + y) * width + left - ATLAS_PADDING + x) * 4;
+                    pixels[target..target + 4]
+                        .copy_from_slice(&image[source_y * image_width + source_x]);
+                }
+            }
+            offsets.insert(*key, Vec2f::new(left as f32, top as f32));
+        }
+
+        Atlas {
+            width,
+            height,
+            pixels,
+            offsets,
+        }
+    }
+
+    fn save_png(&self, path: &Path) -> Result<()> {
+        let error = |error: &dyn std::fmt::Display| {
+            ErrorKind(format!("Cannot write `{}`: {}", path.display(), error))
+        };
+        let file = File::create(path).map_err(|e| error(&e))?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| error(&e))?;
+        writer.finish().map_err(|e| error(&e))?;
+        Ok(())
+    }
+}
+
+/// The average colour of the opaque pixels of a texture, for untextured skies.
+fn average_color(textures: &TextureDirectory, pixels: &[u16]) -> [f32; 3] {
+    let palette = &textures.palette(0).0;
+    let mut sum = [0.0; 3];
+    let mut count = 0;
+    for &pixel in pixels.iter().filter(|&&pixel| pixel & 0xff00 == 0) {
+        let index = usize::from(pixel as u8) * 3;
+        for (channel, sum) in sum.iter_mut().enumerate() {
+            *sum += f32::from(palette[index + channel]) / 255.0;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return DEFAULT_SKY_COLOR;
+    }
+    let [r, g, b] = sum;
+    [r / count as f32, g / count as f32, b / count as f32]
+}
+
+/// Indexed triangles with a single material.
+#[derive(Default)]
+struct Primitive {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl Primitive {
+    /// Adds a convex polygon as a triangle fan. Polygons with fewer than 3 vertices are skipped.
+    fn add_face(&mut self, vertices: impl Iterator<Item = (Pnt3f, [f32; 2])>) {
+        let vertices: Vec<_> = vertices.collect();
+        if vertices.len() < 3 {
+            return;
+        }
+        let first = self.positions.len() as u32;
+        for (pos, uv) in vertices {
+            self.positions.push([pos[0], pos[1], pos[2]]);
+            self.uvs.push(uv);
+        }
+        for index in first + 1..self.positions.len() as u32 - 1 {
+            self.indices.extend_from_slice(&[first, index, index + 1]);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// The geometry of one object: the atlas-textured faces and the sky faces.
+struct Mesh {
+    name: String,
+    textured: Primitive,
+    sky: Primitive,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+/// Quotes `value` as a JSON string, escaping quotes, backslashes and control characters.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c < ' ' => {
+                write!(quoted, "\\u{:04x}", c as u32).expect("writing to a string cannot fail")
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>> {
+    File::create(path)
+        .map(BufWriter::new)
+        .chain_err(|| ErrorKind(format!("Cannot create `{}`", path.display())))
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    create_file(path)?
+        .write_all(contents)
+        .chain_err(|| ErrorKind(format!("Cannot write `{}`", path.display())))
+}
+
+/// Writes a glTF 2.0 scene with a root node for the level and a child node per object.
+fn write_gltf(
+    path: &Path,
+    level_name: &str,
+    meshes: &[Mesh],
+    atlas_path: &Path,
+    sky_color: [f32; 3],
+) -> Result<()> {
+    const ARRAY_BUFFER: u32 = 34_962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34_963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const NEAREST: u32 = 9728;
+    const CLAMP_TO_EDGE: u32 = 33_071;
+
+    let bin_path = path.with_extension("bin");
+    let mut buffer = Vec::<u8>::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut gltf_meshes = Vec::new();
+    // The first node is the level's root, filled in once its children are known.
+    let mut nodes = vec![String::new()];
+
+    // Appends a buffer view and an accessor for it, returning the accessor's index.
+    let mut add_accessor = |data: Vec<u8>, target: u32, accessor: String| {
+        views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            buffer.len(),
+            data.len(),
+            target
+        ));
+        buffer.extend(data);
+        accessors.push(format!(
+            r#"{{"bufferView":{},{}}}"#,
+            views.len() - 1,
+            accessor
+        ));
+        accessors.len() - 1
+    };
+
+    for mesh in meshes {
+        let mut primitives = Vec::new();
+        for (material, primitive) in [(0, &mesh.textured), (1, &mesh.sky)] {
+            if primitive.is_empty() {
+                continue;
+            }
+            let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+            for position in &primitive.positions {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+            let positions = add_accessor(
+                primitive
+                    .positions
+                    .iter()
+                    .flatten()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect(),
+                ARRAY_BUFFER,
+                format!(
+                    r#""componentType":{},"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]"#,
+                    FLOAT,
+                    primitive.positions.len(),
+                    min[0],
+                    min[1],
+                    min[2],
+                    max[0],
+                    max[1],
+                    max[2]
+                ),
+            );
+            let uvs = add_accessor(
+                primitive
+                    .uvs
+                    .iter()
+                    .flatten()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect(),
+                ARRAY_BUFFER,
+                format!(
+                    r#""componentType":{},"count":{},"type":"VEC2""#,
+                    FLOAT,
+                    primitive.uvs.len()
+                ),
+            );
+            let indices = add_accessor(
+                primitive
+                    .indices
+                    .iter()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect(),
+                ELEMENT_ARRAY_BUFFER,
+                format!(
+                    r#""componentType":{},"count":{},"type":"SCALAR""#,
+                    UNSIGNED_INT,
+                    primitive.indices.len()
+                ),
+            );
+            primitives.push(format!(
+                r#"{{"attributes":{{"POSITION":{},"TEXCOORD_0":{}}},"indices":{},"material":{}}}"#,
+                positions, uvs, indices, material
+            ));
+        }
+        if primitives.is_empty() {
+            continue;
+        }
+        gltf_meshes.push(format!(
+            r#"{{"name":{},"primitives":[{}]}}"#,
+            json_string(&mesh.name),
+            primitives.join(",")
+        ));
+        nodes.push(format!(
+            r#"{{"name":{},"mesh":{}}}"#,
+            json_string(&mesh.name),
+            gltf_meshes.len() - 1
+        ));
+    }
+
+    let children = (1..nodes.len())
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    nodes[0] = format!(
+        r#"{{"name":{},"children":[{}]}}"#,
+        json_string(level_name),
+        children
+    );
+    let mut json = String::new();
+    write!(
+        json,
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"rs_doom"}},"scene":0,"#,
+            r#""scenes":[{{"name":{level},"nodes":[0]}}],"#,
+            r#""nodes":[{nodes}],"#,
+            r#""meshes":[{meshes}],"#,
+            r#""materials":["#,
+            r#"{{"name":"atlas","pbrMetallicRoughness":{{"baseColorTexture":{{"index":0}},"#,
+            r#""metallicFactor":0.0,"roughnessFactor":1.0}},"alphaMode":"MASK","doubleSided":true}},"#,
+            r#"{{"name":"sky","pbrMetallicRoughness":{{"baseColorFactor":[{r},{g},{b},1.0],"#,
+            r#""metallicFactor":0.0,"roughnessFactor":1.0}}}}],"#,
+            r#""textures":[{{"sampler":0,"source":0}}],"#,
+            r#""images":[{{"uri":{atlas}}}],"#,
+            r#""samplers":[{{"magFilter":{nearest},"minFilter":{nearest},"#,
+            r#""wrapS":{clamp},"wrapT":{clamp}}}],"#,
+            r#""buffers":[{{"uri":{bin},"byteLength":{length}}}],"#,
+            r#""bufferViews":[{views}],"#,
+            r#""accessors":[{accessors}]}}"#,
+        ),
+        level = json_string(level_name),
+        nodes = nodes.join(","),
+        meshes = gltf_meshes.join(","),
+        r = sky_color[0],
+        g = sky_color[1],
+        b = sky_color[2],
+        atlas = json_string(&file_name(atlas_path)),
+        nearest = NEAREST,
+        clamp = CLAMP_TO_EDGE,
+        bin = json_string(&file_name(&bin_path)),
+        length = buffer.len(),
+        views = views.join(","),
+        accessors = accessors.join(","),
+    )
+    .expect("writing to a string cannot fail");
+
+    write_file(&bin_path, &buffer)?;
+    write_file(path, json.as_bytes())
+}
+
+/// Writes a Wavefront OBJ file with an object per `Mesh`, along with its material library.
+fn write_obj(path: &Path, meshes: &[Mesh], atlas_path: &Path, sky_color: [f32; 3]) -> Result<()> {
+    let mtl_path = path.with_extension("mtl");
+    let [r, g, b] = sky_color;
+    let atlas = file_name(atlas_path);
+    let mtl = format!(
+        "newmtl atlas\nKd 1 1 1\nmap_Kd {atlas}\nmap_d {atlas}\n\nnewmtl sky\nKd {} {} {}\n",
+        r,
+        g,
+        b,
+        atlas = atlas
+    );
+    write_file(&mtl_path, mtl.as_bytes())?;
+
+    let mut obj = String::new();
+    writeln!(obj, "mtllib {}", file_name(&mtl_path)).expect("writing to a string cannot fail");
+    let mut num_vertices = 0;
+    for mesh in meshes {
+        if mesh.textured.is_empty() && mesh.sky.is_empty() {
+            continue;
+        }
+        (|| -> std::fmt::Result {
+            writeln!(obj, "o {}", mesh.name)?;
+            for (material, primitive) in [("atlas", &mesh.textured), ("sky", &mesh.sky)] {
+                if primitive.is_empty() {
+                    continue;
+                }
+                for [x, y, z] in &primitive.positions {
+                    writeln!(obj, "v {} {} {}", x, y, z)?;
+                }
+                // OBJ texture coordinates start at the bottom of the image.
+                for [u, v] in &primitive.uvs {
+                    writeln!(obj, "vt {} {}", u, 1.0 - v)?;
+                }
+                writeln!(obj, "usemtl {}", material)?;
+                for triangle in primitive.indices.chunks(3) {
+                    let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize + num_vertices + 1);
+                    writeln!(obj, "f {a}/{a} {b}/{b} {c}/{c}", a = a, b = b, c = c)?;
+                }
+                num_vertices += primitive.positions.len();
+            }
+            Ok(())
+        })()
+        .expect("writing to a string cannot fail");
+    }
+    write_file(path, obj.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clip, json_string, split_into_tiles, FaceVertex, Primitive};
+    use math::{Pnt2f, Pnt3f, Vec2f};
+
+    /// A vertical rectangle whose texture coordinates go from `uv_min` to `uv_max`.
+    fn rectangle(uv_min: (f32, f32), uv_max: (f32, f32)) -> Vec<FaceVertex> {
+        let (x0, x1) = (uv_min.0 / 64.0, uv_max.0 / 64.0);
+        let (y0, y1) = (uv_min.1 / 64.0, uv_max.1 / 64.0);
+        vec![
+            FaceVertex::new(Pnt2f::new(x0, 0.0), y0, uv_min.0, uv_min.1),
+            FaceVertex::new(Pnt2f::new(x1, 0.0), y0, uv_max.0, uv_min.1),
+            FaceVertex::new(Pnt2f::new(x1, 0.0), y1, uv_max.0, uv_max.1),
+            FaceVertex::new(Pnt2f::new(x0, 0.0), y1, uv_min.0, uv_max.1),
+        ]
+    }
+
+    fn uv_range(vertices: &[FaceVertex], axis: usize) -> (f32, f32) {
+        vertices
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+                (min.min(v.uv[axis]), max.max(v.uv[axis]))
+            })
+    }
+
+    #[test]
+    fn tiles_cover_single_repetitions() {
+        let size = Vec2f::new(64.0, 32.0);
+        let tiles = split_into_tiles(rectangle((-32.0, 0.0), (96.0, 32.0)), size);
+        assert_eq!(tiles.len(), 3);
+        let mut positions: Vec<(f32, f32)> = tiles
+            .iter()
+            .map(|tile| {
+                assert_eq!(tile.len(), 4);
+                assert_eq!(uv_range(tile, 1), (0.0, 32.0));
+                let (min, max) = uv_range(tile, 0);
+                assert!(min >= 0.0 && max <= 64.0);
+                let x = tile.iter().map(|v| v.pos[0]);
+                (
+                    x.clone().fold(f32::INFINITY, f32::min),
+                    x.fold(0.0, f32::max),
+                )
+            })
+            .collect();
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(positions, vec![(-0.5, 0.0), (0.0, 1.0), (1.0, 1.5)]);
+
+        let tiles = split_into_tiles(rectangle((0.0, 0.0), (64.0, 96.0)), size);
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(uv_range(&tiles[2], 1), (0.0, 32.0));
+
+        assert_eq!(
+            split_into_tiles(rectangle((0.0, 0.0), (8.0, 8.0)), size).len(),
+            1
+        );
+        assert!(split_into_tiles(Vec::new(), size).is_empty());
+    }
+
+    #[test]
+    fn clip_keeps_the_positive_side() {
+        let square = rectangle((0.0, 0.0), (64.0, 64.0));
+
+        let clipped = clip(&square, |v| v.uv[0] - 16.0);
+        assert_eq!(clipped.len(), 4);
+        assert_eq!(uv_range(&clipped, 0), (16.0, 64.0));
+        assert_eq!(uv_range(&clipped, 1), (0.0, 64.0));
+        assert!(clipped
+            .iter()
+            .all(|v| (v.pos[0] - v.uv[0] / 64.0).abs() < 1e-6));
+
+        // Cutting a corner off leaves a pentagon.
+        let clipped = clip(&square, |v| 96.0 - v.uv[0] - v.uv[1]);
+        assert_eq!(clipped.len(), 5);
+
+        assert_eq!(clip(&square, |_| 1.0).len(), 4);
+        assert!(clip(&square, |_| -1.0).is_empty());
+    }
+
+    #[test]
+    fn faces_are_triangle_fans() {
+        let vertex = |x: f32| (Pnt3f::new(x, 0.0, 0.0), [x, 0.0]);
+        let mut primitive = Primitive::default();
+        primitive.add_face((0..5).map(|x| vertex(x as f32)));
+        assert_eq!(primitive.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        primitive.add_face((0..3).map(|x| vertex(x as f32)));
+        assert_eq!(&primitive.indices[9..], &[5, 6, 7]);
+        assert_eq!(primitive.positions.len(), 8);
+        assert_eq!(primitive.uvs.len(), 8);
+
+        let mut empty = Primitive::default();
+        empty.add_face(std::iter::empty());
+        empty.add_face((0..2).map(|x| vertex(x as f32)));
+        assert!(empty.is_empty());
+        assert!(empty.positions.is_empty());
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("E1M1"), r#""E1M1""#);
+        assert_eq!(json_string("a \"b\"\\c\n\t"), r#""a \"b\"\\c\n\u0009""#);
+    }
+}
@@ -0,0 +1,68 @@
+//! Bitmap fonts built from the character patches in the WAD, drawn with the game palette.
+
+use super::wad_system::read_patch;
+use engine::{BitmapFont, BitmapGlyph};
+use log::{debug, warn};
+use wad::Archive;
+
+/// Font patches are named after the ASCII code of their character, for `!` to `~`. No WAD has all
+/// of them: Doom's stop at `y` and most fonts only have capitals.
+const FIRST_CHARACTER: u8 = b'!';
+const LAST_CHARACTER: u8 = b'~';
+
+/// Space widths in font pixels, like vanilla's heads-up text and Heretic's big font.
+const SMALL_SPACE_WIDTH: u32 = 4;
+const BIG_SPACE_WIDTH: u32 = 8;
+
+/// The heads-up font: `STCFN033` onwards in Doom, or `FONTA01` onwards in Heretic.
+pub fn small_font(archive: &Archive, scale: u32) -> Option<BitmapFont> {
+    load_font(archive, SMALL_SPACE_WIDTH, scale, |code| {
+        format!("STCFN{:03}", code)
+    })
+    .or_else(|| {
+        load_font(archive, SMALL_SPACE_WIDTH, scale, |code| {
+            format!("FONTA{:02}", code - b' ')
+        })
+    })
+}
+
+/// Heretic's big menu font, `FONTB01` onwards. Doom's menus are whole graphics, so it has none.
+pub fn big_font(archive: &Archive, scale: u32) -> Option<BitmapFont> {
+    load_font(archive, BIG_SPACE_WIDTH, scale, |code| {
+        format!("FONTB{:02}", code - b' ')
+    })
+}
+
+fn load_font(
+    archive: &Archive,
+    space_width: u32,
+    scale: u32,
+    lump_name: impl Fn(u8) -> String,
+) -> Option<BitmapFont> {
+    let mut font = BitmapFont::new(space_width, scale);
+    for code in FIRST_CHARACTER..=LAST_CHARACTER {
+        let name = lump_name(code);
+        let image = match read_patch(archive, &name) {
+            Ok(Some(image)) => image,
+            Ok(None) => continue,
+            Err(error) => {
+                warn!("Skipping bad font patch {}: {}", name, error);
+                continue;
+            }
+        };
+        font.add_glyph(
+            char::from(code),
+            BitmapGlyph {
+                width: image.width() as u32,
+                height: image.height() as u32,
+                pixels: image.pixels().to_vec(),
+            },
+        );
+    }
+    if font.is_empty() {
+        debug!("No font patches named like {}.", lump_name(FIRST_CHARACTER));
+        None
+    } else {
+        Some(font)
+    }
+}
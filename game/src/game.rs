@@ -1,25 +1,43 @@
+use super::automap::Automap;
+use super::bindings::BindingsFile;
+use super::camera_path::{CameraPaths, Config as CameraPathConfig};
 use super::errors::{ErrorKind, Result};
-use super::game_shaders::GameShaders;
-use super::hud::{Bindings as HudBindings, Hud};
+use super::game_shaders::{GameShaders, ScreenConfig};
+use super::hud::Hud;
 use super::level::Level;
-use super::player::{Bindings as PlayerBindings, Config as PlayerConfig, Player};
+use super::palette_effects::PaletteEffects;
+use super::player::{CameraPose, Config as PlayerConfig, Player};
+use super::software_shaders::{
+    DebugShader, SkyShader, SpriteShader, StaticShader, StatusBarShader,
+};
+use super::status_bar::StatusBar;
+use super::top_down::TopDownCamera;
 use super::wad_system::{Config as WadConfig, WadSystem};
 use super::SHADER_ROOT;
 use engine::type_list::Peek;
 use engine::{
-    Context, ContextBuilder, Entities, FrameTimers, Input, Materials, Meshes, Projections,
-    RenderPipeline, Renderer, ShaderConfig, Shaders, System, TextRenderer, Tick, TickConfig,
-    Transforms, Uniforms, Window, WindowConfig,
+    Capture, CaptureConfig, Context, ContextBuilder, Entities, FrameTimers, Fullscreen, Input,
+    InputConfig, LineShader, Materials, Meshes, Projections, RenderBackend, RenderPipeline,
+    Renderer, Screenshot, ShaderConfig, Shaders, SoftwareShaders, System, TextRenderer, Tick,
+    TickConfig, Transforms, Uniforms, Window, WindowConfig, LINE_SHADER,
 };
 use failchain::ResultExt;
 use std::marker::PhantomData;
 use std::path::PathBuf;
+use wad::types::{GameMode, Skill};
 
 pub trait Game {
     fn run(self) -> !;
     fn destroy(&mut self) -> Result<()>;
     fn num_levels(&self) -> usize;
     fn load_level(&mut self, level_index: usize) -> Result<()>;
+
+    /// Renders a single frame of the current level as seen from `pose` and reads it back.
+    fn screenshot(&mut self, pose: CameraPose) -> Result<Screenshot>;
+
+    /// Renders looking straight down at the player, showing `view_height` map units vertically,
+    /// or from the player's eyes again with `None`.
+    fn set_top_down(&mut self, view_height: Option<f32>);
 }
 
 #[derive(Clone)]
@@ -31,9 +49,34 @@ pub struct GameConfig {
     pub height: u32,
     pub version: &'static str,
     pub initial_level_index: usize,
+    pub skill: Skill,
+    pub mode: GameMode,
+    pub backend: RenderBackend,
+    pub fullscreen: Fullscreen,
+    pub vsync: bool,
+    pub msaa_samples: u16,
+    pub max_fps: Option<f32>,
+    /// Draws the scene at this size and stretches it over the window, e.g. 320x200 like vanilla.
+    pub internal_resolution: Option<(u32, u32)>,
+    /// Post-processes frames to look like a CRT monitor.
+    pub crt: bool,
+    /// Plays this camera path file, then quits.
+    pub camera_path: Option<PathBuf>,
+    /// Saves every frame of the `camera_path` as numbered PNG files in this directory.
+    pub camera_path_frames: Option<PathBuf>,
+    /// A TOML file overriding some of the default key and mouse bindings.
+    pub bindings_file: Option<PathBuf>,
+    /// Saves every tick's input to this file, to reproduce the session with `replay_input`.
+    pub record_input: Option<PathBuf>,
+    /// Replays the input saved in this file by `record_input` instead of live input.
+    pub replay_input: Option<PathBuf>,
 }
 
 pub fn create(config: &GameConfig) -> Result<impl Game> {
+    let bindings = match config.bindings_file {
+        Some(ref path) => BindingsFile::load(path)?,
+        None => BindingsFile::default(),
+    };
     let context = (|| {
         ContextBuilder::new()
             // Engine configs and systems.
@@ -44,9 +87,28 @@ pub fn create(config: &GameConfig) -> Result<impl Game> {
                 width: config.width,
                 height: config.height,
                 title: format!("Rusty Doom v{}", config.version),
+                backend: config.backend,
+                fullscreen: config.fullscreen,
+                vsync: config.vsync,
+                msaa_samples: config.msaa_samples,
+                max_fps: config.max_fps,
             })
             .inject(ShaderConfig {
                 root_path: SHADER_ROOT.into(),
+                software: SoftwareShaders::new()
+                    .with("static", StaticShader)
+                    .with("sprite", SpriteShader)
+                    .with("sky", SkyShader)
+                    .with("debug", DebugShader)
+                    .with("status_bar", StatusBarShader)
+                    .with(LINE_SHADER, LineShader),
+            })
+            .inject(CaptureConfig {
+                directory: "screenshots".into(),
+            })
+            .inject(InputConfig {
+                record: config.record_input.clone(),
+                replay: config.replay_input.clone(),
             })
             .system(Tick::bind())?
             .system(FrameTimers::bind())?
@@ -61,20 +123,39 @@ pub fn create(config: &GameConfig) -> Result<impl Game> {
             .system(Materials::bind())?
             .system(RenderPipeline::bind())?
             .system(TextRenderer::bind())?
+            .system(Capture::bind())?
             // Game configs and systems.
             .inject(WadConfig {
                 wad_path: config.wad_file.clone(),
                 metadata_path: config.metadata_file.clone(),
                 initial_level_index: config.initial_level_index,
+                skill: config.skill,
+                mode: config.mode,
+            })
+            .inject(ScreenConfig {
+                internal_resolution: config.internal_resolution,
+                crt: config.crt,
+            })
+            .inject(bindings.hud)
+            .inject(bindings.player)
+            .inject(bindings.automap)
+            .inject(bindings.top_down)
+            .inject(bindings.camera_path)
+            .inject(CameraPathConfig {
+                play: config.camera_path.clone(),
+                frames: config.camera_path_frames.clone(),
             })
-            .inject(HudBindings::default())
-            .inject(PlayerBindings::default())
             .inject(PlayerConfig::default())
             .system(WadSystem::bind())?
+            .system(PaletteEffects::bind())?
             .system(GameShaders::bind())?
             .system(Level::bind())?
             .system(Hud::bind())?
             .system(Player::bind())?
+            .system(TopDownCamera::bind())?
+            .system(CameraPaths::bind())?
+            .system(Automap::bind())?
+            .system(StatusBar::bind())?
             .system(Renderer::bind())?
             .build()
     })()
@@ -83,17 +164,34 @@ pub fn create(config: &GameConfig) -> Result<impl Game> {
     Ok(GameImpl::new(context))
 }
 
-struct GameImpl<WadIndexT, ContextT>
+struct GameImpl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT>
 where
-    ContextT: Context + Peek<WadSystem, WadIndexT>,
+    ContextT: Context
+        + Peek<WadSystem, WadIndexT>
+        + Peek<Player, PlayerIndexT>
+        + Peek<TopDownCamera, TopDownIndexT>
+        + Peek<Tick, TickIndexT>
+        + Peek<Window, WindowIndexT>,
 {
     context: Option<ContextT>,
-    phantom: PhantomData<WadIndexT>,
+    phantom: PhantomData<(
+        WadIndexT,
+        PlayerIndexT,
+        TopDownIndexT,
+        TickIndexT,
+        WindowIndexT,
+    )>,
 }
 
-impl<WadIndexT, ContextT> GameImpl<WadIndexT, ContextT>
+impl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT>
+    GameImpl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT>
 where
-    ContextT: Context + Peek<WadSystem, WadIndexT>,
+    ContextT: Context
+        + Peek<WadSystem, WadIndexT>
+        + Peek<Player, PlayerIndexT>
+        + Peek<TopDownCamera, TopDownIndexT>
+        + Peek<Tick, TickIndexT>
+        + Peek<Window, WindowIndexT>,
 {
     fn new(context: ContextT) -> Self {
         Self {
@@ -103,21 +201,28 @@ where
     }
 }
 
-impl<WadIndexT, ContextT> Game for GameImpl<WadIndexT, ContextT>
+impl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT> Game
+    for GameImpl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT>
 where
-    ContextT: Context + Peek<WadSystem, WadIndexT>,
+    ContextT: Context
+        + Peek<WadSystem, WadIndexT>
+        + Peek<Player, PlayerIndexT>
+        + Peek<TopDownCamera, TopDownIndexT>
+        + Peek<Tick, TickIndexT>
+        + Peek<Window, WindowIndexT>,
 {
     fn run(mut self) -> ! {
         self.context.take().unwrap().run()
     }
 
     fn num_levels(&self) -> usize {
-        self.context.as_ref().unwrap().peek().archive.num_levels()
+        let wad: &WadSystem = self.context.as_ref().unwrap().peek();
+        wad.archive.num_levels()
     }
 
     fn load_level(&mut self, level_index: usize) -> Result<()> {
         let context = self.context.as_mut().unwrap();
-        let wad = context.peek_mut();
+        let wad: &mut WadSystem = context.peek_mut();
         wad.change_level(level_index);
         context
             .step()
@@ -128,6 +233,30 @@ where
         Ok(())
     }
 
+    fn screenshot(&mut self, pose: CameraPose) -> Result<Screenshot> {
+        let context = self.context.as_mut().unwrap();
+        let player: &mut Player = context.peek_mut();
+        player.set_pose(Some(pose));
+        // Transforms are propagated before the player moves, so it takes a second step for the
+        // camera to actually be at `pose` when rendering.
+        for _ in 0..2 {
+            let tick: &mut Tick = context.peek_mut();
+            tick.resync();
+            context
+                .step()
+                .chain_err(|| ErrorKind("during screenshot step".to_owned()))?;
+        }
+        let window: &Window = context.peek();
+        window
+            .read_frame()
+            .chain_err(|| ErrorKind("during screenshot read back".to_owned()))
+    }
+
+    fn set_top_down(&mut self, view_height: Option<f32>) {
+        let top_down: &mut TopDownCamera = self.context.as_mut().unwrap().peek_mut();
+        top_down.set_view(view_height);
+    }
+
     fn destroy(&mut self) -> Result<()> {
         if let Some(context) = self.context.as_mut() {
             context
@@ -138,9 +267,15 @@ where
     }
 }
 
-impl<WadIndexT, ContextT> Drop for GameImpl<WadIndexT, ContextT>
+impl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT> Drop
+    for GameImpl<WadIndexT, PlayerIndexT, TopDownIndexT, TickIndexT, WindowIndexT, ContextT>
 where
-    ContextT: Context + Peek<WadSystem, WadIndexT>,
+    ContextT: Context
+        + Peek<WadSystem, WadIndexT>
+        + Peek<Player, PlayerIndexT>
+        + Peek<TopDownCamera, TopDownIndexT>
+        + Peek<Tick, TickIndexT>
+        + Peek<Window, WindowIndexT>,
 {
     fn drop(&mut self) {
         if let Some(mut context) = self.context.take() {
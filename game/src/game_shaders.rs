@@ -1,19 +1,34 @@
+use super::lights::MAX_LIGHTS;
+use super::palette_effects::PaletteEffects;
 use super::wad_system::WadSystem;
 use engine::{
     BufferTextureId, BufferTextureType, ClientFormat, DependenciesFrom, Entities, EntityId, Error,
-    FloatUniformId, MagnifySamplerFilter, MaterialId, Materials, MinifySamplerFilter,
-    RenderPipeline, Result, SamplerBehavior, SamplerWrapFunction, ShaderId, Shaders, System,
-    Texture2dId, Tick, Uniforms, Window,
+    ErrorKind, FloatUniformId, MagnifySamplerFilter, MaterialId, Materials, MinifySamplerFilter,
+    RenderBackend, RenderPass, RenderPipeline, Result, SamplerBehavior, SamplerWrapFunction,
+    ShaderId, Shaders, System, TargetSize, Texture2dId, TextureArray, Tick, Uniforms, Window,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use math::Vec2;
-use wad::tex::BoundsLookup;
+use wad::tex::{BoundsLookup, MappedPalette};
 use wad::types::{COLORMAP_SIZE, PALETTE_SIZE};
 use wad::util::{is_sky_flat, is_untextured};
-use wad::{OpaqueImage as WadOpaqueImage, TransparentImage as WadTransparentImage, WadName};
+use wad::{
+    OpaqueImage as WadOpaqueImage, TextureDirectory, TransparentImage as WadTransparentImage,
+    WadName,
+};
+
+/// How frames are drawn before they reach the window.
+#[derive(Debug, Default)]
+pub struct ScreenConfig {
+    /// Draws the scene at this size and stretches it over the window, e.g. 320x200 like vanilla.
+    pub internal_resolution: Option<(u32, u32)>,
+    /// Darkens the frame between rows of pixels and towards the edges, like a CRT monitor.
+    pub crt: bool,
+}
 
 pub struct AtlasMaterial {
     pub material: MaterialId,
+    /// Every page fits in a vertex's `u8`, as checked when loading the atlas.
     pub bounds: BoundsLookup,
 }
 
@@ -22,6 +37,73 @@ pub struct LevelMaterials {
     pub walls: AtlasMaterial,
     pub decor: AtlasMaterial,
     pub sky: MaterialId,
+    /// Replaces the flats and walls materials in the debug views which need it.
+    pub debug: MaterialId,
+}
+
+/// How light levels map to colormaps in the level materials; the HUD, automap and status bar
+/// always use the unlit colormap.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColormapMode {
+    /// Colormaps 0 to 31, by light level and distance.
+    Normal,
+    /// Colormap 0 regardless of light, like light amplification visors.
+    Fullbright,
+    /// The inverse grey colormap 32, like invulnerability.
+    Inverse,
+}
+
+impl ColormapMode {
+    /// The value of `u_colormap_mode` in the level shaders.
+    fn shader_mode(self) -> f32 {
+        match self {
+            ColormapMode::Normal => 0.0,
+            ColormapMode::Fullbright => 1.0,
+            ColormapMode::Inverse => 2.0,
+        }
+    }
+}
+
+/// Alternative ways of drawing the level, to track down bad geometry.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DebugView {
+    /// The level as it normally looks.
+    Normal,
+    /// Only the edges of triangles.
+    Wireframe,
+    /// Each BSP leaf in a distinct flat colour.
+    Leaves,
+    /// The light level of walls and flats, in greyscale.
+    Light,
+    /// Trigger lines in yellow and each dynamic object in a distinct colour.
+    Triggers,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Normal => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Leaves,
+            DebugView::Leaves => DebugView::Light,
+            DebugView::Light => DebugView::Triggers,
+            DebugView::Triggers => DebugView::Normal,
+        }
+    }
+
+    /// Whether walls and flats use the `debug` material.
+    pub fn uses_debug_material(self) -> bool {
+        self.shader_mode().is_some()
+    }
+
+    /// The value of `u_debug_view` in the `debug` shader.
+    fn shader_mode(self) -> Option<f32> {
+        match self {
+            DebugView::Normal | DebugView::Wireframe => None,
+            DebugView::Leaves => Some(1.0),
+            DebugView::Light => Some(2.0),
+            DebugView::Triggers => Some(3.0),
+        }
+    }
 }
 
 impl GameShaders {
@@ -29,6 +111,21 @@ impl GameShaders {
         self.globals.time
     }
 
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    /// Selects how the level is drawn, from the next update on.
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view;
+    }
+
+    /// The current palette, as a `u_palette` texture. Its first row is the unlit colormap, then
+    /// come the other light levels and finally the inverse colormap.
+    pub fn palette(&self) -> Texture2dId {
+        self.globals.palette
+    }
+
     pub fn lights_buffer_texture(&self) -> BufferTextureId<u8> {
         self.globals.lights_buffer_texture
     }
@@ -40,6 +137,7 @@ impl GameShaders {
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
+    screen: &'context ScreenConfig,
     tick: &'context Tick,
     window: &'context Window,
     entities: &'context mut Entities,
@@ -49,6 +147,7 @@ pub struct Dependencies<'context> {
     materials: &'context mut Materials,
 
     wad: &'context mut WadSystem,
+    palette_effects: &'context PaletteEffects,
 }
 
 impl<'context> System<'context> for GameShaders {
@@ -65,12 +164,15 @@ impl<'context> System<'context> for GameShaders {
 
         let globals = deps.load_globals(globals_id)?;
         let level = deps.load_level(&globals, level_id)?;
+        deps.set_render_passes(globals_id)?;
 
         Ok(GameShaders {
             globals_id,
             level_id,
             globals,
             level,
+            palette: 0,
+            debug_view: DebugView::Normal,
         })
     }
 
@@ -92,6 +194,23 @@ impl<'context> System<'context> for GameShaders {
                 .expect("missing time") += deps.tick.timestep();
         }
 
+        let palette = deps.palette_effects.palette_index();
+        if palette != self.palette {
+            deps.replace_palette(self.globals.palette, palette)?;
+            self.palette = palette;
+        }
+        *deps
+            .uniforms
+            .get_float_mut(self.globals.colormap_mode)
+            .expect("missing colormap mode") = deps.palette_effects.colormap_mode().shader_mode();
+
+        if let Some(mode) = self.debug_view.shader_mode() {
+            *deps
+                .uniforms
+                .get_float_mut(self.globals.debug_view)
+                .expect("missing debug view") = mode;
+        }
+
         Ok(())
     }
 
@@ -108,20 +227,30 @@ pub struct GameShaders {
 
     globals: Globals,
     level: LevelMaterials,
+
+    /// The `PLAYPAL` palette currently uploaded to the palette texture.
+    palette: usize,
+    debug_view: DebugView,
 }
 
 struct Globals {
     time: FloatUniformId,
     lights_buffer_texture: BufferTextureId<u8>,
     palette: Texture2dId,
+    colormap_mode: FloatUniformId,
     static_shader: ShaderId,
     sky_shader: ShaderId,
     sprite_shader: ShaderId,
+    debug_shader: ShaderId,
+    debug_view: FloatUniformId,
 }
 
 impl<'context> Dependencies<'context> {
     fn load_palette(&mut self, parent: EntityId) -> Result<Texture2dId> {
-        let palette = self.wad.textures.build_palette_texture(0, 0, 32);
+        if INVERSE_COLORMAP >= self.wad.textures.num_colormaps() {
+            warn!("WAD has no inverse colormap, invulnerability will not invert colours.");
+        }
+        let palette = build_palette(&self.wad.textures, 0);
         self.uniforms.add_texture_2d(
             self.window,
             self.entities,
@@ -143,32 +272,60 @@ impl<'context> Dependencies<'context> {
         )
     }
 
+    fn replace_palette(&mut self, texture: Texture2dId, palette: usize) -> Result<()> {
+        if palette >= self.wad.textures.num_palettes() {
+            error!("WAD has no palette {}, keeping current one.", palette);
+            return Ok(());
+        }
+        let palette = build_palette(&self.wad.textures, palette);
+        self.uniforms
+            .get_texture_2d_mut(texture)
+            .expect("missing palette texture")
+            .replace_pixels(
+                self.window,
+                &palette.pixels,
+                Vec2::new(COLORMAP_SIZE, palette.pixels.len() / PALETTE_SIZE),
+                ClientFormat::U8U8U8,
+                None,
+            )
+    }
+
     fn load_globals(&mut self, parent: EntityId) -> Result<Globals> {
         let palette = self.load_palette(parent)?;
 
         let time = self
             .uniforms
             .add_float(self.entities, parent, "time_uniform", 0.0)?;
+        let colormap_mode =
+            self.uniforms
+                .add_float(self.entities, parent, "colormap_mode_uniform", 0.0)?;
         let lights_buffer_texture = self.uniforms.add_persistent_buffer_texture_u8(
             self.window,
             self.entities,
             parent,
             "lights_buffer_texture",
-            256,
+            MAX_LIGHTS,
             BufferTextureType::Float,
         )?;
 
         let static_shader = self.load_shader(parent, "static_shader", "static")?;
         let sky_shader = self.load_shader(parent, "sky_shader", "sky")?;
         let sprite_shader = self.load_shader(parent, "sprite_shader", "sprite")?;
+        let debug_shader = self.load_shader(parent, "debug_shader", "debug")?;
+        let debug_view =
+            self.uniforms
+                .add_float(self.entities, parent, "debug_view_uniform", 0.0)?;
 
         Ok(Globals {
             time,
             lights_buffer_texture,
             palette,
+            colormap_mode,
             static_shader,
             sky_shader,
             sprite_shader,
+            debug_shader,
+            debug_view,
         })
     }
 
@@ -190,6 +347,7 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_time", globals.time)
             .add_uniform("u_lights", globals.lights_buffer_texture)
             .add_uniform("u_palette", globals.palette)
+            .add_uniform("u_colormap_mode", globals.colormap_mode)
             .add_uniform("u_atlas", flats_atlas.texture)
             .add_uniform(
                 "u_atlas_size",
@@ -215,6 +373,7 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_time", globals.time)
             .add_uniform("u_lights", globals.lights_buffer_texture)
             .add_uniform("u_palette", globals.palette)
+            .add_uniform("u_colormap_mode", globals.colormap_mode)
             .add_uniform("u_atlas", walls_atlas.texture)
             .add_uniform(
                 "u_atlas_size",
@@ -233,6 +392,7 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_modelview", modelview)
             .add_uniform("u_projection", projection)
             .add_uniform("u_palette", globals.palette)
+            .add_uniform("u_colormap_mode", globals.colormap_mode)
             .add_uniform("u_texture", sky_uniforms.texture)
             .add_uniform("u_tiled_band_size", sky_uniforms.tiled_band_size)
             .id();
@@ -251,6 +411,7 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_time", globals.time)
             .add_uniform("u_lights", globals.lights_buffer_texture)
             .add_uniform("u_palette", globals.palette)
+            .add_uniform("u_colormap_mode", globals.colormap_mode)
             .add_uniform("u_atlas", decor_atlas.texture)
             .add_uniform(
                 "u_atlas_size",
@@ -262,6 +423,20 @@ impl<'context> Dependencies<'context> {
             )
             .id();
 
+        let debug_material = self
+            .materials
+            .add(
+                self.entities,
+                parent,
+                globals.debug_shader,
+                "debug_material",
+            )?
+            .add_uniform("u_modelview", modelview)
+            .add_uniform("u_projection", projection)
+            .add_uniform("u_lights", globals.lights_buffer_texture)
+            .add_uniform("u_debug_view", globals.debug_view)
+            .id();
+
         Ok(LevelMaterials {
             flats: AtlasMaterial {
                 material: flats_material,
@@ -276,6 +451,7 @@ impl<'context> Dependencies<'context> {
                 bounds: decor_atlas.bounds,
             },
             sky: sky_material,
+            debug: debug_material,
         })
     }
 
@@ -336,14 +512,22 @@ impl<'context> Dependencies<'context> {
                 .level
                 .things
                 .iter()
+                .filter(|thing| wad.spawns(thing))
                 .filter_map(|thing| wad.archive.metadata().find_thing(thing.thing_type))
                 .flat_map(|decor| {
+                    let frame = decor.sequence.as_bytes()[0];
                     let mut sprite0 = decor.sprite;
-                    let _ = sprite0.push(decor.sequence.as_bytes()[0]);
+                    let _ = sprite0.push(frame);
                     let mut sprite1 = sprite0;
                     let sprite0 = sprite0.push(b'0').ok().map(|_| sprite0);
                     let sprite1 = sprite1.push(b'1').ok().map(|_| sprite1);
-                    sprite0.into_iter().chain(sprite1)
+                    let rotations = wad
+                        .textures
+                        .sprite_rotations(decor.sprite, frame)
+                        .into_iter()
+                        .flatten()
+                        .map(|rotation| rotation.tex_name);
+                    sprite0.into_iter().chain(sprite1).chain(rotations)
                 });
             wad.textures.build_texture_atlas(names)
         };
@@ -409,6 +593,7 @@ impl<'context> Dependencies<'context> {
                     ImageRef::Transparent {
                         pixels: image.pixels(),
                         size: image.size(),
+                        pages: None,
                     }
                 } else {
                     error!("Missing texture {:?} for {:?}.", texture_name, name);
@@ -416,20 +601,42 @@ impl<'context> Dependencies<'context> {
                     ImageRef::Transparent {
                         pixels: &dummy_texture,
                         size: Vec2::new(1, 1),
+                        pages: None,
                     }
                 }
             }
             TextureSpec::TransparentAtlas(image) => ImageRef::Transparent {
                 pixels: &image.pixels,
                 size: image.size,
+                pages: Some(image.pages),
             },
             TextureSpec::OpaqueAtlas(image) => ImageRef::Opaque {
                 pixels: &image.pixels,
                 size: image.size,
+                pages: image.pages,
             },
         };
+        if let ImageRef::Transparent {
+            pages: Some(pages), ..
+        }
+        | ImageRef::Opaque { pages, .. } = &image_ref
+        {
+            if *pages > MAX_ATLAS_PAGES {
+                return Err(ErrorKind::UnsupportedFeature {
+                    needed_by: format!(
+                        "{:?} with {} pages, more than the {} vertices can address",
+                        name, pages, MAX_ATLAS_PAGES
+                    ),
+                }
+                .into());
+            }
+        }
         Ok(match image_ref {
-            ImageRef::Transparent { pixels, size } => self.uniforms.add_texture_2d(
+            ImageRef::Transparent {
+                pixels,
+                size,
+                pages: None,
+            } => self.uniforms.add_texture_2d(
                 self.window,
                 self.entities,
                 parent,
@@ -439,19 +646,71 @@ impl<'context> Dependencies<'context> {
                 ClientFormat::U8U8,
                 sampler,
             )?,
-            ImageRef::Opaque { pixels, size } => self.uniforms.add_texture_2d(
+            ImageRef::Transparent {
+                pixels,
+                size,
+                pages: Some(pages),
+            } => self.uniforms.add_texture_2d_array(
                 self.window,
                 self.entities,
                 parent,
                 name,
+                TextureArray {
+                    pixels,
+                    size,
+                    layers: pages,
+                    format: ClientFormat::U8U8,
+                },
+                sampler,
+            )?,
+            ImageRef::Opaque {
                 pixels,
                 size,
-                ClientFormat::U8,
+                pages,
+            } => self.uniforms.add_texture_2d_array(
+                self.window,
+                self.entities,
+                parent,
+                name,
+                TextureArray {
+                    pixels,
+                    size,
+                    layers: pages,
+                    format: ClientFormat::U8,
+                },
                 sampler,
             )?,
         })
     }
 
+    /// Sets up the render passes needed by the `ScreenConfig`, if any.
+    fn set_render_passes(&mut self, parent: EntityId) -> Result<()> {
+        let ScreenConfig {
+            internal_resolution,
+            crt,
+        } = *self.screen;
+        if internal_resolution.is_none() && !crt {
+            return Ok(());
+        }
+        let size = internal_resolution.map_or(TargetSize::Window, |(width, height)| {
+            TargetSize::Fixed(width, height)
+        });
+        let mut passes = vec![RenderPass::Scene { size }];
+        if crt && self.window.backend() == RenderBackend::Software {
+            warn!("The CRT filter needs the GPU renderer, ignoring it.");
+        } else if crt {
+            passes.push(RenderPass::PostProcess {
+                shader: self.load_shader(parent, "crt_shader", "crt")?,
+                size: TargetSize::Window,
+                filter: MagnifySamplerFilter::Nearest,
+            });
+        }
+        info!("Rendering at {:?} with {} pass(es).", size, passes.len());
+        self.render
+            .set_passes(passes, MagnifySamplerFilter::Nearest);
+        Ok(())
+    }
+
     fn load_shader(
         &mut self,
         parent: EntityId,
@@ -463,6 +722,24 @@ impl<'context> Dependencies<'context> {
     }
 }
 
+/// Builds the palette texture: one row per light level then one for the inverse colormap, each
+/// mapping palette indices to colours. WADs without an inverse colormap repeat the unlit one.
+fn build_palette(textures: &TextureDirectory, palette: usize) -> MappedPalette {
+    let inverse = if INVERSE_COLORMAP < textures.num_colormaps() {
+        INVERSE_COLORMAP
+    } else {
+        0
+    };
+    let colormaps: Vec<usize> = (0..NUM_LIGHT_COLORMAPS).chain(Some(inverse)).collect();
+    textures.build_mapped_palette(palette, &colormaps)
+}
+
+/// Vertices store their atlas page in a `u8`.
+const MAX_ATLAS_PAGES: usize = 256;
+
+const NUM_LIGHT_COLORMAPS: usize = 32;
+const INVERSE_COLORMAP: usize = 32;
+
 struct SkyUniforms {
     tiled_band_size: FloatUniformId,
     texture: Texture2dId,
@@ -480,13 +757,16 @@ enum TextureSpec<'a> {
     TextureName(WadName),
 }
 
+/// Atlases have `pages`, and are loaded as texture arrays.
 enum ImageRef<'a> {
     Transparent {
         pixels: &'a [u16],
         size: Vec2<usize>,
+        pages: Option<usize>,
     },
     Opaque {
         pixels: &'a [u8],
         size: Vec2<usize>,
+        pages: usize,
     },
 }
@@ -1,17 +1,28 @@
+use super::fonts;
+use super::game_shaders::GameShaders;
+use super::palette_effects::PaletteEffects;
 use super::wad_system::WadSystem;
 use engine::{
-    ControlFlow, DependenciesFrom, Gesture, InfallibleSystem, Input, Scancode, TextId,
-    TextRenderer, Window,
+    Capture, ControlFlow, DependenciesFrom, FontId, Gesture, InfallibleSystem, Input, Scancode,
+    TextId, TextRenderer, Tick, TickIndex, Window,
 };
 use math::prelude::*;
 use math::Pnt2f;
+use serde::Deserialize;
 
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Bindings {
     pub quit: Gesture,
     pub next_level: Gesture,
     pub previous_level: Gesture,
     pub toggle_mouse: Gesture,
     pub toggle_help: Gesture,
+    pub screenshot: Gesture,
+    pub toggle_recording: Gesture,
+    pub cycle_colormap: Gesture,
+    pub cycle_debug_view: Gesture,
+    pub toggle_fullscreen: Gesture,
 }
 
 impl Default for Bindings {
@@ -31,6 +42,23 @@ impl Default for Bindings {
             ]),
             toggle_mouse: Gesture::KeyTrigger(Scancode::Grave),
             toggle_help: Gesture::KeyTrigger(Scancode::H),
+            screenshot: Gesture::KeyTrigger(Scancode::F12),
+            toggle_recording: Gesture::KeyTrigger(Scancode::F9),
+            cycle_colormap: Gesture::AllOf(vec![
+                Gesture::KeyHold(Scancode::LControl),
+                Gesture::KeyTrigger(Scancode::L),
+            ]),
+            cycle_debug_view: Gesture::AllOf(vec![
+                Gesture::KeyHold(Scancode::LControl),
+                Gesture::KeyTrigger(Scancode::V),
+            ]),
+            toggle_fullscreen: Gesture::AllOf(vec![
+                Gesture::AnyOf(vec![
+                    Gesture::KeyHold(Scancode::LAlt),
+                    Gesture::KeyHold(Scancode::RAlt),
+                ]),
+                Gesture::KeyTrigger(Scancode::Return),
+            ]),
         }
     }
 }
@@ -38,12 +66,16 @@ impl Default for Bindings {
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
     bindings: &'context Bindings,
-    window: &'context Window,
+    tick: &'context Tick,
+    window: &'context mut Window,
     input: &'context mut Input,
     text: &'context mut TextRenderer,
     control_flow: &'context mut ControlFlow,
+    capture: &'context mut Capture,
 
     wad: &'context mut WadSystem,
+    game_shaders: &'context mut GameShaders,
+    palette_effects: &'context mut PaletteEffects,
 }
 
 pub struct Hud {
@@ -51,6 +83,14 @@ pub struct Hud {
     current_help: HelpState,
     prompt_text: TextId,
     help_text: TextId,
+
+    /// The WAD's big font, or the small one if it has none.
+    big_font: FontId,
+    /// The current level's name, shown for a few seconds after it loads.
+    title_text: TextId,
+    title_shown_at: TickIndex,
+    /// The game palette the bitmap fonts are drawn with.
+    palette_index: usize,
 }
 
 impl<'context> InfallibleSystem<'context> for Hud {
@@ -64,27 +104,69 @@ impl<'context> InfallibleSystem<'context> for Hud {
         deps.input.set_mouse_enabled(true);
         deps.input.set_cursor_grabbed(true);
 
-        let prompt_text = deps
-            .text
-            .insert(deps.window, PROMPT_TEXT, Pnt2f::origin(), HELP_PADDING);
+        // Scale the WAD fonts up like the rest of the 320x200 game would be.
+        let scale = (deps.window.height() / 200).max(1);
+        let small_font = match fonts::small_font(&deps.wad.archive, scale) {
+            Some(font) => deps.text.add_bitmap_font(font),
+            None => deps.text.default_font(),
+        };
+        let big_font = match fonts::big_font(&deps.wad.archive, scale) {
+            Some(font) => deps.text.add_bitmap_font(font),
+            None => small_font,
+        };
+
+        let prompt_text = deps.text.insert_with_font(
+            deps.window,
+            small_font,
+            PROMPT_TEXT,
+            Pnt2f::origin(),
+            HELP_PADDING,
+        );
         let help_text = deps
             .text
             .insert(deps.window, HELP_TEXT, Pnt2f::origin(), HELP_PADDING);
         deps.text[help_text].set_visible(false);
+        let title_text = insert_title(deps.text, deps.window, deps.wad, big_font);
+        let palette_index = deps.palette_effects.palette_index();
+        deps.text
+            .set_palette(deps.window, &deps.wad.textures.palette(palette_index).0);
 
         Hud {
             prompt_text,
             help_text,
             mouse_grabbed: true,
             current_help: HelpState::Prompt,
+            big_font,
+            title_text,
+            title_shown_at: deps.tick.index(),
+            palette_index,
         }
     }
 
     fn update(&mut self, deps: Dependencies) {
+        if deps.wad.level_changed() {
+            deps.text.remove(self.title_text);
+            self.title_text = insert_title(deps.text, deps.window, deps.wad, self.big_font);
+            self.title_shown_at = deps.tick.index();
+        } else if deps.tick.seconds_since_tick(self.title_shown_at) > TITLE_SECONDS {
+            deps.text[self.title_text].set_visible(false);
+        }
+
+        let palette_index = deps.palette_effects.palette_index();
+        if palette_index != self.palette_index {
+            self.palette_index = palette_index;
+            deps.text
+                .set_palette(deps.window, &deps.wad.textures.palette(palette_index).0);
+        }
+
         let Dependencies {
+            window,
             input,
             text,
             control_flow,
+            capture,
+            palette_effects,
+            game_shaders,
             bindings,
             ..
         } = deps;
@@ -99,6 +181,26 @@ impl<'context> InfallibleSystem<'context> for Hud {
             input.set_cursor_grabbed(self.mouse_grabbed);
         }
 
+        if input.poll_gesture(&bindings.toggle_fullscreen) {
+            window.toggle_fullscreen();
+        }
+
+        if input.poll_gesture(&bindings.screenshot) {
+            capture.request_screenshot();
+        }
+
+        if input.poll_gesture(&bindings.toggle_recording) {
+            capture.toggle_recording();
+        }
+
+        if input.poll_gesture(&bindings.cycle_colormap) {
+            palette_effects.cycle_debug_colormap_mode();
+        }
+
+        if input.poll_gesture(&bindings.cycle_debug_view) {
+            game_shaders.set_debug_view(game_shaders.debug_view().next());
+        }
+
         if input.poll_gesture(&bindings.toggle_help) {
             self.current_help = match self.current_help {
                 HelpState::Prompt => {
@@ -129,11 +231,23 @@ impl<'context> InfallibleSystem<'context> for Hud {
     }
 
     fn teardown(&mut self, deps: Dependencies) {
+        deps.text.remove(self.title_text);
         deps.text.remove(self.help_text);
         deps.text.remove(self.prompt_text);
     }
 }
 
+fn insert_title(text: &mut TextRenderer, window: &Window, wad: &WadSystem, font: FontId) -> TextId {
+    let name = wad.level_name();
+    text.insert_with_font(
+        window,
+        font,
+        name.as_ref().trim_end_matches('\0'),
+        Pnt2f::new(0.0, TITLE_TOP),
+        HELP_PADDING,
+    )
+}
+
 enum HelpState {
     Prompt,
     Shown,
@@ -141,6 +255,8 @@ enum HelpState {
 }
 
 const HELP_PADDING: u32 = 6;
+const TITLE_TOP: f32 = 0.1;
+const TITLE_SECONDS: f32 = 3.0;
 const PROMPT_TEXT: &str = "WASD and mouse, 'E' to push/use, LB to shoot or 'h' for help.";
 const HELP_TEXT: &str = r"Use WASD to move and the mouse or arrow keys to aim.
 Other keys:
@@ -153,4 +269,13 @@ Other keys:
     c - to toggle clipping (wall collisions)
     Ctrl-N - to change to next level (though using the exit will also do this!)
     Ctrl-P - to change to previous level
+    Ctrl-L - to cycle forced colormaps (fullbright, inverse, normal, off)
+    Ctrl-V - to cycle debug views (wireframe, BSP leaves, light, triggers, off)
+    Alt-Enter - toggle fullscreen
+    Tab - toggle the automap (+/- to zoom, IJKL to pan, 0 to follow the player)
+    Ctrl-M - reveal the whole automap
+    Ctrl-T - toggle the top-down view (PgUp/PgDn to zoom, numpad to pan, 5 to follow)
+    F12 - save a screenshot (in 'screenshots')
+    F9 - start/stop recording every frame (in 'screenshots')
+    F10 - start/stop recording a camera path (in 'screenshots')
     h - toggle this help message";
@@ -1,26 +1,39 @@
-use super::game_shaders::{GameShaders, LevelMaterials};
-use super::lights::Lights;
+use super::game_shaders::{DebugView, GameShaders, LevelMaterials};
+use super::lights::{LightIndex, Lights};
+use super::pickups::Pickup;
 use super::vertex::{SkyVertex, SpriteVertex, StaticVertex};
 use super::wad_system::WadSystem;
 use super::world::{World, WorldBuilder};
 use engine::{
-    DependenciesFrom, Entities, EntityId, Error, Meshes, RenderPipeline, Result, System, Tick,
-    Transforms, Uniforms, Window,
+    CullingTree, DependenciesFrom, Entities, EntityId, Error, MaterialId, MeshId, Meshes,
+    RenderPipeline, Result, System, Tick, Transforms, Uniforms, Window,
 };
 use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::{vec2, Line2f, Pnt2f, Pnt3f, Rad, Trans3, Vec3f};
+use math::{vec2, Aabb3, Line2f, Pnt2f, Pnt3f, Rad, Trans3, Vec3f};
+use std::collections::HashSet;
+use std::mem;
+use std::ops::Range;
 use std::time::Instant;
 use vec_map::VecMap;
 use wad::tex::Bounds as WadBounds;
+use wad::util::from_wad_height;
+use wad::Level as WadLevel;
 use wad::{
-    Decor, LevelVisitor, LightInfo, Marker, MoveEffect, ObjectId, SkyPoly, SkyQuad, StaticPoly,
-    StaticQuad, Trigger, TriggerType,
+    Branch, Decor, DecorRotation, LevelVisitor, LightInfo, Marker, MoveEffect, ObjectId, SkyPoly,
+    SkyQuad, StaticPoly, StaticQuad, Trigger, TriggerType,
 };
 
 pub struct Level {
     root: EntityId,
     objects: Vec<EntityId>,
+    /// The flats and walls models, with their normal materials, swapped out by debug views.
+    static_models: Vec<(EntityId, MaterialId)>,
+    /// Strips along the trigger lines which have no wall, shown only by the triggers debug view.
+    trigger_strips: TriggerStrips,
+    decor: DecorModels,
+    pickups: Vec<PickupItem>,
+    debug_view: DebugView,
     triggers: Vec<Trigger>,
     removed: Vec<usize>,
     effects: VecMap<MoveEffect>,
@@ -74,6 +87,53 @@ impl Level {
         &self.volume
     }
 
+    /// The pickups not taken yet which a player standing at `feet` would touch, with their index
+    /// for `remove_pickup`. Like vanilla, the bounding boxes must overlap and the item must be at
+    /// most 8 units below the feet or the player's height above them.
+    pub fn pickups_touching(
+        &self,
+        feet: Pnt3f,
+        radius: f32,
+        transforms: &Transforms,
+    ) -> Vec<(usize, Pickup)> {
+        self.pickups
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                if item.taken {
+                    return false;
+                }
+                let position = transforms
+                    .get_absolute(self.objects[item.object])
+                    .map_or(item.position, |transform| {
+                        transform.transform_point(item.position)
+                    });
+                let delta = position - feet;
+                let reach = radius + item.radius;
+                delta.x.abs() < reach
+                    && delta.z.abs() < reach
+                    && (-PICKUP_REACH_BELOW..=PLAYER_HEIGHT).contains(&delta.y)
+            })
+            .map(|(i_pickup, item)| (i_pickup, item.pickup))
+            .collect()
+    }
+
+    /// Hides a pickup the player took. Its object's decor model is rebuilt on the next update.
+    pub fn remove_pickup(&mut self, i_pickup: usize) {
+        let item = &mut self.pickups[i_pickup];
+        item.taken = true;
+        if let Some(model) = self.decor.models.get_mut(item.object) {
+            // Collapse the sprite's quads into a point, so they cover no pixels.
+            let indices = &mut model.indices[item.indices.clone()];
+            if let Some(&first) = indices.first() {
+                for index in indices {
+                    *index = first;
+                }
+            }
+            model.dirty = true;
+        }
+    }
+
     pub fn poll_triggers(
         &mut self,
         transform: &Trans3,
@@ -190,6 +250,7 @@ impl<'context> System<'context> for Level {
             info!("Level changed. {}", deps.entities.debug_tree_dump(4));
             self.level_changed = false;
         }
+        self.decor.rebuild_dirty(&mut deps, &self.objects)?;
 
         if self.exit_triggered {
             self.exit_triggered = false;
@@ -263,6 +324,30 @@ impl<'context> System<'context> for Level {
             .map_buffer_texture_u8(deps.game_shaders.lights_buffer_texture(), |buffer| {
                 light_infos.fill_buffer_at(time, buffer)
             });
+
+        let debug_view = deps.game_shaders.debug_view();
+        if debug_view != self.debug_view {
+            info!("Switching to {:?} debug view.", debug_view);
+            let debug_material = deps.game_shaders.level_materials().debug;
+            for &(entity, material) in &self.static_models {
+                deps.render.set_material(
+                    entity,
+                    if debug_view.uses_debug_material() {
+                        debug_material
+                    } else {
+                        material
+                    },
+                );
+            }
+            deps.render
+                .set_wireframe(debug_view == DebugView::Wireframe);
+            self.trigger_strips.show(
+                &mut deps,
+                self.objects[0],
+                debug_view == DebugView::Triggers,
+            )?;
+            self.debug_view = debug_view;
+        }
         Ok(())
     }
 
@@ -272,6 +357,97 @@ impl<'context> System<'context> for Level {
     }
 }
 
+struct TriggerStrips {
+    mesh: MeshId,
+    indices: Vec<u32>,
+    entity: Option<EntityId>,
+}
+
+impl TriggerStrips {
+    /// Adds or removes the strips' model, which always uses the debug material.
+    fn show(&mut self, deps: &mut Dependencies, parent: EntityId, visible: bool) -> Result<()> {
+        if let Some(entity) = self.entity.take() {
+            deps.entities.remove(entity);
+        }
+        if !visible || self.indices.is_empty() {
+            return Ok(());
+        }
+        let entity = deps.entities.add(parent, "trigger_strips")?;
+        let mesh = deps
+            .meshes
+            .add(deps.window, deps.entities, entity, "trigger_strips_mesh")
+            .shared(self.mesh)
+            .immutable_indices(&self.indices)?
+            .build()?;
+        deps.transforms.attach_identity(entity);
+        deps.render
+            .attach_model(entity, mesh, deps.game_shaders.level_materials().debug);
+        self.entity = Some(entity);
+        Ok(())
+    }
+}
+
+/// The decor model of each object, by object index.
+struct DecorModels {
+    mesh: MeshId,
+    models: VecMap<DecorModel>,
+}
+
+struct DecorModel {
+    entity: EntityId,
+    indices: Vec<u32>,
+    /// Whether `indices` changed since the model was added.
+    dirty: bool,
+}
+
+impl DecorModels {
+    /// Adds a model drawing the sprites at `indices` in `mesh` as a child of `parent`.
+    fn add_model(
+        deps: &mut Dependencies,
+        parent: EntityId,
+        mesh: MeshId,
+        indices: &[u32],
+    ) -> Result<EntityId> {
+        let entity = deps.entities.add(parent, "decor")?;
+        let mesh = deps
+            .meshes
+            .add(deps.window, deps.entities, entity, "object_decor_mesh")
+            .shared(mesh)
+            .immutable_indices(indices)?
+            .build()?;
+        deps.transforms.attach_identity(entity);
+        deps.render.attach_model(
+            entity,
+            mesh,
+            deps.game_shaders.level_materials().decor.material,
+        );
+        Ok(entity)
+    }
+
+    /// Replaces the models whose indices changed, since their index buffers are immutable.
+    fn rebuild_dirty(&mut self, deps: &mut Dependencies, objects: &[EntityId]) -> Result<()> {
+        for (i_object, model) in &mut self.models {
+            if model.dirty {
+                deps.entities.remove(model.entity);
+                model.entity = Self::add_model(deps, objects[i_object], self.mesh, &model.indices)?;
+                model.dirty = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An item the player can pick up, drawn by `indices` in its object's decor model.
+struct PickupItem {
+    pickup: Pickup,
+    object: usize,
+    /// The bottom middle of the sprite, before its object moves.
+    position: Pnt3f,
+    radius: f32,
+    indices: Range<usize>,
+    taken: bool,
+}
+
 struct Indices {
     wall: Vec<u32>,
     flat: Vec<u32>,
@@ -304,12 +480,30 @@ impl Indices {
     }
 }
 
+/// Per-vertex attributes for the debug views, shared by all the vertices of a wall or flat.
+#[derive(Copy, Clone, Default)]
+struct DebugInfo {
+    leaf: u16,
+    object: u16,
+    trigger: bool,
+}
+
 struct Builder<'a> {
     materials: &'a LevelMaterials,
 
+    trigger_linedefs: HashSet<usize>,
+    /// Trigger linedefs which got a wall, and so need no strip.
+    walled_trigger_linedefs: HashSet<usize>,
+    trigger_strip_indices: Vec<u32>,
+    num_leaves: usize,
+    /// The number of static wall and flat indices when the current BSP leaf began.
+    leaf_start: (usize, usize),
+    debug: DebugInfo,
+
     lights: Lights,
     start_pos: Pnt3f,
     start_yaw: Rad<f32>,
+    pickups: Vec<PickupItem>,
 
     static_vertices: Vec<StaticVertex>,
     sky_vertices: Vec<SkyVertex>,
@@ -317,6 +511,10 @@ struct Builder<'a> {
 
     object_indices: VecMap<Indices>,
 
+    // The static object's walls & flats are split by BSP node, for frustum culling.
+    wall_culling: CullingTree,
+    flat_culling: CullingTree,
+
     num_wall_quads: usize,
     num_floor_polys: usize,
     num_ceil_polys: usize,
@@ -355,9 +553,23 @@ impl<'a> Builder<'a> {
         let mut builder = Builder {
             materials: deps.game_shaders.level_materials(),
 
+            trigger_linedefs: deps
+                .wad
+                .analysis
+                .triggers()
+                .iter()
+                .map(|trigger| trigger.linedef)
+                .collect(),
+            walled_trigger_linedefs: HashSet::new(),
+            trigger_strip_indices: Vec::new(),
+            num_leaves: 0,
+            leaf_start: (0, 0),
+            debug: DebugInfo::default(),
+
             lights: Lights::new(),
             start_pos: Pnt3f::origin(),
             start_yaw: Rad(0.0f32),
+            pickups: Vec::new(),
 
             static_vertices: Vec::with_capacity(16_384),
             sky_vertices: Vec::with_capacity(16_384),
@@ -365,6 +577,9 @@ impl<'a> Builder<'a> {
 
             object_indices: VecMap::new(),
 
+            wall_culling: CullingTree::new(),
+            flat_culling: CullingTree::new(),
+
             num_wall_quads: 0,
             num_floor_polys: 0,
             num_ceil_polys: 0,
@@ -380,6 +595,11 @@ impl<'a> Builder<'a> {
             deps.wad.walk(&mut builder.chain(&mut world_builder));
             world_builder.build()
         };
+        for trigger in deps.wad.analysis.triggers() {
+            if !builder.walled_trigger_linedefs.contains(&trigger.linedef) {
+                builder.trigger_strip(&deps.wad.level, trigger);
+            }
+        }
 
         info!(
             "Level built in {:.2}ms:\n\
@@ -422,6 +642,7 @@ impl<'a> Builder<'a> {
         );
 
         info!("Creating static meshes and models...");
+        let mut static_models = Vec::new();
         let global_static_mesh = deps
             .meshes
             .add(deps.window, deps.entities, root, "global_world_static_mesh")
@@ -440,7 +661,8 @@ impl<'a> Builder<'a> {
             .immutable(&builder.decor_vertices)?
             .build_unindexed()?;
 
-        for (id, indices) in &builder.object_indices {
+        let mut decor_models = VecMap::new();
+        for (id, indices) in &mut builder.object_indices {
             let object = objects[id];
             if !indices.flat.is_empty() {
                 let entity = deps.entities.add(object, "flats")?;
@@ -453,6 +675,11 @@ impl<'a> Builder<'a> {
                 deps.transforms.attach_identity(entity);
                 deps.render
                     .attach_model(entity, mesh, builder.materials.flats.material);
+                static_models.push((entity, builder.materials.flats.material));
+                if id == 0 {
+                    deps.render
+                        .attach_culling(entity, mem::take(&mut builder.flat_culling));
+                }
             }
 
             if !indices.wall.is_empty() {
@@ -466,19 +693,24 @@ impl<'a> Builder<'a> {
                 deps.transforms.attach_identity(entity);
                 deps.render
                     .attach_model(entity, mesh, builder.materials.walls.material);
+                static_models.push((entity, builder.materials.walls.material));
+                if id == 0 {
+                    deps.render
+                        .attach_culling(entity, mem::take(&mut builder.wall_culling));
+                }
             }
 
             if !indices.decor.is_empty() {
-                let entity = deps.entities.add(object, "decor")?;
-                let mesh = deps
-                    .meshes
-                    .add(deps.window, deps.entities, entity, "object_decor_mesh")
-                    .shared(global_decor_mesh)
-                    .immutable_indices(&indices.decor)?
-                    .build()?;
-                deps.transforms.attach_identity(entity);
-                deps.render
-                    .attach_model(entity, mesh, builder.materials.decor.material);
+                let entity =
+                    DecorModels::add_model(deps, object, global_decor_mesh, &indices.decor)?;
+                decor_models.insert(
+                    id,
+                    DecorModel {
+                        entity,
+                        indices: mem::take(&mut indices.decor),
+                        dirty: false,
+                    },
+                );
             }
 
             if !indices.sky.is_empty() {
@@ -499,6 +731,18 @@ impl<'a> Builder<'a> {
             root,
             volume,
             objects,
+            static_models,
+            trigger_strips: TriggerStrips {
+                mesh: global_static_mesh,
+                indices: builder.trigger_strip_indices,
+                entity: None,
+            },
+            decor: DecorModels {
+                mesh: global_decor_mesh,
+                models: decor_models,
+            },
+            pickups: builder.pickups,
+            debug_view: DebugView::Normal,
             triggers: deps.wad.analysis.take_triggers(),
             removed: Vec::with_capacity(128),
             effects: VecMap::new(),
@@ -517,37 +761,110 @@ impl<'a> Builder<'a> {
         y: f32,
         tile_u: f32,
         tile_v: f32,
-        light_info: u8,
+        light_info: LightIndex,
         scroll_rate: f32,
         bounds: &WadBounds,
     ) -> &mut Self {
         self.static_vertices.push(StaticVertex {
             a_pos: [xz[0], y, xz[1]],
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
+            a_atlas_page: bounds.page as u8,
             a_tile_uv: [tile_u, tile_v],
             a_tile_size: [bounds.size[0], bounds.size[1]],
             a_scroll_rate: scroll_rate,
             a_num_frames: bounds.num_frames as u8,
             a_row_height: bounds.row_height as f32,
             a_light: light_info,
+            a_debug_leaf: self.debug.leaf,
+            a_debug_object: self.debug.object,
+            a_debug_trigger: u8::from(self.debug.trigger),
         });
         self
     }
 
-    fn flat_vertex(&mut self, xz: Pnt2f, y: f32, light_info: u8, bounds: &WadBounds) -> &mut Self {
+    fn flat_vertex(
+        &mut self,
+        xz: Pnt2f,
+        y: f32,
+        light_info: LightIndex,
+        bounds: &WadBounds,
+    ) -> &mut Self {
         self.static_vertices.push(StaticVertex {
             a_pos: [xz[0], y, xz[1]],
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
+            a_atlas_page: bounds.page as u8,
             a_tile_uv: [-xz[0] * 100.0, -xz[1] * 100.0],
             a_tile_size: [bounds.size[0], bounds.size[1]],
             a_scroll_rate: 0.0,
             a_num_frames: bounds.num_frames as u8,
             a_row_height: bounds.row_height as f32,
             a_light: light_info,
+            a_debug_leaf: self.debug.leaf,
+            a_debug_object: self.debug.object,
+            a_debug_trigger: u8::from(self.debug.trigger),
+        });
+        self
+    }
+
+    /// A vertex of a trigger strip, which is only ever drawn with the debug material.
+    fn trigger_vertex(&mut self, xz: Pnt2f, y: f32, light_info: LightIndex) -> &mut Self {
+        self.static_vertices.push(StaticVertex {
+            a_pos: [xz[0], y, xz[1]],
+            a_atlas_uv: [0.0, 0.0],
+            a_atlas_page: 0,
+            a_tile_uv: [0.0, 0.0],
+            a_tile_size: [1.0, 1.0],
+            a_scroll_rate: 0.0,
+            a_num_frames: 1,
+            a_row_height: 1.0,
+            a_light: light_info,
+            a_debug_leaf: 0,
+            a_debug_object: 0,
+            a_debug_trigger: 1,
         });
         self
     }
 
+    /// Adds a short strip standing on the floor along a trigger line which has no wall, so that
+    /// walk-over lines between floors of the same height show up in the triggers debug view.
+    fn trigger_strip(&mut self, level: &WadLevel, trigger: &Trigger) {
+        let linedef = if let Some(linedef) = level.linedefs.get(trigger.linedef) {
+            linedef
+        } else {
+            return;
+        };
+        let low = [level.right_sidedef(linedef), level.left_sidedef(linedef)]
+            .iter()
+            .flatten()
+            .filter_map(|sidedef| level.sidedef_sector(sidedef))
+            .map(|sector| from_wad_height(sector.floor_height))
+            .fold(None, |low: Option<f32>, floor| {
+                Some(low.map_or(floor, |low| low.max(floor)))
+            });
+        let low = if let Some(low) = low {
+            low
+        } else {
+            return;
+        };
+        let high = low + TRIGGER_STRIP_HEIGHT;
+        let (v1, v2) = (
+            trigger.line.origin,
+            trigger.line.at_offset(trigger.line.length),
+        );
+        let light_info = self.add_light_info(&LightInfo {
+            level: 1.0,
+            effect: None,
+        });
+        // Both windings, since back faces are culled.
+        for &(v1, v2) in &[(v1, v2), (v2, v1)] {
+            self.trigger_vertex(v1, low, light_info)
+                .trigger_vertex(v2, low, light_info)
+                .trigger_vertex(v2, high, light_info)
+                .trigger_vertex(v1, high, light_info);
+            Self::any_quad(self.static_vertices.len(), &mut self.trigger_strip_indices);
+        }
+    }
+
     fn sky_vertex(&mut self, xz: Pnt2f, y: f32) -> &mut Self {
         self.sky_vertices.push(SkyVertex {
             a_pos: [xz[0], y, xz[1]],
@@ -559,23 +876,74 @@ impl<'a> Builder<'a> {
         &mut self,
         pos: Pnt3f,
         local_x: f32,
-        tile_u: f32,
-        tile_v: f32,
+        (tile_u, tile_v): (f32, f32),
         bounds: &WadBounds,
-        light_info: u8,
+        (yaw, rotation): (Rad<f32>, u8),
+        (light_info, fullbright): (LightIndex, bool),
     ) -> &mut Self {
         self.decor_vertices.push(SpriteVertex {
             a_pos: [pos[0], pos[1], pos[2]],
             a_local_x: local_x,
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
+            a_atlas_page: bounds.page as u8,
             a_tile_uv: [tile_u, tile_v],
             a_tile_size: [bounds.size[0], bounds.size[1]],
+            a_yaw: yaw.0,
             a_num_frames: 1,
+            a_rotation: rotation,
             a_light: light_info,
+            a_fullbright: u8::from(fullbright),
         });
         self
     }
 
+    fn decor_sprite(
+        &mut self,
+        object_id: ObjectId,
+        sprite: &DecorRotation,
+        rotation: (Rad<f32>, u8),
+        light: (LightIndex, bool),
+    ) {
+        let &DecorRotation {
+            low,
+            high,
+            half_width,
+            tex_name,
+            mirrored,
+        } = sprite;
+        let bounds = if let Some(bounds) = self.materials.decor.bounds.get(&tex_name) {
+            *bounds
+        } else {
+            warn!("No such decor texture {}.", tex_name);
+            return;
+        };
+        let (left_u, right_u) = if mirrored {
+            (bounds.size[0], 0.0)
+        } else {
+            (0.0, bounds.size[0])
+        };
+        let (top_v, bottom_v) = (0.0, bounds.size[1]);
+        self.decor_vertex(
+            low,
+            -half_width,
+            (left_u, bottom_v),
+            &bounds,
+            rotation,
+            light,
+        )
+        .decor_vertex(
+            low,
+            half_width,
+            (right_u, bottom_v),
+            &bounds,
+            rotation,
+            light,
+        )
+        .decor_vertex(high, half_width, (right_u, top_v), &bounds, rotation, light)
+        .decor_vertex(high, -half_width, (left_u, top_v), &bounds, rotation, light)
+        .decor_quad(object_id);
+    }
+
     fn flat_poly(&mut self, object_id: ObjectId, poly_length: usize) {
         Self::any_poly(
             self.static_vertices.len(),
@@ -606,6 +974,12 @@ impl<'a> Builder<'a> {
         );
     }
 
+    fn num_decor_indices(&mut self, object_id: ObjectId) -> usize {
+        Indices::in_map(&mut self.object_indices, object_id)
+            .decor
+            .len()
+    }
+
     fn decor_quad(&mut self, object_id: ObjectId) {
         Self::any_quad(
             self.decor_vertices.len(),
@@ -613,7 +987,54 @@ impl<'a> Builder<'a> {
         );
     }
 
-    fn add_light_info(&mut self, light_info: &LightInfo) -> u8 {
+    fn begin_culling_node(&mut self, bounds: &Aabb3) {
+        let (num_walls, num_flats) = self.num_static_indices();
+        self.wall_culling.begin_node(*bounds, num_walls);
+        self.flat_culling.begin_node(*bounds, num_flats);
+    }
+
+    /// Grows the current leaf's bounds to contain the static geometry added since it began, which
+    /// can stick out of the BSP's bounding boxes.
+    fn include_leaf_geometry(&mut self) {
+        let indices = match self.object_indices.get(0) {
+            Some(indices) => indices,
+            None => return,
+        };
+        let vertices = &self.static_vertices;
+        let bounds = |indices: &[u32]| {
+            Aabb3::from_points(
+                indices
+                    .iter()
+                    .map(|&index| Pnt3f::from(vertices[index as usize].a_pos)),
+            )
+        };
+        let (walls_start, flats_start) = self.leaf_start;
+        if let Some(bounds) = bounds(&indices.wall[walls_start..]) {
+            self.wall_culling.include(&bounds);
+        }
+        if let Some(bounds) = bounds(&indices.flat[flats_start..]) {
+            self.flat_culling.include(&bounds);
+        }
+    }
+
+    fn end_culling_node(&mut self) {
+        let (num_walls, num_flats) = self.num_static_indices();
+        self.wall_culling.end_node(num_walls);
+        self.flat_culling.end_node(num_flats);
+    }
+
+    fn num_static_indices(&self) -> (usize, usize) {
+        self.object_indices
+            .get(0)
+            .map_or((0, 0), |indices| (indices.wall.len(), indices.flat.len()))
+    }
+
+    fn set_debug_info(&mut self, object_id: ObjectId, trigger: bool) {
+        self.debug.object = object_id.0 as u16;
+        self.debug.trigger = trigger;
+    }
+
+    fn add_light_info(&mut self, light_info: &LightInfo) -> LightIndex {
         self.lights.push(light_info)
     }
 
@@ -658,6 +1079,7 @@ impl<'a> LevelVisitor for Builder<'a> {
             height_range: (low, high),
             tex_start: (s1, t1),
             tex_end: (s2, t2),
+            linedef,
             ..
         } = quad;
 
@@ -673,6 +1095,11 @@ impl<'a> LevelVisitor for Builder<'a> {
             return;
         };
         let light_info = self.add_light_info(light_info);
+        let trigger = self.trigger_linedefs.contains(&linedef);
+        if trigger {
+            self.walled_trigger_linedefs.insert(linedef);
+        }
+        self.set_debug_info(object_id, trigger);
         self.wall_vertex(v1, low, s1, t1, light_info, scroll, &bounds)
             .wall_vertex(v2, low, s2, t1, light_info, scroll, &bounds)
             .wall_vertex(v2, high, s2, t2, light_info, scroll, &bounds)
@@ -696,6 +1123,7 @@ impl<'a> LevelVisitor for Builder<'a> {
             return;
         };
         let light_info = self.add_light_info(light_info);
+        self.set_debug_info(object_id, false);
         for &vertex in vertices {
             self.flat_vertex(vertex, height, light_info, &bounds);
         }
@@ -718,6 +1146,7 @@ impl<'a> LevelVisitor for Builder<'a> {
             return;
         };
         let light_info = self.add_light_info(light_info);
+        self.set_debug_info(object_id, false);
         for &vertex in vertices.iter().rev() {
             self.flat_vertex(vertex, height, light_info, &bounds);
         }
@@ -754,6 +1183,30 @@ impl<'a> LevelVisitor for Builder<'a> {
             .sky_quad(object_id);
     }
 
+    fn visit_bsp_root(&mut self, _line: &Line2f, bounds: &Aabb3) {
+        self.begin_culling_node(bounds);
+    }
+
+    fn visit_bsp_node(&mut self, _line: &Line2f, _branch: Branch, bounds: &Aabb3) {
+        self.begin_culling_node(bounds);
+    }
+
+    fn visit_bsp_leaf(&mut self, _branch: Branch, bounds: &Aabb3) {
+        self.debug.leaf = self.num_leaves as u16;
+        self.num_leaves += 1;
+        self.leaf_start = self.num_static_indices();
+        self.begin_culling_node(bounds);
+    }
+
+    fn visit_bsp_leaf_end(&mut self) {
+        self.include_leaf_geometry();
+        self.end_culling_node();
+    }
+
+    fn visit_bsp_node_end(&mut self) {
+        self.end_culling_node();
+    }
+
     fn visit_marker(&mut self, pos: Pnt3f, yaw: Rad<f32>, marker: Marker) {
         if let Marker::StartPos { player: 0 } = marker {
             self.start_pos = pos + Vec3f::new(0.0, 0.5, 32.0 / 100.0);
@@ -765,30 +1218,57 @@ impl<'a> LevelVisitor for Builder<'a> {
         self.num_decors += 1;
         let &Decor {
             object_id,
+            thing_type,
             low,
             high,
             half_width,
+            yaw,
+            radius,
             light_info,
+            fullbright,
             tex_name,
+            rotations,
+            ..
         } = decor;
-        let light_info = self.add_light_info(light_info);
-        let bounds = if let Some(bounds) = self.materials.decor.bounds.get(&tex_name) {
-            *bounds
-        } else {
-            warn!("No such decor texture {}.", tex_name);
-            return;
-        };
-        self.decor_vertex(low, -half_width, 0.0, bounds.size[1], &bounds, light_info)
-            .decor_vertex(
+        let light = (self.add_light_info(light_info), fullbright);
+        let start = self.num_decor_indices(object_id);
+        if rotations.is_empty() {
+            let sprite = DecorRotation {
                 low,
+                high,
                 half_width,
-                bounds.size[0],
-                bounds.size[1],
-                &bounds,
-                light_info,
-            )
-            .decor_vertex(high, half_width, bounds.size[0], 0.0, &bounds, light_info)
-            .decor_vertex(high, -half_width, 0.0, 0.0, &bounds, light_info)
-            .decor_quad(object_id);
+                tex_name,
+                mirrored: false,
+            };
+            self.decor_sprite(object_id, &sprite, (yaw, 0), light);
+        } else {
+            // Each rotation gets its own quad, the sprite shader collapses all but the one matching
+            // the viewing angle. The shader works in world space, where angles go the other way
+            // around.
+            let world_yaw = -Rad::turn_div_4() - yaw;
+            for (index, rotation) in rotations.iter().enumerate() {
+                self.decor_sprite(object_id, rotation, (world_yaw, index as u8 + 1), light);
+            }
+        }
+
+        if let Some(pickup) = Pickup::from_thing_type(thing_type) {
+            let end = self.num_decor_indices(object_id);
+            self.pickups.push(PickupItem {
+                pickup,
+                object: object_id.0 as usize,
+                position: low,
+                radius,
+                indices: start..end,
+                taken: false,
+            });
+        }
     }
 }
+
+/// How far below the feet, and how far above them, pickups can be touched: vanilla's 8 units and
+/// player height.
+const PICKUP_REACH_BELOW: f32 = 0.08;
+const PLAYER_HEIGHT: f32 = 0.56;
+
+/// How high trigger strips stand above the floor, like a short step.
+const TRIGGER_STRIP_HEIGHT: f32 = 0.08;
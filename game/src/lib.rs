@@ -1,18 +1,33 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
 
+mod automap;
+mod bindings;
+mod camera_path;
 mod errors;
+mod export;
+mod fonts;
 mod game;
 mod game_shaders;
 mod hud;
 mod level;
 mod lights;
+mod map_view;
+mod palette_effects;
+mod pickups;
 mod player;
+mod player_status;
+mod software_shaders;
+mod status_bar;
+mod top_down;
 mod vertex;
 mod wad_system;
 mod world;
 
 pub use self::errors::{Error, Result};
+pub use self::export::{export_level, ExportFormat};
 pub use self::game::{create, Game, GameConfig};
 pub use self::level::Level;
+pub use self::palette_effects::{PaletteEffects, PaletteEvent};
+pub use self::player::CameraPose;
 
 pub const SHADER_ROOT: &str = "assets/shaders";
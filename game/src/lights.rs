@@ -1,26 +1,39 @@
+use log::error;
+use std::collections::HashMap;
 use wad::{LightEffectKind, LightInfo};
 
+/// Index of a light in the lights buffer texture.
+pub type LightIndex = u16;
+
+/// The number of distinct lights a level can have, and the size of the lights buffer texture.
+pub const MAX_LIGHTS: usize = LightIndex::MAX as usize + 1;
+
 pub struct Lights {
     lights: Vec<LightInfo>,
+    indices: HashMap<LightKey, LightIndex>,
 }
 
 impl Lights {
     pub fn new() -> Self {
         Self {
             lights: Vec::with_capacity(256),
+            indices: HashMap::with_capacity(256),
         }
     }
 
-    pub fn push(&mut self, light_info: &LightInfo) -> u8 {
-        self.lights
-            .iter()
-            .position(|x| x == light_info)
-            .unwrap_or_else(|| {
-                // TODO(cristicbz): Remove this restriction.
-                assert!(self.lights.len() < 255);
-                self.lights.push(light_info.clone());
-                self.lights.len() - 1
-            }) as u8
+    pub fn push(&mut self, light_info: &LightInfo) -> LightIndex {
+        let lights = &mut self.lights;
+        *self
+            .indices
+            .entry(LightKey::new(light_info))
+            .or_insert_with(|| {
+                if lights.len() == MAX_LIGHTS {
+                    error!("Too many distinct lights, reusing the first one.");
+                    return 0;
+                }
+                lights.push(light_info.clone());
+                (lights.len() - 1) as LightIndex
+            })
     }
 
     pub fn fill_buffer_at(&mut self, time: f32, buffer: &mut [u8]) {
@@ -30,6 +43,32 @@ impl Lights {
     }
 }
 
+/// A hashable `LightInfo`, comparing levels by their bits.
+#[derive(PartialEq, Eq, Hash)]
+struct LightKey {
+    level: u32,
+    effect: Option<([u32; 4], LightEffectKind)>,
+}
+
+impl LightKey {
+    fn new(info: &LightInfo) -> Self {
+        Self {
+            level: info.level.to_bits(),
+            effect: info.effect.as_ref().map(|effect| {
+                (
+                    [
+                        effect.alt_level.to_bits(),
+                        effect.speed.to_bits(),
+                        effect.duration.to_bits(),
+                        effect.sync.to_bits(),
+                    ],
+                    effect.kind,
+                )
+            }),
+        }
+    }
+}
+
 fn light_level_at(info: &LightInfo, time: f32) -> f32 {
     let effect = if let Some(ref effect) = info.effect {
         effect
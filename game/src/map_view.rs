@@ -0,0 +1,65 @@
+use engine::{Analog2d, Gesture, Input};
+use math::prelude::*;
+use math::{Pnt2f, Vec2f};
+
+/// The part of the level shown by a view looking straight down at it with north up, like the
+/// automap and the top-down camera, zoomed and panned the same way in both.
+pub struct MapView {
+    /// The point at the middle of the view, on the horizontal plane of the world (`x` and `z`).
+    pub center: Pnt2f,
+    /// Half the height of the view in world units.
+    pub half_height: f32,
+    /// Whether the view is centered on the player. Panning turns this off.
+    pub follow: bool,
+}
+
+impl Default for MapView {
+    fn default() -> Self {
+        MapView {
+            center: Pnt2f::origin(),
+            half_height: DEFAULT_HALF_HEIGHT,
+            follow: true,
+        }
+    }
+}
+
+impl MapView {
+    /// Zooms while `zoom_in` or `zoom_out` are held and pans by `pan`, then centers the view on
+    /// `player` if it is following them.
+    pub fn update(
+        &mut self,
+        input: &Input,
+        delta_time: f32,
+        (zoom_in, zoom_out): (&Gesture, &Gesture),
+        pan: &Analog2d,
+        player: Pnt2f,
+    ) {
+        if input.poll_gesture(zoom_in) {
+            self.half_height /= ZOOM_PER_SECOND.powf(delta_time);
+        }
+        if input.poll_gesture(zoom_out) {
+            self.half_height *= ZOOM_PER_SECOND.powf(delta_time);
+        }
+        self.half_height = self.half_height.clamp(MIN_HALF_HEIGHT, MAX_HALF_HEIGHT);
+
+        // Panning is in screen space: right moves east (-z) and up moves north (-x).
+        let pan = input.poll_analog2d(pan) * PAN_SPEED * self.half_height;
+        if pan != Vec2f::zero() {
+            self.follow = false;
+            self.center.x -= pan.y * delta_time;
+            self.center.y -= pan.x * delta_time;
+        }
+        if self.follow {
+            self.center = player;
+        }
+    }
+}
+
+/// Half the height of the view in world units, i.e. hundreds of map units.
+const DEFAULT_HALF_HEIGHT: f32 = 10.0;
+const MIN_HALF_HEIGHT: f32 = 1.0;
+const MAX_HALF_HEIGHT: f32 = 200.0;
+const ZOOM_PER_SECOND: f32 = 2.0;
+
+/// Panning speed in view heights per second.
+const PAN_SPEED: f32 = 1.0;
@@ -0,0 +1,167 @@
+use super::game_shaders::ColormapMode;
+use super::wad_system::WadSystem;
+use engine::{DependenciesFrom, InfallibleSystem, Tick};
+
+/// Something that happened to the player, which changes how the screen is coloured.
+#[derive(Copy, Clone, Debug)]
+pub enum PaletteEvent {
+    /// The player took this much damage.
+    Damage(u32),
+    /// The player picked up an item.
+    Bonus,
+    /// The player picked up a radiation suit.
+    RadiationSuit,
+    /// The player picked up an invulnerability sphere.
+    Invulnerability,
+    /// The player picked up a light amplification visor.
+    LightAmplification,
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    tick: &'context Tick,
+
+    wad: &'context WadSystem,
+}
+
+/// Picks the `PLAYPAL` palettes to flash the screen red (damage), gold (pickups) or green
+/// (radiation suit), fading out like vanilla does. Also picks the colormap mode for the
+/// invulnerability and light amplification powerups.
+///
+/// `GameShaders` reads both every update, so this system must be bound before it.
+pub struct PaletteEffects {
+    // Counters in vanilla tics, same as the `player_t` fields.
+    damage_count: f32,
+    bonus_count: f32,
+    radiation_suit: f32,
+    invulnerability: f32,
+    light_amplification: f32,
+
+    debug_colormap_mode: Option<ColormapMode>,
+}
+
+impl PaletteEffects {
+    pub fn trigger(&mut self, event: PaletteEvent) {
+        match event {
+            PaletteEvent::Damage(damage) => {
+                self.damage_count = (self.damage_count + damage as f32).min(MAX_DAMAGE_COUNT);
+            }
+            PaletteEvent::Bonus => self.bonus_count += BONUS_ADD,
+            PaletteEvent::RadiationSuit => self.radiation_suit = RADIATION_SUIT_TICS,
+            PaletteEvent::Invulnerability => self.invulnerability = INVULNERABILITY_TICS,
+            PaletteEvent::LightAmplification => self.light_amplification = LIGHT_AMPLIFICATION_TICS,
+        }
+    }
+
+    /// Whether a radiation suit is protecting the player from damaging floors.
+    pub fn has_radiation_suit(&self) -> bool {
+        self.radiation_suit > 0.0
+    }
+
+    /// Whether the player is invulnerable, and so takes no damage.
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerability > 0.0
+    }
+
+    /// Cycles through forcing each colormap mode, then back to following powerups.
+    pub fn cycle_debug_colormap_mode(&mut self) {
+        self.debug_colormap_mode = match self.debug_colormap_mode {
+            None => Some(ColormapMode::Fullbright),
+            Some(ColormapMode::Fullbright) => Some(ColormapMode::Inverse),
+            Some(ColormapMode::Inverse) => Some(ColormapMode::Normal),
+            Some(ColormapMode::Normal) => None,
+        };
+    }
+
+    fn reset(&mut self) {
+        self.damage_count = 0.0;
+        self.bonus_count = 0.0;
+        self.radiation_suit = 0.0;
+        self.invulnerability = 0.0;
+        self.light_amplification = 0.0;
+    }
+
+    /// The palette to use, as picked by vanilla's `ST_doPaletteStuff`.
+    pub fn palette_index(&self) -> usize {
+        let damage_count = self.damage_count.ceil() as usize;
+        let bonus_count = self.bonus_count.ceil() as usize;
+        if damage_count > 0 {
+            START_RED_PALETTES + ((damage_count + 7) >> 3).min(NUM_RED_PALETTES - 1)
+        } else if bonus_count > 0 {
+            START_BONUS_PALETTES + ((bonus_count + 7) >> 3).min(NUM_BONUS_PALETTES - 1)
+        } else if powerup_visible(self.radiation_suit) {
+            RADIATION_PALETTE
+        } else {
+            0
+        }
+    }
+
+    /// The colormap mode to use, as picked by vanilla's `P_PlayerThink`.
+    pub fn colormap_mode(&self) -> ColormapMode {
+        if let Some(mode) = self.debug_colormap_mode {
+            mode
+        } else if powerup_visible(self.invulnerability) {
+            ColormapMode::Inverse
+        } else if powerup_visible(self.light_amplification) {
+            ColormapMode::Fullbright
+        } else {
+            ColormapMode::Normal
+        }
+    }
+}
+
+impl<'context> InfallibleSystem<'context> for PaletteEffects {
+    type Dependencies = Dependencies<'context>;
+
+    fn debug_name() -> &'static str {
+        "palette_effects"
+    }
+
+    fn create(_deps: Dependencies) -> Self {
+        PaletteEffects {
+            damage_count: 0.0,
+            bonus_count: 0.0,
+            radiation_suit: 0.0,
+            invulnerability: 0.0,
+            light_amplification: 0.0,
+            debug_colormap_mode: None,
+        }
+    }
+
+    fn update(&mut self, deps: Dependencies) {
+        if deps.wad.level_changed() {
+            self.reset();
+        }
+
+        let tics = deps.tick.timestep() * TICS_PER_SECOND;
+        for counter in &mut [
+            &mut self.damage_count,
+            &mut self.bonus_count,
+            &mut self.radiation_suit,
+            &mut self.invulnerability,
+            &mut self.light_amplification,
+        ] {
+            **counter = (**counter - tics).max(0.0);
+        }
+    }
+}
+
+/// Powerup effects flicker on and off while running out.
+fn powerup_visible(tics: f32) -> bool {
+    let tics = tics.ceil() as usize;
+    tics > 4 * 32 || tics & 8 != 0
+}
+
+const TICS_PER_SECOND: f32 = 35.0;
+
+const START_RED_PALETTES: usize = 1;
+const NUM_RED_PALETTES: usize = 8;
+const START_BONUS_PALETTES: usize = 9;
+const NUM_BONUS_PALETTES: usize = 4;
+const RADIATION_PALETTE: usize = 13;
+
+const MAX_DAMAGE_COUNT: f32 = 100.0;
+const BONUS_ADD: f32 = 6.0;
+const RADIATION_SUIT_TICS: f32 = 60.0 * TICS_PER_SECOND;
+const INVULNERABILITY_TICS: f32 = 30.0 * TICS_PER_SECOND;
+const LIGHT_AMPLIFICATION_TICS: f32 = 120.0 * TICS_PER_SECOND;
@@ -0,0 +1,119 @@
+use wad::types::ThingType;
+
+/// An item the player picks up by walking over it, named after vanilla's sprites.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Pickup {
+    Stimpack,
+    Medikit,
+    HealthBonus,
+    Soulsphere,
+    Megasphere,
+    ArmorBonus,
+    GreenArmor,
+    BlueArmor,
+    /// Indexed like `PlayerStatus::keys`.
+    Key(usize),
+    /// Indexed like `PlayerStatus::ammo`, with the amount in vanilla clips (`CLIP_AMMO`).
+    Ammo {
+        ammo: usize,
+        clips: u32,
+    },
+    Backpack,
+    /// Indexed like `PlayerStatus::weapons`.
+    Weapon(usize),
+    /// Powerups, which `PaletteEffects` keeps track of.
+    Invulnerability,
+    RadiationSuit,
+    LightAmplification,
+}
+
+impl Pickup {
+    /// The item placed by things of `thing_type`, if any.
+    pub fn from_thing_type(thing_type: ThingType) -> Option<Self> {
+        Some(match thing_type {
+            2011 => Pickup::Stimpack,
+            2012 => Pickup::Medikit,
+            2014 => Pickup::HealthBonus,
+            2013 => Pickup::Soulsphere,
+            83 => Pickup::Megasphere,
+            2015 => Pickup::ArmorBonus,
+            2018 => Pickup::GreenArmor,
+            2019 => Pickup::BlueArmor,
+
+            5 => Pickup::Key(0),
+            6 => Pickup::Key(1),
+            13 => Pickup::Key(2),
+            40 => Pickup::Key(3),
+            39 => Pickup::Key(4),
+            38 => Pickup::Key(5),
+
+            2007 => Pickup::Ammo {
+                ammo: BULLETS,
+                clips: 1,
+            },
+            2048 => Pickup::Ammo {
+                ammo: BULLETS,
+                clips: 5,
+            },
+            2008 => Pickup::Ammo {
+                ammo: SHELLS,
+                clips: 1,
+            },
+            2049 => Pickup::Ammo {
+                ammo: SHELLS,
+                clips: 5,
+            },
+            2047 => Pickup::Ammo {
+                ammo: CELLS,
+                clips: 1,
+            },
+            17 => Pickup::Ammo {
+                ammo: CELLS,
+                clips: 5,
+            },
+            2010 => Pickup::Ammo {
+                ammo: ROCKETS,
+                clips: 1,
+            },
+            2046 => Pickup::Ammo {
+                ammo: ROCKETS,
+                clips: 5,
+            },
+            8 => Pickup::Backpack,
+
+            2001 => Pickup::Weapon(2),
+            2002 => Pickup::Weapon(3),
+            2003 => Pickup::Weapon(4),
+            2004 => Pickup::Weapon(5),
+            2006 => Pickup::Weapon(6),
+            2005 => Pickup::Weapon(7),
+            82 => Pickup::Weapon(8),
+
+            2022 => Pickup::Invulnerability,
+            2025 => Pickup::RadiationSuit,
+            2045 => Pickup::LightAmplification,
+            _ => return None,
+        })
+    }
+}
+
+/// Vanilla's `clipammo`: how much of each ammo a clip holds.
+pub const CLIP_AMMO: [u32; 4] = [10, 4, 20, 1];
+
+/// The ammo each weapon fires, like vanilla's `weaponinfo`.
+pub const WEAPON_AMMO: [Option<usize>; 9] = [
+    None,
+    Some(BULLETS),
+    Some(SHELLS),
+    Some(BULLETS),
+    Some(ROCKETS),
+    Some(CELLS),
+    Some(CELLS),
+    None,
+    Some(SHELLS),
+];
+
+pub const BULLETS: usize = 0;
+pub const SHELLS: usize = 1;
+pub const CELLS: usize = 2;
+pub const ROCKETS: usize = 3;
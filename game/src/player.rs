@@ -1,13 +1,22 @@
 use super::level::{Level, PlayerAction};
+use super::palette_effects::{PaletteEffects, PaletteEvent};
+use super::pickups::Pickup;
+use super::player_status::PlayerStatus;
+use super::wad_system::WadSystem;
 use engine::{
     Analog2d, DependenciesFrom, Entities, EntityId, Gesture, InfallibleSystem, Input, MouseButton,
-    Projection, Projections, RenderPipeline, Scancode, Tick, Transforms, Window,
+    PadButton, PadStick, Projection, Projections, RenderPipeline, Scancode, Tick, Transforms,
+    Window,
 };
 use log::error;
 use math::prelude::*;
-use math::{vec3, Deg, Euler, Pnt3f, Quat, Rad, Sphere, Trans3, Vec3f};
+use math::{vec3, Deg, Euler, Pnt2f, Pnt3f, Quat, Rad, Sphere, Trans3, Vec3f};
+use serde::Deserialize;
 use std::f32::consts::FRAC_PI_2;
+use wad::types::SectorType;
 
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Bindings {
     pub movement: Analog2d,
     pub look: Analog2d,
@@ -21,12 +30,21 @@ pub struct Bindings {
 impl Default for Bindings {
     fn default() -> Bindings {
         Bindings {
-            movement: Analog2d::Gestures {
-                x_positive: Gesture::KeyHold(Scancode::D),
-                x_negative: Gesture::KeyHold(Scancode::A),
-                y_positive: Gesture::KeyHold(Scancode::S),
-                y_negative: Gesture::KeyHold(Scancode::W),
-                step: 1.0,
+            movement: Analog2d::Sum {
+                analogs: vec![
+                    Analog2d::Gestures {
+                        x_positive: Gesture::KeyHold(Scancode::D),
+                        x_negative: Gesture::KeyHold(Scancode::A),
+                        y_positive: Gesture::KeyHold(Scancode::S),
+                        y_negative: Gesture::KeyHold(Scancode::W),
+                        step: 1.0,
+                    },
+                    Analog2d::PadStick {
+                        stick: PadStick::Left,
+                        deadzone: 0.2,
+                        sensitivity: 1.0,
+                    },
+                ],
             },
             look: Analog2d::Sum {
                 analogs: vec![
@@ -39,12 +57,27 @@ impl Default for Bindings {
                     },
                     Analog2d::Mouse {
                         sensitivity: 0.0015,
+                        invert_y: false,
+                    },
+                    Analog2d::PadStick {
+                        stick: PadStick::Right,
+                        deadzone: 0.2,
+                        sensitivity: 0.04,
                     },
                 ],
             },
-            jump: Gesture::KeyHold(Scancode::Space),
-            push: Gesture::KeyTrigger(Scancode::E),
-            shoot: Gesture::ButtonTrigger(MouseButton::Left),
+            jump: Gesture::AnyOf(vec![
+                Gesture::KeyHold(Scancode::Space),
+                Gesture::PadHold(PadButton::South),
+            ]),
+            push: Gesture::AnyOf(vec![
+                Gesture::KeyTrigger(Scancode::E),
+                Gesture::PadTrigger(PadButton::West),
+            ]),
+            shoot: Gesture::AnyOf(vec![
+                Gesture::ButtonTrigger(MouseButton::Left),
+                Gesture::PadTrigger(PadButton::RightTrigger),
+            ]),
             fly: Gesture::KeyTrigger(Scancode::F),
             clip: Gesture::KeyTrigger(Scancode::C),
         }
@@ -104,18 +137,86 @@ pub struct Dependencies<'context> {
     projections: &'context mut Projections,
     render: &'context mut RenderPipeline,
 
+    wad: &'context WadSystem,
     level: &'context mut Level,
+    palette_effects: &'context mut PaletteEffects,
+}
+
+/// A fixed camera placement, which overrides movement and input while set.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraPose {
+    pub position: Pnt3f,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+}
+
+impl CameraPose {
+    /// Creates a pose from an eye position in WAD map units (`z` is the absolute eye height) and
+    /// angles in degrees. `yaw` follows thing angles (0 is east, 90 is north) and positive `pitch`
+    /// looks up.
+    pub fn from_wad(x: f32, y: f32, z: f32, yaw: Deg<f32>, pitch: Deg<f32>) -> Self {
+        // Same axes as `wad::util::from_wad_coords`.
+        CameraPose {
+            position: Pnt3f::new(-y / 100.0, z / 100.0, -x / 100.0),
+            yaw: yaw.into(),
+            pitch: pitch.into(),
+        }
+    }
+
+    /// The inverse of `from_wad`: the eye position in WAD map units, yaw and pitch in degrees.
+    pub fn to_wad(&self) -> ([f32; 3], Deg<f32>, Deg<f32>) {
+        let position = self.position;
+        (
+            [-position.z * 100.0, -position.x * 100.0, position.y * 100.0],
+            self.yaw.into(),
+            self.pitch.into(),
+        )
+    }
+
+    /// The pose of a camera with `transform`, which must not be rolled.
+    pub fn from_transform(transform: &Trans3) -> Self {
+        let forward = transform.rot.rotate_vector(vec3(0.0, 0.0, -1.0));
+        CameraPose {
+            position: Pnt3f::from_vec(transform.disp),
+            yaw: Rad((-forward.x).atan2(-forward.z)),
+            pitch: Rad(forward.y.clamp(-1.0, 1.0).asin()),
+        }
+    }
 }
 
 pub struct Player {
     id: EntityId,
+    camera: EntityId,
+    pose: Option<CameraPose>,
     velocity: Vec3f,
     fly: bool,
     clip: bool,
+    grounded: bool,
+    hazard_time: f32,
     last_height_diff: f32,
+    status: PlayerStatus,
 }
 
 impl Player {
+    /// Places the camera at `pose` until it is cleared with `None`.
+    pub fn set_pose(&mut self, pose: Option<CameraPose>) {
+        self.pose = pose;
+    }
+
+    pub fn status(&self) -> &PlayerStatus {
+        &self.status
+    }
+
+    /// The root entity, whose local transform is the player's position and orientation.
+    pub fn entity(&self) -> EntityId {
+        self.id
+    }
+
+    /// The entity with the player's first person projection.
+    pub fn camera(&self) -> EntityId {
+        self.camera
+    }
+
     fn reset(&mut self, transforms: &mut Transforms, level: &Level) {
         let transform = transforms
             .get_local_mut(self.id)
@@ -129,7 +230,12 @@ impl Player {
         transform.disp = level.start_pos().to_vec();
 
         self.velocity = Vec3f::zero();
+        self.hazard_time = 0.0;
         self.last_height_diff = 0.0;
+        if self.status.is_dead() {
+            // Dead players restart the next level from scratch, like vanilla's reborn.
+            self.status = PlayerStatus::default();
+        }
     }
 
     fn head(&self, config: &Config, transform: &Trans3) -> Sphere {
@@ -189,6 +295,74 @@ impl Player {
         }
     }
 
+    /// Hurts the player every 32 tics while they stand on a damaging floor, like vanilla's
+    /// `P_PlayerInSpecialSector`.
+    fn hazard(
+        &mut self,
+        delta_time: f32,
+        position: Pnt2f,
+        wad: &WadSystem,
+        palette_effects: &mut PaletteEffects,
+    ) {
+        let sector_type = wad
+            .level
+            .sector_at(position)
+            .map_or(0, |sector| sector.sector_type);
+        let protected = palette_effects.has_radiation_suit();
+        let damage = match sector_type {
+            HELLSLIME_SECTOR if !protected => 10,
+            NUKAGE_SECTOR if !protected => 5,
+            SUPER_HELLSLIME_SECTOR_1 | SUPER_HELLSLIME_SECTOR_2 if !protected => 20,
+            EXIT_DAMAGE_SECTOR => 20,
+            _ => 0,
+        };
+        if damage == 0
+            || !self.grounded
+            || self.fly
+            || self.status.is_dead()
+            || palette_effects.is_invulnerable()
+        {
+            self.hazard_time = 0.0;
+            return;
+        }
+        self.hazard_time += delta_time;
+        if self.hazard_time >= HAZARD_PERIOD {
+            self.hazard_time -= HAZARD_PERIOD;
+            self.status.damage(damage);
+            palette_effects.trigger(PaletteEvent::Damage(damage));
+        }
+    }
+
+    /// Picks up the items touched by a player standing at `feet`, flashing the screen gold for each
+    /// one taken and starting powerups, like vanilla's `P_TouchSpecialThing`.
+    fn touch_pickups(
+        &mut self,
+        feet: Pnt3f,
+        config: &Config,
+        level: &mut Level,
+        transforms: &Transforms,
+        palette_effects: &mut PaletteEffects,
+    ) {
+        if self.status.is_dead() {
+            return;
+        }
+        for (i_pickup, pickup) in level.pickups_touching(feet, config.radius, transforms) {
+            if self.status.pick_up(pickup) {
+                level.remove_pickup(i_pickup);
+                palette_effects.trigger(PaletteEvent::Bonus);
+                let powerup = match pickup {
+                    Pickup::Invulnerability => Some(PaletteEvent::Invulnerability),
+                    Pickup::RadiationSuit => Some(PaletteEvent::RadiationSuit),
+                    Pickup::LightAmplification => Some(PaletteEvent::LightAmplification),
+                    _ => None,
+                };
+                if let Some(powerup) = powerup {
+                    palette_effects.trigger(powerup);
+                }
+            }
+        }
+    }
+
     fn move_force(
         &mut self,
         delta_time: f32,
@@ -265,6 +439,7 @@ impl Player {
         } else {
             (config.height, None)
         };
+        self.grounded = normal.is_some();
         let mut force: Vec3f = self.move_force(
             delta_time,
             normal.is_some(),
@@ -335,7 +510,7 @@ impl<'context> InfallibleSystem<'context> for Player {
         );
         deps.projections.attach(
             camera_entity,
-            Projection {
+            Projection::Perspective {
                 fov: deps.config.fov.into(),
                 aspect_ratio: deps.window.aspect_ratio() * deps.config.aspect_ratio_correction,
                 near: deps.config.near,
@@ -346,10 +521,15 @@ impl<'context> InfallibleSystem<'context> for Player {
 
         let mut player = Player {
             id: player_entity,
+            camera: camera_entity,
+            pose: None,
             velocity: Vec3f::zero(),
             fly: false,
             clip: true,
+            grounded: false,
+            hazard_time: 0.0,
             last_height_diff: 0.0,
+            status: PlayerStatus::default(),
         };
 
         player.reset(deps.transforms, deps.level);
@@ -367,6 +547,17 @@ impl<'context> InfallibleSystem<'context> for Player {
             .get_local_mut(self.id)
             .expect("player has no transform component: update");
 
+        if let Some(pose) = self.pose {
+            // The camera is a child of the player, `camera_height` above it.
+            transform.rot = Quat::from_angle_y(pose.yaw) * Quat::from_angle_x(pose.pitch);
+            transform.disp = pose.position.to_vec()
+                - transform
+                    .rot
+                    .rotate_vector(vec3(0.0, deps.config.camera_height, 0.0));
+            self.velocity = Vec3f::zero();
+            return;
+        }
+
         if deps.input.poll_gesture(&deps.bindings.fly) {
             self.fly = !self.fly;
         }
@@ -394,17 +585,36 @@ impl<'context> InfallibleSystem<'context> for Player {
         transform.disp = head.center.to_vec();
         self.velocity += force * delta_time;
 
+        self.hazard(
+            delta_time,
+            Pnt2f::new(transform.disp.x, transform.disp.z),
+            deps.wad,
+            deps.palette_effects,
+        );
+
+        self.status.attack_down = deps.input.poll_gesture(&deps.bindings.shoot);
         deps.level.poll_triggers(
             transform,
             self.velocity * delta_time,
             if deps.input.poll_gesture(&deps.bindings.push) {
                 Some(PlayerAction::Push)
-            } else if deps.input.poll_gesture(&deps.bindings.shoot) {
+            } else if self.status.attack_down {
                 Some(PlayerAction::Shoot)
             } else {
                 None
             },
         );
+
+        // Roughly where the feet probe finds the floor.
+        let feet = Pnt3f::from_vec(transform.disp)
+            - vec3(0.0, deps.config.radius + deps.config.height, 0.0);
+        self.touch_pickups(
+            feet,
+            deps.config,
+            deps.level,
+            deps.transforms,
+            deps.palette_effects,
+        );
     }
 
     fn teardown(&mut self, deps: Dependencies) {
@@ -412,6 +622,15 @@ impl<'context> InfallibleSystem<'context> for Player {
     }
 }
 
+const HELLSLIME_SECTOR: SectorType = 5;
+const NUKAGE_SECTOR: SectorType = 7;
+const SUPER_HELLSLIME_SECTOR_1: SectorType = 4;
+const SUPER_HELLSLIME_SECTOR_2: SectorType = 16;
+const EXIT_DAMAGE_SECTOR: SectorType = 11;
+
+/// Damaging floors hurt every 32 vanilla tics.
+const HAZARD_PERIOD: f32 = 32.0 / 35.0;
+
 fn clamp<T: PartialOrd>(value: T, (limit_min, limit_max): (T, T)) -> T {
     if value < limit_min {
         limit_min
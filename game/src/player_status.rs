@@ -0,0 +1,200 @@
+use super::pickups::{Pickup, BULLETS, CLIP_AMMO, WEAPON_AMMO};
+
+/// The player's health, armour, weapons, ammo and keys, shown by the status bar. These start out
+/// like a fresh vanilla game, then change when the player is hurt or picks something up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerStatus {
+    pub health: u32,
+    pub armor: u32,
+    /// 0 for none, 1 for green armour which absorbs a third of all damage, 2 for blue (a half).
+    pub armor_class: u32,
+    /// Indexed by vanilla's weapon number: the fist, pistol, shotgun, chaingun, rocket launcher,
+    /// plasma rifle, BFG, chainsaw then super shotgun.
+    pub weapons: [bool; NUM_WEAPONS],
+    /// The index in `ammo` of what the ready weapon fires, `None` for the fist or chainsaw.
+    pub ready_ammo: Option<usize>,
+    /// Indexed like vanilla's `ammotype_t`: bullets, shells, cells then rockets.
+    pub ammo: [u32; NUM_AMMO],
+    pub max_ammo: [u32; NUM_AMMO],
+    /// Whether a backpack already doubled `max_ammo`.
+    pub backpack: bool,
+    /// The blue, yellow and red keycards, then the skull keys in the same order.
+    pub keys: [bool; NUM_KEYS],
+    /// Whether the shoot binding is held, which eventually makes the face rampage.
+    pub attack_down: bool,
+}
+
+impl PlayerStatus {
+    pub fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+
+    /// Takes `damage` like vanilla's `P_DamageMobj`, with armour absorbing part of it.
+    pub fn damage(&mut self, damage: u32) {
+        let mut damage = damage;
+        if self.armor_class > 0 {
+            let mut saved = if self.armor_class == 1 {
+                damage / 3
+            } else {
+                damage / 2
+            };
+            if self.armor <= saved {
+                saved = self.armor;
+                self.armor_class = 0;
+            }
+            self.armor -= saved;
+            damage -= saved;
+        }
+        self.health = self.health.saturating_sub(damage);
+    }
+
+    /// Gives the player `pickup` like vanilla's `P_TouchSpecialThing`. Returns whether it was
+    /// taken, items which would not help (e.g. a medikit at full health) are left where they are.
+    pub fn pick_up(&mut self, pickup: Pickup) -> bool {
+        match pickup {
+            Pickup::Stimpack => self.give_health(10),
+            Pickup::Medikit => self.give_health(25),
+            Pickup::HealthBonus => {
+                self.health = (self.health + 1).min(MAX_SOULSPHERE_HEALTH);
+                true
+            }
+            Pickup::Soulsphere => {
+                self.health = (self.health + 100).min(MAX_SOULSPHERE_HEALTH);
+                true
+            }
+            Pickup::Megasphere => {
+                self.health = MAX_SOULSPHERE_HEALTH;
+                self.give_armor(2);
+                true
+            }
+            Pickup::ArmorBonus => {
+                self.armor = (self.armor + 1).min(MAX_ARMOR);
+                if self.armor_class == 0 {
+                    self.armor_class = 1;
+                }
+                true
+            }
+            Pickup::GreenArmor => self.give_armor(1),
+            Pickup::BlueArmor => self.give_armor(2),
+            Pickup::Key(key) => {
+                self.keys[key] = true;
+                true
+            }
+            Pickup::Ammo { ammo, clips } => self.give_ammo(ammo, clips),
+            Pickup::Backpack => {
+                if !self.backpack {
+                    self.backpack = true;
+                    for max_ammo in &mut self.max_ammo {
+                        *max_ammo *= 2;
+                    }
+                }
+                for ammo in 0..NUM_AMMO {
+                    self.give_ammo(ammo, 1);
+                }
+                true
+            }
+            Pickup::Weapon(weapon) => {
+                let gave_ammo = WEAPON_AMMO[weapon].is_some_and(|ammo| self.give_ammo(ammo, 2));
+                if self.weapons[weapon] {
+                    gave_ammo
+                } else {
+                    // Vanilla switches to new weapons straight away.
+                    self.weapons[weapon] = true;
+                    self.ready_ammo = WEAPON_AMMO[weapon];
+                    true
+                }
+            }
+            Pickup::Invulnerability | Pickup::RadiationSuit | Pickup::LightAmplification => true,
+        }
+    }
+
+    /// Vanilla's `P_GiveBody`.
+    fn give_health(&mut self, amount: u32) -> bool {
+        if self.health >= MAX_HEALTH {
+            return false;
+        }
+        self.health = (self.health + amount).min(MAX_HEALTH);
+        true
+    }
+
+    /// Vanilla's `P_GiveArmor`, where green armour is class 1 and blue armour class 2.
+    fn give_armor(&mut self, armor_class: u32) -> bool {
+        let armor = armor_class * 100;
+        if self.armor >= armor {
+            return false;
+        }
+        self.armor = armor;
+        self.armor_class = armor_class;
+        true
+    }
+
+    /// Vanilla's `P_GiveAmmo`, for `clips` clips of `ammo`.
+    fn give_ammo(&mut self, ammo: usize, clips: u32) -> bool {
+        if self.ammo[ammo] == self.max_ammo[ammo] {
+            return false;
+        }
+        self.ammo[ammo] = (self.ammo[ammo] + clips * CLIP_AMMO[ammo]).min(self.max_ammo[ammo]);
+        true
+    }
+}
+
+impl Default for PlayerStatus {
+    /// What `G_PlayerReborn` gives a player: a fist, a pistol and 50 bullets.
+    fn default() -> Self {
+        let mut weapons = [false; NUM_WEAPONS];
+        // The fist and the pistol.
+        weapons[0] = true;
+        weapons[1] = true;
+        let mut ammo = [0; NUM_AMMO];
+        ammo[BULLETS] = 50;
+        PlayerStatus {
+            health: 100,
+            armor: 0,
+            armor_class: 0,
+            weapons,
+            ready_ammo: Some(BULLETS),
+            ammo,
+            max_ammo: [200, 50, 300, 50],
+            backpack: false,
+            keys: [false; NUM_KEYS],
+            attack_down: false,
+        }
+    }
+}
+
+pub const NUM_WEAPONS: usize = 9;
+pub const NUM_AMMO: usize = 4;
+pub const NUM_KEYS: usize = 6;
+
+const MAX_HEALTH: u32 = 100;
+const MAX_SOULSPHERE_HEALTH: u32 = 200;
+const MAX_ARMOR: u32 = 200;
+
+#[cfg(test)]
+mod test {
+    use super::{PlayerStatus, BULLETS};
+    use crate::pickups::{Pickup, SHELLS};
+
+    #[test]
+    fn test_pick_up() {
+        let mut status = PlayerStatus::default();
+        assert!(!status.pick_up(Pickup::Medikit));
+        assert!(status.pick_up(Pickup::HealthBonus));
+        assert_eq!(status.health, 101);
+
+        assert!(status.pick_up(Pickup::GreenArmor));
+        assert!(!status.pick_up(Pickup::GreenArmor));
+        status.damage(30);
+        assert_eq!((status.health, status.armor), (81, 90));
+
+        assert!(status.pick_up(Pickup::Weapon(2)));
+        assert_eq!(status.ready_ammo, Some(SHELLS));
+        assert_eq!(status.ammo[SHELLS], 8);
+        status.ammo[SHELLS] = status.max_ammo[SHELLS];
+        assert!(!status.pick_up(Pickup::Weapon(2)));
+
+        assert!(status.pick_up(Pickup::Backpack));
+        assert_eq!(status.max_ammo[BULLETS], 400);
+        assert_eq!(status.ammo[BULLETS], 60);
+    }
+}
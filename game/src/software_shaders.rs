@@ -0,0 +1,452 @@
+//! CPU versions of the GLSL shaders in `assets/shaders`, used with the software backend. Keep
+//! these in sync with their GLSL counterparts.
+
+use super::vertex::{SkyVertex, SpriteVertex, StaticVertex, StatusBarVertex};
+use engine::{SoftwareSampler, SoftwareShader, SoftwareUniforms};
+use math::prelude::*;
+use math::{vec2, vec3, vec4, Mat4, Vec2f, Vec3f, Vec4f};
+use std::f32::consts::PI;
+
+const ANIM_FPS: f32 = 8.0 / 35.0;
+const LIGHT_SCALE: f32 = 2.0;
+const NUM_LIGHT_COLORMAPS: f32 = 32.0;
+const NUM_PALETTE_ROWS: f32 = 33.0;
+
+/// The `static` shader, for walls and flats.
+pub struct StaticShader;
+
+/// The `sprite` shader, for things.
+pub struct SpriteShader;
+
+/// The `sky` shader.
+pub struct SkyShader;
+
+/// The `debug` shader, for walls and flats in debug views.
+pub struct DebugShader;
+
+/// The `status_bar` shader.
+pub struct StatusBarShader;
+
+pub struct AtlasUniforms<'a> {
+    modelview: Mat4,
+    transform: Mat4,
+    atlas_size: Vec2f,
+    time: f32,
+    lights: &'a [u8],
+    atlas: SoftwareSampler<'a>,
+    palette: SoftwareSampler<'a>,
+    colormap_mode: f32,
+}
+
+impl<'a> AtlasUniforms<'a> {
+    fn new(uniforms: &SoftwareUniforms<'a>) -> Option<Self> {
+        let modelview = uniforms.mat4("u_modelview")?;
+        Some(Self {
+            modelview,
+            transform: uniforms.mat4("u_projection")? * modelview,
+            atlas_size: uniforms.vec2f("u_atlas_size")?,
+            time: uniforms.float("u_time")?,
+            lights: uniforms.buffer_texture_u8("u_lights")?,
+            atlas: uniforms.texture_2d("u_atlas")?,
+            palette: uniforms.texture_2d("u_palette")?,
+            colormap_mode: uniforms.float("u_colormap_mode")?,
+        })
+    }
+
+    fn light(&self, index: u16) -> f32 {
+        self.lights
+            .get(usize::from(index))
+            .map_or(0.0, |&light| f32::from(light) / 255.0)
+    }
+
+    fn animated_atlas_uv(
+        &self,
+        atlas_uv: Vec2f,
+        tile_size: Vec2f,
+        num_frames: u8,
+        row_height: f32,
+    ) -> Vec2f {
+        if num_frames == 1 {
+            return atlas_uv;
+        }
+        let frame_index = glsl_mod(self.time / ANIM_FPS, f32::from(num_frames)).floor();
+
+        let mut atlas_u = atlas_uv.x + frame_index * tile_size.x;
+        let n_rows_down = ((atlas_u + tile_size.x) / self.atlas_size.x).ceil() - 1.0;
+        atlas_u += glsl_mod(self.atlas_size.x - atlas_uv.x, tile_size.x) * n_rows_down;
+
+        let atlas_v = atlas_uv.y + n_rows_down * row_height;
+        vec2(atlas_u, atlas_v)
+    }
+
+    /// The shared fragment stage of the `static` and `sprite` shaders; `light` maps the distance
+    /// term to the final light level.
+    fn shade(
+        &self,
+        varyings: &AtlasVaryings,
+        flat: &AtlasFlat,
+        dist_scale: f32,
+        light: impl FnOnce(f32) -> f32,
+    ) -> Option<Vec3f> {
+        let [dist, tile_u, tile_v] = *varyings;
+        let uv = vec2(
+            glsl_mod(tile_u, flat.tile_size.x),
+            glsl_mod(tile_v, flat.tile_size.y),
+        ) + flat.atlas_uv;
+        let palette_index = self
+            .atlas
+            .sample_layer(uv.div_element_wise(self.atlas_size), flat.atlas_page);
+        if palette_index.y > 0.5 {
+            // Transparent pixel.
+            return None;
+        }
+        let dist_term = (1.0 - dist_scale / (dist + dist_scale)).min(1.0);
+        let light = light(dist_term);
+        Some(
+            self.palette
+                .sample(vec2(palette_index.x, colormap_v(self.colormap_mode, light)))
+                .truncate(),
+        )
+    }
+}
+
+/// `v_dist` followed by `v_tile_uv`.
+type AtlasVaryings = [f32; 3];
+
+#[derive(Copy, Clone)]
+pub struct AtlasFlat {
+    atlas_uv: Vec2f,
+    atlas_page: usize,
+    tile_size: Vec2f,
+    light: f32,
+}
+
+impl SoftwareShader for StaticShader {
+    type Vertex = StaticVertex;
+    type Uniforms<'a> = AtlasUniforms<'a>;
+    type Varyings = AtlasVaryings;
+    type Flat = AtlasFlat;
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<AtlasUniforms<'a>> {
+        AtlasUniforms::new(uniforms)
+    }
+
+    fn vertex(
+        &self,
+        uniforms: &AtlasUniforms,
+        vertex: &StaticVertex,
+    ) -> (Vec4f, AtlasVaryings, AtlasFlat) {
+        let tile_size = Vec2f::from(vertex.a_tile_size);
+        let [x, y, z] = vertex.a_pos;
+        let projected_pos = uniforms.transform * vec4(x, y, z, 1.0);
+        (
+            projected_pos,
+            [
+                projected_pos.w,
+                vertex.a_tile_uv[0] + uniforms.time * vertex.a_scroll_rate,
+                vertex.a_tile_uv[1],
+            ],
+            AtlasFlat {
+                atlas_uv: uniforms.animated_atlas_uv(
+                    vertex.a_atlas_uv.into(),
+                    tile_size,
+                    vertex.a_num_frames,
+                    vertex.a_row_height,
+                ),
+                atlas_page: usize::from(vertex.a_atlas_page),
+                tile_size,
+                light: uniforms.light(vertex.a_light),
+            },
+        )
+    }
+
+    fn fragment(
+        &self,
+        uniforms: &AtlasUniforms,
+        varyings: &AtlasVaryings,
+        flat: &AtlasFlat,
+    ) -> Option<Vec3f> {
+        uniforms.shade(varyings, flat, 0.9, |dist_term| {
+            flat.light * LIGHT_SCALE - dist_term
+        })
+    }
+}
+
+impl SoftwareShader for SpriteShader {
+    type Vertex = SpriteVertex;
+    type Uniforms<'a> = AtlasUniforms<'a>;
+    type Varyings = AtlasVaryings;
+    type Flat = AtlasFlat;
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<AtlasUniforms<'a>> {
+        AtlasUniforms::new(uniforms)
+    }
+
+    fn vertex(
+        &self,
+        uniforms: &AtlasUniforms,
+        vertex: &SpriteVertex,
+    ) -> (Vec4f, AtlasVaryings, AtlasFlat) {
+        let tile_size = Vec2f::from(vertex.a_tile_size);
+        let flat = AtlasFlat {
+            atlas_uv: uniforms.animated_atlas_uv(
+                vertex.a_atlas_uv.into(),
+                tile_size,
+                vertex.a_num_frames,
+                tile_size.y,
+            ),
+            atlas_page: usize::from(vertex.a_atlas_page),
+            tile_size,
+            light: if vertex.a_fullbright != 0 {
+                1.0
+            } else {
+                uniforms.light(vertex.a_light)
+            },
+        };
+        if vertex.a_rotation != 0 && vertex.a_rotation != view_rotation(uniforms, vertex) {
+            // Collapse the quads of all other rotations to a single point outside the clip volume.
+            return (vec4(2.0, 2.0, 2.0, 1.0), [0.0; 3], flat);
+        }
+
+        let modelview = &uniforms.modelview;
+        let right = vec4(modelview.x.x, modelview.y.x, modelview.z.x, 0.0);
+        let [x, y, z] = vertex.a_pos;
+        let pos = vec4(x, y, z, 1.0) + right * vertex.a_local_x;
+        let projected_pos = uniforms.transform * pos;
+        (
+            projected_pos,
+            [projected_pos.w, vertex.a_tile_uv[0], vertex.a_tile_uv[1]],
+            flat,
+        )
+    }
+
+    fn fragment(
+        &self,
+        uniforms: &AtlasUniforms,
+        varyings: &AtlasVaryings,
+        flat: &AtlasFlat,
+    ) -> Option<Vec3f> {
+        uniforms.shade(varyings, flat, 1.0, |dist_term| {
+            flat.light.min(flat.light * LIGHT_SCALE - dist_term)
+        })
+    }
+}
+
+/// Picks one of the eight rotations (1 to 8), same as `view_rotation` in `sprite.vert`.
+fn view_rotation(uniforms: &AtlasUniforms, vertex: &SpriteVertex) -> u8 {
+    let modelview = &uniforms.modelview;
+    let translation = modelview.w.truncate();
+    let camera_x = -(modelview.x.truncate().dot(translation));
+    let camera_z = -(modelview.z.truncate().dot(translation));
+    let to_thing = vec2(vertex.a_pos[0] - camera_x, vertex.a_pos[2] - camera_z);
+    let relative = vertex.a_yaw - to_thing.y.atan2(to_thing.x) + PI * (9.0 / 8.0);
+    (glsl_mod(relative, 2.0 * PI) / (PI / 4.0)).floor() as u8 % 8 + 1
+}
+
+pub struct SkyUniforms<'a> {
+    transform: Mat4,
+    tiled_band_size: f32,
+    texture: SoftwareSampler<'a>,
+    palette: SoftwareSampler<'a>,
+    colormap_mode: f32,
+}
+
+impl SoftwareShader for SkyShader {
+    type Vertex = SkyVertex;
+    type Uniforms<'a> = SkyUniforms<'a>;
+    /// `v_p`, without the unused `z`.
+    type Varyings = [f32; 3];
+    /// `v_r`.
+    type Flat = Vec2f;
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<SkyUniforms<'a>> {
+        Some(SkyUniforms {
+            transform: uniforms.mat4("u_projection")? * uniforms.mat4("u_modelview")?,
+            tiled_band_size: uniforms.float("u_tiled_band_size")?,
+            texture: uniforms.texture_2d("u_texture")?,
+            palette: uniforms.texture_2d("u_palette")?,
+            colormap_mode: uniforms.float("u_colormap_mode")?,
+        })
+    }
+
+    fn vertex(&self, uniforms: &SkyUniforms, vertex: &SkyVertex) -> (Vec4f, [f32; 3], Vec2f) {
+        let forward = uniforms.transform.z;
+        let [x, y, z] = vertex.a_pos;
+        let projected_pos = uniforms.transform * vec4(x, y, z, 1.0);
+        (
+            projected_pos,
+            [projected_pos.x, projected_pos.y, projected_pos.w],
+            vec2(forward.x.atan2(forward.z), forward.y / forward.w),
+        )
+    }
+
+    fn fragment(&self, uniforms: &SkyUniforms, varyings: &[f32; 3], r: &Vec2f) -> Option<Vec3f> {
+        let [x, y, w] = *varyings;
+        let band = uniforms.tiled_band_size;
+        let u = x / w - 4.0 * r.x / PI;
+        let mut v = -y / w + 1.0 + r.y;
+        if v < 0.0 {
+            v = (glsl_mod(-v + band, band * 2.0) - band).abs();
+        } else if v >= 2.0 {
+            v = (glsl_mod(v - 2.0 + band, band * 2.0) - band).abs();
+        } else if v >= 1.0 {
+            v = 1.0 - v;
+        }
+        let palette_index = uniforms.texture.sample(vec2(u, v)).x;
+        // The sky is unlit, so it only changes with the inverse colormap.
+        let colormap_v = if uniforms.colormap_mode > 1.5 {
+            colormap_v(uniforms.colormap_mode, 0.0)
+        } else {
+            0.0
+        };
+        Some(
+            uniforms
+                .palette
+                .sample(vec2(palette_index, colormap_v))
+                .truncate(),
+        )
+    }
+}
+
+pub struct DebugUniforms<'a> {
+    transform: Mat4,
+    lights: &'a [u8],
+    view: f32,
+}
+
+#[derive(Copy, Clone)]
+pub struct DebugFlat {
+    light: f32,
+    leaf: u16,
+    object: u16,
+    trigger: bool,
+}
+
+impl SoftwareShader for DebugShader {
+    type Vertex = StaticVertex;
+    type Uniforms<'a> = DebugUniforms<'a>;
+    type Varyings = [f32; 0];
+    type Flat = DebugFlat;
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<DebugUniforms<'a>> {
+        Some(DebugUniforms {
+            transform: uniforms.mat4("u_projection")? * uniforms.mat4("u_modelview")?,
+            lights: uniforms.buffer_texture_u8("u_lights")?,
+            view: uniforms.float("u_debug_view")?,
+        })
+    }
+
+    fn vertex(
+        &self,
+        uniforms: &DebugUniforms,
+        vertex: &StaticVertex,
+    ) -> (Vec4f, [f32; 0], DebugFlat) {
+        let [x, y, z] = vertex.a_pos;
+        (
+            uniforms.transform * vec4(x, y, z, 1.0),
+            [],
+            DebugFlat {
+                light: uniforms
+                    .lights
+                    .get(usize::from(vertex.a_light))
+                    .map_or(0.0, |&light| f32::from(light) / 255.0),
+                leaf: vertex.a_debug_leaf,
+                object: vertex.a_debug_object,
+                trigger: vertex.a_debug_trigger != 0,
+            },
+        )
+    }
+
+    fn fragment(
+        &self,
+        uniforms: &DebugUniforms,
+        _varyings: &[f32; 0],
+        flat: &DebugFlat,
+    ) -> Option<Vec3f> {
+        let light = (flat.light * LIGHT_SCALE).clamp(0.0, 1.0);
+        Some(if uniforms.view < 1.5 {
+            id_color(flat.leaf)
+        } else if uniforms.view < 2.5 {
+            Vec3f::from_value(light)
+        } else if flat.trigger {
+            vec3(1.0, 1.0, 0.0)
+        } else if flat.object != 0 {
+            id_color(flat.object)
+        } else {
+            Vec3f::from_value(0.25 + 0.25 * light)
+        })
+    }
+}
+
+pub struct StatusBarUniforms<'a> {
+    transform: Mat4,
+    texture: SoftwareSampler<'a>,
+    palette: SoftwareSampler<'a>,
+}
+
+impl SoftwareShader for StatusBarShader {
+    type Vertex = StatusBarVertex;
+    type Uniforms<'a> = StatusBarUniforms<'a>;
+    /// `v_uv`.
+    type Varyings = [f32; 2];
+    type Flat = ();
+
+    fn uniforms<'a>(&self, uniforms: &SoftwareUniforms<'a>) -> Option<StatusBarUniforms<'a>> {
+        Some(StatusBarUniforms {
+            transform: uniforms.mat4("u_transform")?,
+            texture: uniforms.texture_2d("u_texture")?,
+            palette: uniforms.texture_2d("u_palette")?,
+        })
+    }
+
+    fn vertex(
+        &self,
+        uniforms: &StatusBarUniforms,
+        vertex: &StatusBarVertex,
+    ) -> (Vec4f, [f32; 2], ()) {
+        let [x, y, z] = vertex.a_pos;
+        (uniforms.transform * vec4(x, y, z, 1.0), vertex.a_uv, ())
+    }
+
+    fn fragment(&self, uniforms: &StatusBarUniforms, uv: &[f32; 2], _flat: &()) -> Option<Vec3f> {
+        let palette_index = uniforms.texture.sample(Vec2f::from(*uv));
+        if palette_index.y > 0.5 {
+            // Transparent pixel.
+            return None;
+        }
+        Some(
+            uniforms
+                .palette
+                .sample(vec2(palette_index.x, 0.0))
+                .truncate(),
+        )
+    }
+}
+
+/// Same as `id_color` in `debug.frag`.
+fn id_color(id: u16) -> Vec3f {
+    let hue = (f32::from(id) * 0.618_034).fract();
+    let channel =
+        |offset: f32| ((glsl_mod(hue * 6.0 + offset, 6.0) - 3.0).abs() - 1.0).clamp(0.0, 1.0);
+    let rgb = vec3(channel(0.0), channel(4.0), channel(2.0));
+    (Vec3f::from_value(1.0).lerp(rgb, 0.7)) * 0.9
+}
+
+/// GLSL's `mod`, which (unlike `%`) has the sign of `y`.
+/// The `u_palette` row for a light level, following `u_colormap_mode`.
+fn colormap_v(colormap_mode: f32, light: f32) -> f32 {
+    let row = if colormap_mode > 1.5 {
+        NUM_LIGHT_COLORMAPS
+    } else if colormap_mode > 0.5 {
+        0.0
+    } else {
+        ((1.0 - light) * NUM_LIGHT_COLORMAPS)
+            .floor()
+            .clamp(0.0, NUM_LIGHT_COLORMAPS - 1.0)
+    };
+    (row + 0.5) / NUM_PALETTE_ROWS
+}
+
+fn glsl_mod(x: f32, y: f32) -> f32 {
+    x - y * (x / y).floor()
+}
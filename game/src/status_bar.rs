@@ -0,0 +1,678 @@
+use super::game_shaders::GameShaders;
+use super::palette_effects::PaletteEffects;
+use super::player::Player;
+use super::player_status::{PlayerStatus, NUM_AMMO, NUM_WEAPONS};
+use super::vertex::StatusBarVertex;
+use super::wad_system::{read_patch, WadSystem};
+use engine::{
+    ClientFormat, DependenciesFrom, Entities, EntityId, Error, MagnifySamplerFilter, Mat4UniformId,
+    Materials, Meshes, MinifySamplerFilter, RenderPipeline, Result, SamplerBehavior,
+    SamplerWrapFunction, Shaders, System, Texture2dId, Tick, Uniforms, Window,
+};
+use log::{info, warn};
+use math::prelude::*;
+use math::{Mat4, Vec2};
+use wad::{Archive, Image};
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    tick: &'context Tick,
+    window: &'context Window,
+    entities: &'context mut Entities,
+    shaders: &'context mut Shaders,
+    uniforms: &'context mut Uniforms,
+    meshes: &'context mut Meshes,
+    materials: &'context mut Materials,
+    render: &'context mut RenderPipeline,
+
+    wad: &'context WadSystem,
+    game_shaders: &'context GameShaders,
+    palette_effects: &'context PaletteEffects,
+    player: &'context Player,
+}
+
+/// Vanilla's status bar: `STBAR` with the player's ammo, health, weapons, face, armour and keys
+/// drawn over it, at the bottom of a 4:3 screen fitted to the window.
+///
+/// The bar is composed on the CPU like vanilla does and uploaded as palette indices, so it flashes
+/// with the palette like the 3D view. Disabled if the WAD has no `STBAR` (e.g. Heretic).
+pub struct StatusBar {
+    root: EntityId,
+    bar: Option<Bar>,
+}
+
+struct Bar {
+    patches: Patches,
+    face: Face,
+    /// What the texture was last composed from, to only redraw the bar when it changes.
+    composed: Option<(PlayerStatus, usize)>,
+    texture: Texture2dId,
+    transform: Mat4UniformId,
+}
+
+impl Bar {
+    fn new(patches: Patches, deps: &mut Dependencies, parent: EntityId) -> Result<Self> {
+        let texture = deps.uniforms.add_texture_2d(
+            deps.window,
+            deps.entities,
+            parent,
+            "status_bar_texture",
+            patches.bar.pixels(),
+            patches.bar.size(),
+            ClientFormat::U8U8,
+            Some(SamplerBehavior {
+                wrap_function: (
+                    SamplerWrapFunction::Clamp,
+                    SamplerWrapFunction::Clamp,
+                    SamplerWrapFunction::Clamp,
+                ),
+                minify_filter: MinifySamplerFilter::Nearest,
+                magnify_filter: MagnifySamplerFilter::Nearest,
+                ..SamplerBehavior::default()
+            }),
+        )?;
+        let transform =
+            deps.uniforms
+                .add_mat4(deps.entities, parent, "status_bar_transform", Mat4::one())?;
+        let mesh = deps
+            .meshes
+            .add(deps.window, deps.entities, parent, "status_bar_mesh")
+            .immutable(&QUAD)?
+            .build_unindexed()?;
+        let shader = deps.shaders.add(
+            deps.window,
+            deps.entities,
+            parent,
+            "status_bar_shader",
+            "status_bar",
+        )?;
+        let material = deps
+            .materials
+            .add(deps.entities, parent, shader, "status_bar_material")?
+            .add_uniform("u_transform", transform)
+            .add_uniform("u_palette", deps.game_shaders.palette())
+            .add_uniform("u_texture", texture)
+            .id();
+        deps.render.attach_model(parent, mesh, material);
+        Ok(Bar {
+            patches,
+            face: Face::new(),
+            composed: None,
+            texture,
+            transform,
+        })
+    }
+
+    /// Redraws the bar for `status`, like vanilla's `ST_drawWidgets`.
+    fn compose(&self, status: &PlayerStatus) -> Image {
+        let patches = &self.patches;
+        let mut image = patches.bar.clone();
+        let ready_ammo = status.ready_ammo.map(|ammo| status.ammo[ammo]);
+        if let Some(ammo) = ready_ammo {
+            draw_number(&mut image, &patches.big_digits, ammo, AMMO_X, BIG_NUMBERS_Y);
+        }
+        draw_number(
+            &mut image,
+            &patches.big_digits,
+            status.health,
+            HEALTH_X,
+            BIG_NUMBERS_Y,
+        );
+        draw_patch(&mut image, &patches.percent, HEALTH_X, BIG_NUMBERS_Y);
+        draw_number(
+            &mut image,
+            &patches.big_digits,
+            status.armor,
+            ARMOR_X,
+            BIG_NUMBERS_Y,
+        );
+        draw_patch(&mut image, &patches.percent, ARMOR_X, BIG_NUMBERS_Y);
+
+        draw_patch(&mut image, &patches.arms, ARMS_BACKGROUND_X, 0);
+        for slot in 0..NUM_ARMS_SLOTS {
+            // Slots are numbered from 2, for the pistol.
+            let digits = if status.weapons[slot + 1] {
+                &patches.yellow_digits
+            } else {
+                &patches.grey_digits
+            };
+            draw_patch(
+                &mut image,
+                &digits[slot + 2],
+                ARMS_X + (slot % 3) as isize * ARMS_X_SPACE,
+                ARMS_Y + (slot / 3) as isize * ARMS_Y_SPACE,
+            );
+        }
+
+        draw_patch(&mut image, &patches.faces[self.face.index], FACE_X, 0);
+
+        // A skull key is shown instead of the keycard of the same colour.
+        for (color, &y) in KEYS_Y.iter().enumerate() {
+            let key = if status.keys[color + 3] {
+                Some(color + 3)
+            } else if status.keys[color] {
+                Some(color)
+            } else {
+                None
+            };
+            if let Some(key) = key {
+                draw_patch(&mut image, &patches.keys[key], KEYS_X, y);
+            }
+        }
+
+        let counts = status.ammo.iter().zip(&status.max_ammo);
+        for ((&ammo, &max_ammo), &y) in counts.zip(&SMALL_AMMO_Y) {
+            draw_number(&mut image, &patches.yellow_digits, ammo, SMALL_AMMO_X, y);
+            draw_number(
+                &mut image,
+                &patches.yellow_digits,
+                max_ammo,
+                SMALL_MAX_AMMO_X,
+                y,
+            );
+        }
+        image
+    }
+}
+
+/// The patches the bar is drawn with; any but `STBAR` itself may be missing.
+struct Patches {
+    bar: Image,
+    arms: Option<Image>,
+    percent: Option<Image>,
+    big_digits: Vec<Option<Image>>,
+    yellow_digits: Vec<Option<Image>>,
+    grey_digits: Vec<Option<Image>>,
+    keys: Vec<Option<Image>>,
+    faces: Vec<Option<Image>>,
+}
+
+impl Patches {
+    /// Loads the patches, with the same names as vanilla's `ST_loadGraphics`.
+    fn load(archive: &Archive) -> Option<Self> {
+        let bar = load_patch(archive, "STBAR")?;
+        let digits = |prefix: &str| -> Vec<Option<Image>> {
+            (0..10)
+                .map(|digit| load_patch(archive, &format!("{}{}", prefix, digit)))
+                .collect()
+        };
+        let mut faces = Vec::with_capacity(NUM_FACES);
+        for pain in 0..NUM_PAIN_FACES {
+            for straight in 0..NUM_STRAIGHT_FACES {
+                faces.push(load_patch(archive, &format!("STFST{}{}", pain, straight)));
+            }
+            faces.push(load_patch(archive, &format!("STFTR{}0", pain)));
+            faces.push(load_patch(archive, &format!("STFTL{}0", pain)));
+            faces.push(load_patch(archive, &format!("STFOUCH{}", pain)));
+            faces.push(load_patch(archive, &format!("STFEVL{}", pain)));
+            faces.push(load_patch(archive, &format!("STFKILL{}", pain)));
+        }
+        faces.push(load_patch(archive, "STFGOD0"));
+        faces.push(load_patch(archive, "STFDEAD0"));
+        Some(Patches {
+            bar,
+            arms: load_patch(archive, "STARMS"),
+            percent: load_patch(archive, "STTPRCNT"),
+            big_digits: digits("STTNUM"),
+            yellow_digits: digits("STYSNUM"),
+            // Grey numbers only exist for the weapon slots.
+            grey_digits: (0..10)
+                .map(|digit| {
+                    if (2..2 + NUM_ARMS_SLOTS).contains(&digit) {
+                        load_patch(archive, &format!("STGNUM{}", digit))
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            keys: (0..6)
+                .map(|key| load_patch(archive, &format!("STKEYS{}", key)))
+                .collect(),
+            faces,
+        })
+    }
+}
+
+/// Vanilla's face state machine from `ST_updateFaceWidget`, ticked at 35Hz.
+struct Face {
+    index: usize,
+    count: u32,
+    priority: u32,
+    old_health: u32,
+    old_weapons: [bool; NUM_WEAPONS],
+    /// Vanilla's `lastattackdown`, `None` while the shoot binding is up.
+    attack_down_count: Option<u32>,
+    random: u32,
+    pending_tics: f32,
+}
+
+impl Face {
+    fn new() -> Self {
+        Face {
+            index: 0,
+            count: 0,
+            priority: 0,
+            old_health: 100,
+            old_weapons: PlayerStatus::default().weapons,
+            attack_down_count: None,
+            random: 1,
+            pending_tics: 0.0,
+        }
+    }
+
+    fn update(&mut self, delta_time: f32, status: &PlayerStatus, invulnerable: bool) {
+        self.pending_tics += delta_time * TICS_PER_SECOND;
+        while self.pending_tics >= 1.0 {
+            self.pending_tics -= 1.0;
+            self.tick(status, invulnerable);
+        }
+    }
+
+    fn tick(&mut self, status: &PlayerStatus, invulnerable: bool) {
+        let pain = pain_offset(status.health);
+
+        if self.priority < 10 && status.is_dead() {
+            self.priority = 9;
+            self.index = DEAD_FACE;
+            self.count = 1;
+        }
+
+        let new_weapon = status
+            .weapons
+            .iter()
+            .zip(&self.old_weapons)
+            .any(|(&owned, &old)| owned && !old);
+        if self.priority < 9 && new_weapon {
+            self.priority = 8;
+            self.index = pain + EVIL_GRIN_OFFSET;
+            self.count = EVIL_GRIN_COUNT;
+        }
+
+        // No one attacks the player yet, only damaging floors hurt: vanilla shows health lost that
+        // way with the ouch or rampage faces.
+        if self.priority < 7 && status.health < self.old_health {
+            self.index = pain
+                + if self.old_health - status.health > MUCH_PAIN {
+                    self.priority = 7;
+                    OUCH_OFFSET
+                } else {
+                    self.priority = 6;
+                    RAMPAGE_OFFSET
+                };
+            self.count = TURN_COUNT;
+        }
+
+        if self.priority < 6 {
+            self.attack_down_count = if !status.attack_down {
+                None
+            } else {
+                match self.attack_down_count {
+                    None => Some(RAMPAGE_DELAY),
+                    Some(count) if count > 1 => Some(count - 1),
+                    Some(_) => {
+                        self.priority = 5;
+                        self.index = pain + RAMPAGE_OFFSET;
+                        self.count = 1;
+                        Some(1)
+                    }
+                }
+            };
+        }
+
+        if self.priority < 5 && invulnerable {
+            self.priority = 4;
+            self.index = GOD_FACE;
+            self.count = 1;
+        }
+
+        // Look left, right or straight ahead at random once nothing else is showing.
+        if self.count == 0 {
+            self.index = pain + self.next_random() as usize % NUM_STRAIGHT_FACES;
+            self.count = STRAIGHT_FACE_COUNT;
+            self.priority = 0;
+        }
+        self.count -= 1;
+        self.old_health = status.health;
+        self.old_weapons = status.weapons;
+    }
+
+    /// A xorshift generator, standing in for vanilla's `M_Random`.
+    fn next_random(&mut self) -> u32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random
+    }
+}
+
+/// The first face for the player's health, like vanilla's `ST_calcPainOffset`.
+fn pain_offset(health: u32) -> usize {
+    let health = health.min(100) as usize;
+    FACE_STRIDE * (((100 - health) * NUM_PAIN_FACES) / 101)
+}
+
+impl<'context> System<'context> for StatusBar {
+    type Dependencies = Dependencies<'context>;
+    type Error = Error;
+
+    fn debug_name() -> &'static str {
+        "status_bar"
+    }
+
+    fn create(mut deps: Dependencies) -> Result<Self> {
+        let root = deps.entities.add_root("status_bar");
+        let bar = match Patches::load(&deps.wad.archive) {
+            Some(patches) => Some(Bar::new(patches, &mut deps, root)?),
+            None => {
+                info!("WAD has no STBAR, not showing a status bar.");
+                None
+            }
+        };
+        Ok(StatusBar { root, bar })
+    }
+
+    fn update(&mut self, deps: Dependencies) -> Result<()> {
+        let bar = match self.bar {
+            Some(ref mut bar) => bar,
+            None => return Ok(()),
+        };
+        let status = deps.player.status();
+        bar.face.update(
+            deps.tick.timestep(),
+            status,
+            deps.palette_effects.is_invulnerable(),
+        );
+
+        let composed = Some((status.clone(), bar.face.index));
+        if composed != bar.composed {
+            let image = bar.compose(status);
+            deps.uniforms
+                .get_texture_2d_mut(bar.texture)
+                .expect("missing status bar texture")
+                .replace_pixels(
+                    deps.window,
+                    image.pixels(),
+                    image.size(),
+                    ClientFormat::U8U8,
+                    None,
+                )?;
+            bar.composed = composed;
+        }
+
+        *deps
+            .uniforms
+            .get_mat4_mut(bar.transform)
+            .expect("missing status bar transform") = bar_transform(deps.window.aspect_ratio());
+        Ok(())
+    }
+
+    fn teardown(&mut self, deps: Dependencies) -> Result<()> {
+        self.bar = None;
+        deps.entities.remove(self.root);
+        Ok(())
+    }
+}
+
+/// Maps the unit square of `QUAD` to the bottom of the largest 4:3 screen that fits the window,
+/// which is how big vanilla's 320x200 frame would be.
+fn bar_transform(aspect_ratio: f32) -> Mat4 {
+    let half_width = (SCREEN_ASPECT_RATIO / aspect_ratio).min(1.0);
+    let height = 2.0 * BAR_HEIGHT / SCREEN_HEIGHT * (aspect_ratio / SCREEN_ASPECT_RATIO).min(1.0);
+    #[rustfmt::skip]
+    let transform = Mat4::new(
+        2.0 * half_width, 0.0, 0.0, 0.0,
+        0.0, height, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -half_width, -1.0, 0.0, 1.0,
+    );
+    transform
+}
+
+/// Draws `patch` at a point in status bar coordinates, honouring its offsets like `V_DrawPatch`.
+fn draw_patch(image: &mut Image, patch: &Option<Image>, x: isize, y: isize) {
+    if let Some(patch) = patch {
+        image.blit(
+            patch,
+            Vec2::new(x - patch.x_offset(), y - patch.y_offset()),
+            false,
+        );
+    }
+}
+
+/// Draws a number with its last digit ending at `right`, like vanilla's `STlib_drawNum`.
+fn draw_number(image: &mut Image, digits: &[Option<Image>], number: u32, right: isize, y: isize) {
+    let width = match digits[0] {
+        Some(ref zero) => zero.width() as isize,
+        None => return,
+    };
+    let mut number = number.min(999);
+    let mut x = right;
+    loop {
+        x -= width;
+        draw_patch(image, &digits[(number % 10) as usize], x, y);
+        number /= 10;
+        if number == 0 {
+            break;
+        }
+    }
+}
+
+fn load_patch(archive: &Archive, name: &str) -> Option<Image> {
+    match read_patch(archive, name) {
+        Ok(Some(image)) => Some(image),
+        Ok(None) => {
+            warn!("Missing status bar patch {}.", name);
+            None
+        }
+        Err(error) => {
+            warn!("Bad status bar patch {}: {}", name, error);
+            None
+        }
+    }
+}
+
+/// The bar as two triangles over the unit square, in front of everything including the automap.
+const QUAD: [StatusBarVertex; 6] = [
+    StatusBarVertex {
+        a_pos: [0.0, 0.0, BAR_DEPTH],
+        a_uv: [0.0, 1.0],
+    },
+    StatusBarVertex {
+        a_pos: [1.0, 0.0, BAR_DEPTH],
+        a_uv: [1.0, 1.0],
+    },
+    StatusBarVertex {
+        a_pos: [1.0, 1.0, BAR_DEPTH],
+        a_uv: [1.0, 0.0],
+    },
+    StatusBarVertex {
+        a_pos: [0.0, 0.0, BAR_DEPTH],
+        a_uv: [0.0, 1.0],
+    },
+    StatusBarVertex {
+        a_pos: [1.0, 1.0, BAR_DEPTH],
+        a_uv: [1.0, 0.0],
+    },
+    StatusBarVertex {
+        a_pos: [0.0, 1.0, BAR_DEPTH],
+        a_uv: [0.0, 0.0],
+    },
+];
+const BAR_DEPTH: f32 = -0.999;
+
+const SCREEN_HEIGHT: f32 = 200.0;
+const SCREEN_ASPECT_RATIO: f32 = 4.0 / 3.0;
+const BAR_HEIGHT: f32 = 32.0;
+
+// Positions from vanilla's `st_stuff.c`, relative to the top of the bar.
+const BIG_NUMBERS_Y: isize = 3;
+const AMMO_X: isize = 44;
+const HEALTH_X: isize = 90;
+const ARMOR_X: isize = 221;
+const ARMS_BACKGROUND_X: isize = 104;
+const ARMS_X: isize = 111;
+const ARMS_Y: isize = 4;
+const ARMS_X_SPACE: isize = 12;
+const ARMS_Y_SPACE: isize = 10;
+const NUM_ARMS_SLOTS: usize = 6;
+const FACE_X: isize = 143;
+const KEYS_X: isize = 239;
+const KEYS_Y: [isize; 3] = [3, 13, 23];
+const SMALL_AMMO_X: isize = 288;
+const SMALL_MAX_AMMO_X: isize = 314;
+/// Indexed like `PlayerStatus::ammo`, with cells at the bottom.
+const SMALL_AMMO_Y: [isize; NUM_AMMO] = [5, 11, 23, 17];
+
+const TICS_PER_SECOND: f32 = 35.0;
+
+const NUM_PAIN_FACES: usize = 5;
+const NUM_STRAIGHT_FACES: usize = 3;
+/// Three straight faces, two turned, then ouch, evil grin and rampage.
+const FACE_STRIDE: usize = 8;
+const OUCH_OFFSET: usize = 5;
+const EVIL_GRIN_OFFSET: usize = 6;
+const RAMPAGE_OFFSET: usize = 7;
+const GOD_FACE: usize = NUM_PAIN_FACES * FACE_STRIDE;
+const DEAD_FACE: usize = GOD_FACE + 1;
+const NUM_FACES: usize = DEAD_FACE + 1;
+
+const MUCH_PAIN: u32 = 20;
+const TURN_COUNT: u32 = TICS_PER_SECOND as u32;
+const EVIL_GRIN_COUNT: u32 = 2 * TICS_PER_SECOND as u32;
+const STRAIGHT_FACE_COUNT: u32 = TICS_PER_SECOND as u32 / 2;
+const RAMPAGE_DELAY: u32 = 2 * TICS_PER_SECOND as u32;
+
+#[cfg(test)]
+mod test {
+    use super::{
+        draw_number, pain_offset, Face, PlayerStatus, DEAD_FACE, EVIL_GRIN_COUNT, EVIL_GRIN_OFFSET,
+        FACE_STRIDE, GOD_FACE, NUM_STRAIGHT_FACES, OUCH_OFFSET, RAMPAGE_DELAY, RAMPAGE_OFFSET,
+        TURN_COUNT,
+    };
+    use wad::Image;
+
+    /// A `width` x `height` patch filled with palette index `color`.
+    fn solid_patch(width: usize, height: usize, color: u8) -> Image {
+        let post_offset = 8 + 4 * width;
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(width as u16).to_le_bytes());
+        buffer.extend_from_slice(&(height as u16).to_le_bytes());
+        buffer.extend_from_slice(&[0; 4]);
+        for _ in 0..width {
+            buffer.extend_from_slice(&(post_offset as u32).to_le_bytes());
+        }
+        buffer.extend_from_slice(&[0, height as u8, 0]);
+        buffer.extend(std::iter::repeat_n(color, height));
+        buffer.extend_from_slice(&[0, 255]);
+        Image::from_buffer(&buffer).unwrap()
+    }
+
+    #[test]
+    fn pain_offsets() {
+        assert_eq!(pain_offset(100), 0);
+        assert_eq!(pain_offset(200), 0);
+        assert_eq!(pain_offset(80), 0);
+        assert_eq!(pain_offset(79), FACE_STRIDE);
+        assert_eq!(pain_offset(50), 2 * FACE_STRIDE);
+        assert_eq!(pain_offset(0), 4 * FACE_STRIDE);
+    }
+
+    #[test]
+    fn numbers_are_right_aligned() {
+        let digits: Vec<_> = (0..10)
+            .map(|digit| Some(solid_patch(2, 1, digit)))
+            .collect();
+        let draw = |number| {
+            let mut image = Image::new(8, 1).unwrap();
+            draw_number(&mut image, &digits, number, 7, 0);
+            image.pixels().to_vec()
+        };
+        const NONE: u16 = 0xff00;
+        assert_eq!(draw(0), [NONE, NONE, NONE, NONE, NONE, 0, 0, NONE]);
+        assert_eq!(draw(42), [NONE, NONE, NONE, 4, 4, 2, 2, NONE]);
+        assert_eq!(draw(5000), [NONE, 9, 9, 9, 9, 9, 9, NONE]);
+
+        let mut image = Image::new(8, 1).unwrap();
+        draw_number(&mut image, &vec![None; 10], 42, 7, 0);
+        assert_eq!(image.pixels(), &[NONE; 8][..]);
+    }
+
+    #[test]
+    fn face_looks_around_when_idle() {
+        let status = PlayerStatus::default();
+        let mut face = Face::new();
+        for _ in 0..1000 {
+            face.tick(&status, false);
+            assert!(face.index < NUM_STRAIGHT_FACES);
+        }
+    }
+
+    #[test]
+    fn face_winces_when_hurt() {
+        let mut status = PlayerStatus::default();
+        let mut face = Face::new();
+        face.tick(&status, false);
+
+        status.health = 90;
+        face.tick(&status, false);
+        assert_eq!(face.index, RAMPAGE_OFFSET);
+
+        status.health = 60;
+        face.tick(&status, false);
+        assert_eq!(face.index, FACE_STRIDE + OUCH_OFFSET);
+        for _ in 1..TURN_COUNT {
+            face.tick(&status, false);
+            assert_eq!(face.index, FACE_STRIDE + OUCH_OFFSET);
+        }
+        face.tick(&status, false);
+        assert!((FACE_STRIDE..FACE_STRIDE + NUM_STRAIGHT_FACES).contains(&face.index));
+
+        status.health = 0;
+        face.tick(&status, false);
+        assert_eq!(face.index, DEAD_FACE);
+        face.tick(&status, true);
+        assert_eq!(face.index, DEAD_FACE);
+    }
+
+    #[test]
+    fn face_grins_at_new_weapons() {
+        let mut status = PlayerStatus::default();
+        let mut face = Face::new();
+        face.tick(&status, false);
+
+        status.weapons[2] = true;
+        for _ in 0..EVIL_GRIN_COUNT {
+            face.tick(&status, false);
+            assert_eq!(face.index, EVIL_GRIN_OFFSET);
+        }
+        face.tick(&status, false);
+        assert!(face.index < NUM_STRAIGHT_FACES);
+    }
+
+    #[test]
+    fn face_rampages_while_shooting() {
+        let mut status = PlayerStatus::default();
+        let mut face = Face::new();
+        status.attack_down = true;
+        for _ in 0..RAMPAGE_DELAY {
+            face.tick(&status, false);
+            assert_ne!(face.index, RAMPAGE_OFFSET);
+        }
+        face.tick(&status, false);
+        assert_eq!(face.index, RAMPAGE_OFFSET);
+
+        status.attack_down = false;
+        face.tick(&status, false);
+        assert!(face.index < NUM_STRAIGHT_FACES);
+    }
+
+    #[test]
+    fn face_glows_when_invulnerable() {
+        let status = PlayerStatus::default();
+        let mut face = Face::new();
+        face.tick(&status, true);
+        assert_eq!(face.index, GOD_FACE);
+        face.tick(&status, false);
+        assert!(face.index < NUM_STRAIGHT_FACES);
+    }
+}
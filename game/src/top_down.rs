@@ -0,0 +1,180 @@
+use super::map_view::MapView;
+use super::player::Player;
+use engine::{
+    Analog2d, DependenciesFrom, Entities, EntityId, Gesture, InfallibleSystem, Input, Projection,
+    Projections, RenderPipeline, Scancode, Tick, Transforms, Window,
+};
+use log::info;
+use math::prelude::*;
+use math::{Pnt2f, Quat, Rad, Trans3, Vec3f};
+use serde::Deserialize;
+use std::f32::consts::FRAC_PI_2;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    pub toggle: Gesture,
+    pub zoom_in: Gesture,
+    pub zoom_out: Gesture,
+    pub pan: Analog2d,
+    pub follow: Gesture,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            toggle: Gesture::AllOf(vec![
+                Gesture::KeyHold(Scancode::LControl),
+                Gesture::KeyTrigger(Scancode::T),
+            ]),
+            zoom_in: Gesture::KeyHold(Scancode::PageUp),
+            zoom_out: Gesture::KeyHold(Scancode::PageDown),
+            pan: Analog2d::Gestures {
+                x_positive: Gesture::KeyHold(Scancode::Numpad6),
+                x_negative: Gesture::KeyHold(Scancode::Numpad4),
+                y_positive: Gesture::KeyHold(Scancode::Numpad8),
+                y_negative: Gesture::KeyHold(Scancode::Numpad2),
+                step: 1.0,
+            },
+            follow: Gesture::KeyTrigger(Scancode::Numpad5),
+        }
+    }
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    bindings: &'context Bindings,
+
+    tick: &'context Tick,
+    window: &'context Window,
+    input: &'context Input,
+    entities: &'context mut Entities,
+    transforms: &'context mut Transforms,
+    projections: &'context mut Projections,
+    render: &'context mut RenderPipeline,
+
+    player: &'context Player,
+}
+
+/// An orthographic camera looking straight down at the level, with north up like the automap.
+///
+/// While enabled it replaces the player's camera for rendering, so the level is drawn with all of
+/// its usual materials. Ceilings face away from it and are culled, leaving the floors visible.
+pub struct TopDownCamera {
+    id: EntityId,
+    enabled: bool,
+    view: MapView,
+}
+
+impl TopDownCamera {
+    /// Looks down at the player, showing `view_height` map units vertically, or switches back to
+    /// the player's camera with `None`.
+    pub fn set_view(&mut self, view_height: Option<f32>) {
+        self.enabled = view_height.is_some();
+        if let Some(view_height) = view_height {
+            self.view.follow = true;
+            self.view.half_height = view_height / 200.0;
+        }
+    }
+}
+
+impl<'context> InfallibleSystem<'context> for TopDownCamera {
+    type Dependencies = Dependencies<'context>;
+
+    fn debug_name() -> &'static str {
+        "top_down_camera"
+    }
+
+    fn create(deps: Dependencies) -> Self {
+        let id = deps.entities.add_root("top_down_camera");
+        deps.transforms.attach_identity(id);
+        let view = MapView::default();
+        deps.projections.attach(
+            id,
+            Projection::Orthographic {
+                half_height: view.half_height,
+                aspect_ratio: deps.window.aspect_ratio(),
+                near: NEAR,
+                far: FAR,
+            },
+        );
+        TopDownCamera {
+            id,
+            enabled: false,
+            view,
+        }
+    }
+
+    fn update(&mut self, deps: Dependencies) {
+        let bindings = deps.bindings;
+        if deps.input.poll_gesture(&bindings.toggle) {
+            self.enabled = !self.enabled;
+            info!(
+                "Top-down camera {}.",
+                if self.enabled { "on" } else { "off" }
+            );
+        }
+        let camera = if self.enabled {
+            self.id
+        } else {
+            deps.player.camera()
+        };
+        if deps.render.camera() != Some(camera) {
+            deps.render.set_camera(camera);
+        }
+        if !self.enabled {
+            return;
+        }
+
+        if deps.input.poll_gesture(&bindings.follow) {
+            self.view.follow = !self.view.follow;
+        }
+
+        // The player's local transform is already up to date this tick, unlike its absolute one.
+        let player = deps
+            .transforms
+            .get_local_mut(deps.player.entity())
+            .expect("player has no transform component: top down")
+            .disp;
+        self.view.update(
+            deps.input,
+            deps.tick.timestep(),
+            (&bindings.zoom_in, &bindings.zoom_out),
+            &bindings.pan,
+            Pnt2f::new(player.x, player.z),
+        );
+
+        *deps
+            .transforms
+            .get_local_mut(self.id)
+            .expect("top down camera has no transform component") = Trans3 {
+            disp: Vec3f::new(
+                self.view.center.x,
+                player.y + HEIGHT_ABOVE_PLAYER,
+                self.view.center.y,
+            ),
+            // Look down, then turn so that north (-x) is up.
+            rot: Quat::from_angle_y(Rad(FRAC_PI_2)) * Quat::from_angle_x(Rad(-FRAC_PI_2)),
+            scale: 1.0,
+        };
+        let half_height = self.view.half_height;
+        deps.projections.replace_with(self.id, |projection| {
+            if let Some(Projection::Orthographic {
+                half_height: projection_half_height,
+                ..
+            }) = projection
+            {
+                *projection_half_height = half_height;
+            }
+        });
+    }
+
+    fn teardown(&mut self, deps: Dependencies) {
+        deps.entities.remove(self.id);
+    }
+}
+
+/// High enough to be above any ceiling, and far enough to see any floor below it.
+const HEIGHT_ABOVE_PLAYER: f32 = 50.0;
+const NEAR: f32 = 0.01;
+const FAR: f32 = 100.0;
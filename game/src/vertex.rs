@@ -7,24 +7,34 @@ use glium::implement_vertex;
 pub struct StaticVertex {
     pub a_pos: [f32; 3],
     pub a_atlas_uv: [f32; 2],
+    pub a_atlas_page: u8,
     pub a_tile_uv: [f32; 2],
     pub a_tile_size: [f32; 2],
     pub a_scroll_rate: f32,
     pub a_row_height: f32,
     pub a_num_frames: u8,
-    pub a_light: u8,
+    pub a_light: u16,
+    /// Only read by the debug views: the index of the BSP leaf, the `ObjectId` and whether the
+    /// wall's linedef is a trigger.
+    pub a_debug_leaf: u16,
+    pub a_debug_object: u16,
+    pub a_debug_trigger: u8,
 }
 
 implement_vertex! {
     StaticVertex,
     a_pos,
     a_atlas_uv,
+    a_atlas_page,
     a_tile_uv,
     a_tile_size,
     a_scroll_rate,
     a_row_height,
     a_num_frames,
     a_light,
+    a_debug_leaf,
+    a_debug_object,
+    a_debug_trigger,
 }
 
 #[repr(C)]
@@ -32,22 +42,30 @@ implement_vertex! {
 pub struct SpriteVertex {
     pub a_pos: [f32; 3],
     pub a_atlas_uv: [f32; 2],
+    pub a_atlas_page: u8,
     pub a_tile_uv: [f32; 2],
     pub a_tile_size: [f32; 2],
     pub a_local_x: f32,
+    pub a_yaw: f32,
     pub a_num_frames: u8,
-    pub a_light: u8,
+    pub a_rotation: u8,
+    pub a_light: u16,
+    pub a_fullbright: u8,
 }
 
 implement_vertex! {
     SpriteVertex,
     a_pos,
     a_atlas_uv,
+    a_atlas_page,
     a_tile_uv,
     a_tile_size,
     a_local_x,
+    a_yaw,
     a_num_frames,
+    a_rotation,
     a_light,
+    a_fullbright,
 }
 
 #[repr(C)]
@@ -60,3 +78,16 @@ implement_vertex! {
     SkyVertex,
     a_pos,
 }
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct StatusBarVertex {
+    pub a_pos: [f32; 3],
+    pub a_uv: [f32; 2],
+}
+
+implement_vertex! {
+    StatusBarVertex,
+    a_pos,
+    a_uv,
+}
@@ -3,9 +3,10 @@ use engine::{DependenciesFrom, System};
 use failchain::{bail, ResultExt};
 use log::info;
 use std::path::PathBuf;
+use wad::types::{GameMode, Skill, WadThing};
 use wad::{
-    Archive, Level as WadLevel, LevelAnalysis, LevelVisitor, LevelWalker, Result as WadResult,
-    TextureDirectory, WadName,
+    Archive, Image, Level as WadLevel, LevelAnalysis, LevelVisitor, LevelWalker,
+    Result as WadResult, TextureDirectory, WadName,
 };
 
 #[derive(Debug)]
@@ -13,6 +14,8 @@ pub struct Config {
     pub wad_path: PathBuf,
     pub metadata_path: PathBuf,
     pub initial_level_index: usize,
+    pub skill: Skill,
+    pub mode: GameMode,
 }
 
 pub struct WadSystem {
@@ -22,6 +25,8 @@ pub struct WadSystem {
     pub analysis: LevelAnalysis,
 
     level_name: WadName,
+    skill: Skill,
+    mode: GameMode,
     current_level_index: usize,
     next_level_index: usize,
     level_changed: bool,
@@ -44,6 +49,11 @@ impl WadSystem {
         self.level_changed
     }
 
+    /// Whether a thing in the current level should be spawned for the configured skill & mode.
+    pub fn spawns(&self, thing: &WadThing) -> bool {
+        thing.spawns_in(self.skill, self.mode)
+    }
+
     pub fn walk<V: LevelVisitor>(&self, visitor: &mut V) {
         LevelWalker::new(
             &self.level,
@@ -52,6 +62,7 @@ impl WadSystem {
             self.archive.metadata(),
             visitor,
         )
+        .with_skill(self.skill, self.mode)
         .walk();
     }
 }
@@ -110,6 +121,8 @@ impl<'context> System<'context> for WadSystem {
             next_level_index: level_index,
             level_changed: false,
             level_name,
+            skill: deps.config.skill,
+            mode: deps.config.mode,
         })
     }
 
@@ -155,3 +168,12 @@ impl<'context> System<'context> for WadSystem {
         Ok(())
     }
 }
+
+/// Reads the patch in the lump called `name`, if there is one.
+pub fn read_patch(archive: &Archive, name: &str) -> WadResult<Option<Image>> {
+    let name = WadName::from_bytes(name.as_bytes())?;
+    match archive.named_lump(&name)? {
+        Some(lump) => Ok(Some(Image::from_buffer(&lump.read_bytes()?)?)),
+        None => Ok(None),
+    }
+}
@@ -1,11 +1,11 @@
 use engine::{Entity, EntityId, Transforms};
 use idcontain::IdMapVec;
 use math::prelude::*;
-use math::{ContactInfo, Line2f, Pnt2f, Pnt3f, Sphere, Trans3, Vec3f};
+use math::{Aabb3, ContactInfo, Cylinder, Line2f, Pnt2f, Pnt3f, Sphere, Trans3, Vec3f};
 use std::cell::RefCell;
 use std::{f32, i32};
 use vec_map::VecMap;
-use wad::{Branch, LevelVisitor, ObjectId, SkyPoly, SkyQuad, StaticPoly, StaticQuad};
+use wad::{Branch, Decor, LevelVisitor, ObjectId, SkyPoly, SkyQuad, StaticPoly, StaticQuad};
 
 pub struct World {
     nodes: Vec<Node>,
@@ -13,6 +13,10 @@ pub struct World {
     triangles: Vec<Triangle>,
     verts: Vec<Pnt3f>,
 
+    // Solid things, sorted by object, the static ones first.
+    bodies: Vec<Cylinder>,
+    static_bodies: Bodies,
+
     dynamic_chunks: IdMapVec<Entity, DynamicChunk>,
 
     node_stack: RefCell<Vec<usize>>,
@@ -58,6 +62,7 @@ impl World {
                 self.sweep_chunk(&mut first_contact, chunk, sphere, vel);
             }
         }
+        self.sweep_bodies(&mut first_contact, self.static_bodies, sphere, vel);
 
         // Dynamics.
         for dynamic in self.dynamic_chunks.access() {
@@ -72,6 +77,12 @@ impl World {
                 transformed_sphere,
                 transformed_velocity,
             );
+            self.sweep_bodies(
+                &mut first_contact,
+                dynamic.bodies,
+                transformed_sphere,
+                transformed_velocity,
+            );
         }
 
         if first_contact.time < f32::INFINITY {
@@ -101,6 +112,26 @@ impl World {
             });
     }
 
+    fn sweep_bodies(
+        &self,
+        first_contact: &mut ContactInfo,
+        bodies: Bodies,
+        sphere: Sphere,
+        vel: Vec3f,
+    ) {
+        let bodies = &self.bodies[bodies.start as usize..bodies.end as usize];
+        *first_contact = bodies
+            .iter()
+            .filter_map(|body| sphere.sweep_cylinder(body, vel))
+            .fold(*first_contact, |first, current| {
+                if first.time < current.time {
+                    first
+                } else {
+                    current
+                }
+            });
+    }
+
     fn sweep_sphere_triangle(
         &self,
         sphere: Sphere,
@@ -123,8 +154,15 @@ struct Chunk {
     tri_end: u32,
 }
 
+#[derive(Copy, Clone)]
+struct Bodies {
+    start: u32,
+    end: u32,
+}
+
 struct DynamicChunk {
     chunk: Chunk,
+    bodies: Bodies,
     inverse_transform: Trans3,
 }
 
@@ -215,6 +253,7 @@ pub struct WorldBuilder<'a> {
     node_stack: RefCell<Vec<usize>>,
 
     triangles: VecMap<Vec<Triangle>>,
+    bodies: VecMap<Vec<Cylinder>>,
     objects: &'a [EntityId],
 }
 
@@ -228,25 +267,44 @@ impl<'a> WorldBuilder<'a> {
             verts: Vec::with_capacity(4096),
             node_stack: RefCell::new(Vec::with_capacity(32)),
             triangles,
+            bodies: VecMap::new(),
             objects,
         }
     }
 
-    pub fn build(self) -> World {
+    pub fn build(mut self) -> World {
         let mut dynamic_chunks = IdMapVec::with_capacity(self.objects.len() - 1);
         let mut triangles = Vec::with_capacity(self.triangles.values().map(Vec::len).sum());
-        for (i_object, object_triangles) in self.triangles {
+        let mut bodies = Vec::with_capacity(self.bodies.values().map(Vec::len).sum());
+        let mut static_bodies = Bodies { start: 0, end: 0 };
+        for i_object in 0..self.objects.len() {
+            let object_triangles = self.triangles.remove(i_object);
+            let object_bodies = self.bodies.remove(i_object);
+            if object_triangles.is_none() && object_bodies.is_none() {
+                continue;
+            }
+
             let tri_start = triangles.len() as u32;
-            triangles.extend(object_triangles);
+            triangles.extend(object_triangles.into_iter().flatten());
+            let tri_end = triangles.len() as u32;
+            let body_start = bodies.len() as u32;
+            bodies.extend(object_bodies.into_iter().flatten());
+            let object_bodies = Bodies {
+                start: body_start,
+                end: bodies.len() as u32,
+            };
+
             if i_object > 0 {
-                let tri_end = triangles.len() as u32;
                 dynamic_chunks.insert(
                     self.objects[i_object],
                     DynamicChunk {
                         chunk: Chunk { tri_start, tri_end },
+                        bodies: object_bodies,
                         inverse_transform: Trans3::one(),
                     },
                 );
+            } else {
+                static_bodies = object_bodies;
             }
         }
 
@@ -257,6 +315,8 @@ impl<'a> WorldBuilder<'a> {
             node_stack: self.node_stack,
             dynamic_chunks,
             triangles,
+            bodies,
+            static_bodies,
         }
     }
 
@@ -304,20 +364,20 @@ impl<'a> WorldBuilder<'a> {
 }
 
 impl<'a> LevelVisitor for WorldBuilder<'a> {
-    fn visit_bsp_root(&mut self, line: &Line2f) {
+    fn visit_bsp_root(&mut self, line: &Line2f, _bounds: &Aabb3) {
         assert_eq!(self.nodes.len(), 0);
         self.nodes.push(Node::new(*line));
         self.node_stack.borrow_mut().push(0);
     }
 
-    fn visit_bsp_node(&mut self, line: &Line2f, branch: Branch) {
+    fn visit_bsp_node(&mut self, line: &Line2f, branch: Branch, _bounds: &Aabb3) {
         let index = self.nodes.len();
         self.nodes.push(Node::new(*line));
         self.link_child(Child::Node(index), branch);
         self.node_stack.borrow_mut().push(index);
     }
 
-    fn visit_bsp_leaf(&mut self, branch: Branch) {
+    fn visit_bsp_leaf(&mut self, branch: Branch, _bounds: &Aabb3) {
         let index = self.chunks.len();
         self.chunks.push(Chunk {
             tri_start: self.triangles[0].len() as u32,
@@ -385,6 +445,16 @@ impl<'a> LevelVisitor for WorldBuilder<'a> {
         }
     }
 
+    fn visit_decor(&mut self, decor: &Decor) {
+        if !decor.obstacle {
+            return;
+        }
+        self.bodies
+            .entry(decor.object_id.0 as usize)
+            .or_insert_with(Vec::new)
+            .push(Cylinder::new(decor.low, decor.high.y, decor.radius));
+    }
+
     fn visit_sky_quad(&mut self, quad: &SkyQuad) {
         let &SkyQuad {
             object_id,
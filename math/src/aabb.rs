@@ -0,0 +1,54 @@
+use super::{Pnt3f, Vec3f};
+
+/// An axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb3 {
+    pub min: Pnt3f,
+    pub max: Pnt3f,
+}
+
+impl Aabb3 {
+    pub fn new(min: Pnt3f, max: Pnt3f) -> Self {
+        debug_assert!(
+            min.x <= max.x && min.y <= max.y && min.z <= max.z,
+            "inverted bounds {:?} to {:?}",
+            min,
+            max
+        );
+        Self { min, max }
+    }
+
+    /// The smallest box containing all `points`, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Pnt3f>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, point| {
+            let point_bounds = Aabb3 {
+                min: point,
+                max: point,
+            };
+            Some(bounds.map_or(point_bounds, |bounds: Aabb3| bounds.union(&point_bounds)))
+        })
+    }
+
+    pub fn union(&self, other: &Aabb3) -> Aabb3 {
+        Aabb3 {
+            min: Pnt3f::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Pnt3f::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn inflate(&self, amount: f32) -> Aabb3 {
+        let amount = Vec3f::new(amount, amount, amount);
+        Aabb3 {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use super::{Pnt3f, Vec2f};
+
+/// A cylinder with its axis along Y, spanning `low` to `high` vertically.
+#[derive(Copy, Clone, Debug)]
+pub struct Cylinder {
+    pub center: Vec2f,
+    pub low: f32,
+    pub high: f32,
+    pub radius: f32,
+}
+
+impl Cylinder {
+    pub fn new(low: Pnt3f, high: f32, radius: f32) -> Self {
+        Self {
+            center: Vec2f::new(low.x, low.z),
+            low: low.y,
+            high,
+            radius,
+        }
+    }
+}
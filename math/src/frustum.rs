@@ -0,0 +1,70 @@
+use super::prelude::*;
+use super::{Aabb3, Mat4, Vec4f};
+
+/// A view frustum as six inward-facing planes, `(a, b, c, d)` with `a*x + b*y + c*z + d >= 0` on
+/// the inside.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    planes: [Vec4f; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a (model)view-projection matrix. The planes are in the space the
+    /// matrix transforms from, e.g. world space for `projection * view`.
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let (x, y, z, w) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Conservative test: may return `true` for some boxes just outside the frustum near its
+    /// corners, but never returns `false` for a box which is (partially) inside.
+    pub fn intersects_aabb(&self, aabb: &Aabb3) -> bool {
+        self.planes.iter().all(|plane| {
+            // Test the box corner furthest along the plane normal.
+            let corner = Vec4f::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+                1.0,
+            );
+            plane.dot(corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{perspective, Aabb3, Deg, Pnt3f};
+    use super::Frustum;
+
+    #[test]
+    fn test_frustum_aabb() {
+        // Default camera looks down -Z.
+        let frustum = Frustum::from_matrix(&perspective(Deg(90.0), 1.0, 0.1, 100.0));
+        let unit_at = |x: f32, y: f32, z: f32| {
+            Aabb3::new(
+                Pnt3f::new(x - 0.5, y - 0.5, z - 0.5),
+                Pnt3f::new(x + 0.5, y + 0.5, z + 0.5),
+            )
+        };
+        assert!(frustum.intersects_aabb(&unit_at(0.0, 0.0, -10.0)));
+        assert!(frustum.intersects_aabb(&unit_at(0.0, 0.0, 0.0)));
+        assert!(!frustum.intersects_aabb(&unit_at(0.0, 0.0, 10.0)));
+        assert!(!frustum.intersects_aabb(&unit_at(20.0, 0.0, -10.0)));
+        assert!(!frustum.intersects_aabb(&unit_at(0.0, 0.0, -200.0)));
+    }
+}
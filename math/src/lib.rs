@@ -23,11 +23,17 @@ pub use cgmath::{
 
 pub use num_traits::{Float, NumCast};
 
+pub mod aabb;
 pub mod contact;
+pub mod cylinder;
+pub mod frustum;
 pub mod line;
 pub mod sphere;
 
+pub use self::aabb::Aabb3;
 pub use self::contact::ContactInfo;
+pub use self::cylinder::Cylinder;
+pub use self::frustum::Frustum;
 pub use self::line::Line2;
 pub use self::sphere::Sphere;
 
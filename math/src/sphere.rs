@@ -1,4 +1,5 @@
 use super::contact::ContactInfo;
+use super::cylinder::Cylinder;
 use super::prelude::*;
 use super::{vec2, vec3, Pnt3f, Vec2f, Vec3f};
 
@@ -127,6 +128,61 @@ impl Sphere {
             None
         }
     }
+
+    /// Sweeps the sphere against a (capped) cylinder. The sphere's radius is added to the cylinder's
+    /// radius and height, which treats the cylinder's rims as sharp rather than rounded.
+    pub fn sweep_cylinder(&self, cylinder: &Cylinder, vel: Vec3f) -> Option<ContactInfo> {
+        let Self { center, radius } = *self;
+        let radius = cylinder.radius + radius;
+        let (low, high) = (cylinder.low - self.radius, cylinder.high + self.radius);
+        let offset = vec2(center.x, center.z) - cylinder.center;
+        let planar_vel = vec2(vel.x, vel.z);
+        let inside_radius = offset.magnitude2() < radius * radius;
+
+        // Caps.
+        let cap = if vel.y < 0.0 && center.y >= high {
+            Some(((high - center.y) / vel.y, 1.0))
+        } else if vel.y > 0.0 && center.y <= low {
+            Some(((low - center.y) / vel.y, -1.0))
+        } else {
+            None
+        };
+        if let Some((time, normal_y)) = cap {
+            if (offset + planar_vel * time).magnitude2() <= radius * radius {
+                return Some(ContactInfo {
+                    time,
+                    normal: vec3(0.0, normal_y, 0.0),
+                });
+            }
+        }
+
+        // Side: only when moving towards the axis.
+        if offset.dot(planar_vel) >= 0.0 {
+            return None;
+        }
+        let time = if inside_radius {
+            // Already penetrating (e.g. spawned inside), allow moving out, but not further in.
+            if center.y < low || center.y > high {
+                return None;
+            }
+            0.0
+        } else {
+            let a = planar_vel.magnitude2();
+            let b = 2.0 * offset.dot(planar_vel);
+            let c = offset.magnitude2() - radius * radius;
+            let time = lowest_quadratic_root(a, b, c)?;
+            let height = center.y + vel.y * time;
+            if height < low || height > high {
+                return None;
+            }
+            time
+        };
+        let normal = (offset + planar_vel * time).normalize_or_zero();
+        Some(ContactInfo {
+            time,
+            normal: vec3(normal.x, 0.0, normal.y),
+        })
+    }
 }
 
 fn intersect_sphere_line(center: Pnt3f, radius: f32, p1: Pnt3f, p2: Pnt3f) -> Option<f32> {
@@ -181,3 +237,39 @@ fn is_point_inside_triangle(verts: &[Pnt3f; 3], point: Pnt3f) -> bool {
 
     (0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&gamma) && (0.0..=1.0).contains(&beta)
 }
+
+#[cfg(test)]
+mod test {
+    use super::super::prelude::*;
+    use super::super::{vec2, vec3, Cylinder, Pnt3f, Sphere};
+
+    #[test]
+    fn test_sweep_cylinder() {
+        let cylinder = Cylinder {
+            center: vec2(0.0, 0.0),
+            low: 0.0,
+            high: 1.0,
+            radius: 0.5,
+        };
+        let sphere = Sphere::new(Pnt3f::new(-2.0, 0.5, 0.0), 0.5);
+
+        let contact = sphere
+            .sweep_cylinder(&cylinder, vec3(2.0, 0.0, 0.0))
+            .expect("side contact");
+        assert!((contact.time - 0.5).abs() < 1e-5);
+        assert!((contact.normal - vec3(-1.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+        let contact = Sphere::new(Pnt3f::new(0.0, 2.0, 0.0), 0.5)
+            .sweep_cylinder(&cylinder, vec3(0.0, -1.0, 0.0))
+            .expect("cap contact");
+        assert!((contact.time - 0.5).abs() < 1e-5);
+        assert!((contact.normal - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+        assert!(sphere
+            .sweep_cylinder(&cylinder, vec3(-2.0, 0.0, 0.0))
+            .is_none());
+        assert!(Sphere::new(Pnt3f::new(-2.0, 2.0, 0.0), 0.5)
+            .sweep_cylinder(&cylinder, vec3(2.0, 0.0, 0.0))
+            .is_none());
+    }
+}
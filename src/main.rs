@@ -1,12 +1,15 @@
+use engine::{Fullscreen, RenderBackend};
 use failure::{bail, Error};
-use game::{self, Game, GameConfig};
+use game::{self, CameraPose, ExportFormat, Game, GameConfig};
 use log::{error, info};
-use math::DurationExt;
+use math::{Deg, DurationExt};
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::time::Instant;
 use structopt::StructOpt;
+use wad::types::{GameMode, Skill};
 use wad::Archive;
 
 #[derive(StructOpt)]
@@ -64,11 +67,87 @@ struct App {
     /// Horizontal field of view.
     fov: f32,
 
+    #[structopt(
+        short = "s",
+        long = "skill",
+        default_value = "3",
+        value_name = "1-5",
+        parse(try_from_str = parse_skill)
+    )]
+    /// Skill level, from 1 (I'm too young to die) to 5 (Nightmare!).
+    skill: Skill,
+
+    #[structopt(
+        long = "mode",
+        default_value = "single",
+        value_name = "single|coop|deathmatch",
+        parse(try_from_str = parse_mode)
+    )]
+    /// Game mode, used to pick which things are spawned.
+    mode: GameMode,
+
+    #[structopt(
+        long = "renderer",
+        default_value = "gpu",
+        value_name = "gpu|software",
+        parse(try_from_str = parse_backend)
+    )]
+    /// Renderer backend; `software` rasterises on the CPU and needs no display.
+    backend: RenderBackend,
+
+    #[structopt(
+        long = "fullscreen",
+        default_value = "windowed",
+        value_name = "windowed|borderless|exclusive",
+        parse(try_from_str = parse_fullscreen)
+    )]
+    /// Whether to cover the screen; `exclusive` switches to the video mode closest to
+    /// `--resolution`. Toggle at runtime with Alt+Enter.
+    fullscreen: Fullscreen,
+
+    #[structopt(long = "vsync")]
+    /// Wait for vertical sync before showing each frame.
+    vsync: bool,
+
+    #[structopt(long = "msaa", default_value = "0", value_name = "SAMPLES")]
+    /// Number of samples per pixel for anti-aliasing (e.g. 4); 0 disables it.
+    msaa_samples: u16,
+
+    #[structopt(long = "max-fps", value_name = "FPS")]
+    /// Render at most this many frames per second.
+    max_fps: Option<f32>,
+
+    #[structopt(
+        long = "internal-resolution",
+        value_name = "WIDTHxHEIGHT",
+        parse(try_from_str = parse_resolution)
+    )]
+    /// Render at this size and stretch frames over the window; 320x200 looks like vanilla.
+    internal_resolution: Option<(u32, u32)>,
+
+    #[structopt(long = "crt")]
+    /// Add scanlines and a vignette to frames, like a CRT monitor. Needs the gpu renderer.
+    crt: bool,
+
+    #[structopt(long = "bindings", value_name = "FILE", parse(from_os_str))]
+    /// TOML file overriding key and mouse bindings, e.g. assets/bindings/azerty.toml.
+    bindings: Option<PathBuf>,
+
+    #[structopt(long = "record-input", value_name = "FILE", parse(from_os_str))]
+    /// Save every tick's keyboard, mouse and gamepad input to a file, e.g. for a bug report.
+    record_input: Option<PathBuf>,
+
+    #[structopt(long = "replay-input", value_name = "FILE", parse(from_os_str))]
+    /// Replay input saved with `--record-input` instead of live input, reproducing the session
+    /// exactly when started with the same WAD, level and skill. Cannot be combined with
+    /// `--record-input`.
+    replay_input: Option<PathBuf>,
+
     #[structopt(subcommand)]
     command: Option<Command>,
 }
 
-#[derive(StructOpt, Copy, Clone)]
+#[derive(StructOpt, Clone)]
 enum Command {
     /// Load metadata and all levels in WAD, then exit.
     #[structopt(name = "check")]
@@ -77,6 +156,100 @@ enum Command {
     /// List the names and indices of all the leves in the WAD, then exit.
     #[structopt(name = "list-levels")]
     ListLevelNames,
+
+    /// Render frames of a level from fixed camera poses and save them as PNG files, then exit.
+    #[structopt(name = "screenshot")]
+    Screenshot {
+        #[structopt(long = "level", value_name = "N")]
+        /// The index of the level to render (0-based); overrides the global `--level`.
+        level_index: Option<usize>,
+
+        #[structopt(
+            long = "pos",
+            value_name = "X,Y,Z",
+            allow_hyphen_values = true,
+            required_unless = "poses",
+            requires = "out",
+            parse(try_from_str = parse_position)
+        )]
+        /// Eye position in map units; `Z` is the absolute height of the eye.
+        pos: Option<[f32; 3]>,
+
+        #[structopt(
+            long = "yaw",
+            default_value = "0",
+            value_name = "DEGREES",
+            allow_hyphen_values = true
+        )]
+        /// View direction, like thing angles: 0 is east, 90 is north.
+        yaw: f32,
+
+        #[structopt(
+            long = "pitch",
+            default_value = "0",
+            value_name = "DEGREES",
+            allow_hyphen_values = true
+        )]
+        /// Angle to look up by; negative values look down.
+        pitch: f32,
+
+        #[structopt(
+            long = "out",
+            value_name = "FILE",
+            requires = "pos",
+            parse(from_os_str)
+        )]
+        /// PNG file to save the frame to.
+        out: Option<PathBuf>,
+
+        #[structopt(long = "poses", value_name = "FILE", parse(from_os_str))]
+        /// File with one pose per line, as `X,Y,Z YAW PITCH OUT`. Empty lines and lines starting
+        /// with `#` are skipped.
+        poses: Option<PathBuf>,
+
+        #[structopt(long = "top-down", value_name = "MAP_UNITS")]
+        /// Look straight down at each position instead, with an orthographic view this many map
+        /// units tall and north up. Yaw and pitch are ignored.
+        top_down: Option<f32>,
+    },
+
+    /// Export the geometry of a level as a textured mesh, for use in other tools, then exit.
+    #[structopt(name = "export-level")]
+    ExportLevel {
+        #[structopt(long = "level", value_name = "N")]
+        /// The index of the level to export (0-based); overrides the global `--level`.
+        level_index: Option<usize>,
+
+        #[structopt(
+            long = "format",
+            default_value = "gltf",
+            value_name = "gltf|obj",
+            parse(try_from_str = parse_export_format)
+        )]
+        /// File format to write.
+        format: ExportFormat,
+
+        #[structopt(long = "out", value_name = "FILE", parse(from_os_str))]
+        /// File to write the mesh to. The texture atlas is saved next to it as a PNG, along with a
+        /// `.bin` buffer for glTF or a `.mtl` material library for OBJ.
+        out: PathBuf,
+    },
+
+    /// Fly the camera along a path file recorded in-game with F10, then exit.
+    #[structopt(name = "play-path")]
+    PlayPath {
+        #[structopt(long = "level", value_name = "N")]
+        /// The index of the level to fly through (0-based); overrides the global `--level`.
+        level_index: Option<usize>,
+
+        #[structopt(long = "path", value_name = "FILE", parse(from_os_str))]
+        /// Camera path file, with one `SECONDS X,Y,Z YAW PITCH` keyframe per line.
+        path: PathBuf,
+
+        #[structopt(long = "frames", value_name = "DIR", parse(from_os_str))]
+        /// Save every frame as a numbered PNG in this directory, e.g. to encode a video.
+        frames: Option<PathBuf>,
+    },
 }
 
 impl App {
@@ -86,7 +259,7 @@ impl App {
     }
 
     /// Either play the game (if no `Command` was passed), or perform the command.
-    pub fn run(self) -> Result<(), Error> {
+    pub fn run(mut self) -> Result<(), Error> {
         // Init logging, with default `info` level.
         env_logger::Builder::from_env(
             env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
@@ -94,7 +267,7 @@ impl App {
         .format_timestamp(None)
         .init();
 
-        match self.command {
+        match self.command.take() {
             None => {
                 game::create(&self.into_config())?.run();
             }
@@ -119,6 +292,60 @@ impl App {
                     println!("{:3} {:8}", i_level, wad.level_lump(i_level)?.name());
                 }
             }
+            Some(Command::Screenshot {
+                level_index,
+                pos,
+                yaw,
+                pitch,
+                out,
+                poses,
+                top_down,
+            }) => {
+                let mut shots = Vec::new();
+                if let (Some([x, y, z]), Some(out)) = (pos, out) {
+                    shots.push((CameraPose::from_wad(x, y, z, Deg(yaw), Deg(pitch)), out));
+                }
+                if let Some(poses) = poses {
+                    shots.extend(read_poses(&poses)?);
+                }
+
+                let mut game = game::create(&GameConfig {
+                    initial_level_index: level_index.unwrap_or(self.level_index),
+                    ..self.into_config()
+                })?;
+                game.set_top_down(top_down);
+                for (pose, out) in shots {
+                    game.screenshot(pose)?.save_png(&out)?;
+                    info!("Saved {}.", out.display());
+                }
+            }
+            Some(Command::ExportLevel {
+                level_index,
+                format,
+                out,
+            }) => {
+                game::export_level(
+                    &GameConfig {
+                        initial_level_index: level_index.unwrap_or(self.level_index),
+                        ..self.into_config()
+                    },
+                    format,
+                    &out,
+                )?;
+            }
+            Some(Command::PlayPath {
+                level_index,
+                path,
+                frames,
+            }) => {
+                game::create(&GameConfig {
+                    initial_level_index: level_index.unwrap_or(self.level_index),
+                    camera_path: Some(path),
+                    camera_path_frames: frames,
+                    ..self.into_config()
+                })?
+                .run();
+            }
         }
         Ok(())
     }
@@ -133,6 +360,20 @@ impl App {
             height: self.resolution.1,
             version: env!("CARGO_PKG_VERSION"),
             initial_level_index: self.level_index,
+            skill: self.skill,
+            mode: self.mode,
+            backend: self.backend,
+            fullscreen: self.fullscreen,
+            vsync: self.vsync,
+            msaa_samples: self.msaa_samples,
+            max_fps: self.max_fps,
+            internal_resolution: self.internal_resolution,
+            crt: self.crt,
+            camera_path: None,
+            camera_path_frames: None,
+            bindings_file: self.bindings,
+            record_input: self.record_input,
+            replay_input: self.replay_input,
         }
     }
 }
@@ -161,6 +402,98 @@ fn parse_resolution(size_str: &str) -> Result<(u32, u32), Error> {
     }
 }
 
+/// Parse a position string like `X,Y,Z`.
+fn parse_position(position_str: &str) -> Result<[f32; 3], Error> {
+    let coordinates = position_str
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    match coordinates.as_deref() {
+        Ok(&[x, y, z]) => Ok([x, y, z]),
+        _ => bail!("Position format must be X,Y,Z"),
+    }
+}
+
+/// Read a camera poses file, with one `X,Y,Z YAW PITCH OUT` pose per line.
+fn read_poses(path: &Path) -> Result<Vec<(CameraPose, PathBuf)>, Error> {
+    let contents = fs::read_to_string(path)?;
+    let mut poses = Vec::new();
+    for (i_line, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parsed = match fields[..] {
+            [position, yaw, pitch, out] => parse_position(position)
+                .ok()
+                .and_then(|position| Some((position, yaw.parse().ok()?, pitch.parse().ok()?, out))),
+            _ => None,
+        };
+        let ([x, y, z], yaw, pitch, out) = if let Some(parsed) = parsed {
+            parsed
+        } else {
+            bail!(
+                "{}:{}: pose format must be `X,Y,Z YAW PITCH OUT`",
+                path.display(),
+                i_line + 1
+            );
+        };
+        poses.push((
+            CameraPose::from_wad(x, y, z, Deg(yaw), Deg(pitch)),
+            PathBuf::from(out),
+        ));
+    }
+    Ok(poses)
+}
+
+/// Parse a 1-based skill level number, like vanilla's `-skill`.
+fn parse_skill(skill_str: &str) -> Result<Skill, Error> {
+    if let Some(skill) = skill_str.parse::<u8>().ok().and_then(Skill::from_number) {
+        Ok(skill)
+    } else {
+        bail!("Skill must be a number between 1 and 5");
+    }
+}
+
+/// Parse a game mode name.
+fn parse_mode(mode_str: &str) -> Result<GameMode, Error> {
+    Ok(match mode_str {
+        "single" => GameMode::SinglePlayer,
+        "coop" => GameMode::Cooperative,
+        "deathmatch" => GameMode::Deathmatch,
+        _ => bail!("Mode must be one of single, coop or deathmatch"),
+    })
+}
+
+/// Parse a renderer backend name.
+fn parse_backend(backend_str: &str) -> Result<RenderBackend, Error> {
+    Ok(match backend_str {
+        "gpu" => RenderBackend::Gpu,
+        "software" => RenderBackend::Software,
+        _ => bail!("Renderer must be one of gpu or software"),
+    })
+}
+
+/// Parse a level export format name.
+fn parse_export_format(format_str: &str) -> Result<ExportFormat, Error> {
+    Ok(match format_str {
+        "gltf" => ExportFormat::Gltf,
+        "obj" => ExportFormat::Obj,
+        _ => bail!("Export format must be one of gltf or obj"),
+    })
+}
+
+/// Parse a fullscreen mode name.
+fn parse_fullscreen(fullscreen_str: &str) -> Result<Fullscreen, Error> {
+    Ok(match fullscreen_str {
+        "windowed" => Fullscreen::Windowed,
+        "borderless" => Fullscreen::Borderless,
+        "exclusive" => Fullscreen::Exclusive,
+        _ => bail!("Fullscreen must be one of windowed, borderless or exclusive"),
+    })
+}
+
 fn main() {
     if let Err(error) = App::run_from_args() {
         error!("Fatal error: {}", error);
@@ -8,6 +8,7 @@ use std::vec::Vec;
 
 pub const MAX_IMAGE_SIZE: usize = 4096;
 
+#[derive(Clone)]
 pub struct Image {
     width: usize,
     height: usize,
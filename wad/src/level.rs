@@ -1,10 +1,10 @@
 use super::archive::Archive;
 use super::errors::Result;
-use super::types::{LightLevel, SectorId, VertexId, WadNode, WadSector};
+use super::types::{ChildId, LightLevel, SectorId, VertexId, WadNode, WadSector};
 use super::types::{WadCoord, WadLinedef, WadSeg, WadSidedef, WadSubsector, WadThing, WadVertex};
-use super::util::from_wad_coords;
+use super::util::{from_wad_coords, parse_child_id};
 use log::{error, info};
-use math::Pnt2f;
+use math::{Line2f, Pnt2f};
 use std::cmp;
 use std::mem;
 use std::slice::Iter as SliceIter;
@@ -160,6 +160,40 @@ impl Level {
         }
     }
 
+    /// The sector containing `pos` (in world coordinates), found by walking the BSP.
+    pub fn sector_at(&self, pos: Pnt2f) -> Option<&WadSector> {
+        let mut child_id = (self.nodes.len() - 1) as ChildId;
+        loop {
+            let (id, is_leaf) = parse_child_id(child_id);
+            if is_leaf {
+                let segs = self
+                    .ssector(id)
+                    .and_then(|subsector| self.ssector_segs(subsector))
+                    .and_then(|segs| if segs.is_empty() { None } else { Some(segs) });
+                let segs = segs?;
+                let sector = self.seg_sector(&segs[0])?;
+                return if segs
+                    .iter()
+                    .filter_map(|seg| self.seg_vertices(seg))
+                    .map(|(v1, v2)| Line2f::from_two_points(v1, v2))
+                    .all(|line| line.signed_distance(pos) <= SEG_TOLERANCE)
+                {
+                    Some(sector)
+                } else {
+                    None
+                };
+            } else {
+                let node = self.nodes.get(id)?;
+                let partition = partition_line(node);
+                if partition.signed_distance(pos) > 0.0f32 {
+                    child_id = node.left;
+                } else {
+                    child_id = node.right;
+                }
+            }
+        }
+    }
+
     pub fn sector_id(&self, sector: &WadSector) -> SectorId {
         let sector_id = (sector as *const _ as usize - self.sectors.as_ptr() as usize)
             / mem::size_of::<WadSector>();
@@ -257,3 +291,13 @@ impl<'a> Iterator for AdjacentSectorsIter<'a> {
         None
     }
 }
+
+pub(crate) fn partition_line(node: &WadNode) -> Line2f {
+    Line2f::from_two_points(
+        from_wad_coords(node.line_x, node.line_y),
+        from_wad_coords(node.line_x + node.step_x, node.line_y + node.step_y),
+    )
+}
+
+// Distance on the wrong side of a seg line allowed.
+pub(crate) const SEG_TOLERANCE: f32 = 0.1;
@@ -18,8 +18,8 @@ pub use self::level::Level;
 pub use self::light::{LightEffect, LightEffectKind, LightInfo};
 pub use self::meta::{MoveEffectDef, SkyMetadata, ThingMetadata, TriggerType, WadMetadata};
 pub use self::name::WadName;
-pub use self::tex::{OpaqueImage, TextureDirectory, TransparentImage};
+pub use self::tex::{OpaqueImage, SpriteRotation, TextureDirectory, TransparentImage};
 pub use self::visitor::{
-    Branch, Decor, LevelAnalysis, LevelVisitor, LevelWalker, Marker, MoveEffect, ObjectId, SkyPoly,
-    SkyQuad, StaticPoly, StaticQuad, Trigger,
+    Branch, Decor, DecorRotation, LevelAnalysis, LevelVisitor, LevelWalker, Marker, MoveEffect,
+    ObjectId, SkyPoly, SkyQuad, StaticPoly, StaticQuad, Trigger,
 };
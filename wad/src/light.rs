@@ -17,7 +17,7 @@ pub struct LightEffect {
     pub kind: LightEffectKind,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
 pub enum LightEffectKind {
     Glow,
     Random,
@@ -37,6 +37,17 @@ pub struct ThingMetadata {
     pub sequence: String,
     pub hanging: bool,
     pub radius: u32,
+    #[serde(default = "Default::default")]
+    pub obstacle: bool,
+    /// The frames (letters, as in `sequence`) drawn at full brightness, regardless of light.
+    #[serde(default = "Default::default")]
+    pub fullbright: String,
+}
+
+impl ThingMetadata {
+    pub fn is_fullbright(&self, frame: u8) -> bool {
+        self.fullbright.as_bytes().contains(&frame)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -264,7 +275,7 @@ mod test {
 
     #[test]
     fn test_wad_metadata() {
-        WadMetadata::from_text(
+        let metadata = WadMetadata::from_text(
             r#"
             [[sky]]
                 level_pattern = "MAP(0[1-9]|10|11)"
@@ -342,6 +353,7 @@ mod test {
                     radius = 20
                     sprite = "RKEY"
                     sequence = "AB"
+                    fullbright = "B"
                     hanging = false
 
                 [[things.monsters]]
@@ -354,5 +366,11 @@ mod test {
         "#,
         )
         .expect("test: could not parse test metadata");
+
+        let key = metadata.find_thing(13).expect("test: missing red keycard");
+        assert!(!key.is_fullbright(b'A'));
+        assert!(key.is_fullbright(b'B'));
+        let baron = metadata.find_thing(3003).expect("test: missing baron");
+        assert!(!baron.is_fullbright(b'A'));
     }
 }
@@ -15,6 +15,8 @@ use std::time::Instant;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Bounds {
+    /// The atlas page (texture array layer) the texture is in.
+    pub page: usize,
     pub pos: Pnt2f,
     pub size: Vec2f,
     pub num_frames: usize,
@@ -24,6 +26,14 @@ pub struct Bounds {
 pub type Flat = Vec<u8>;
 pub type BoundsLookup = IndexMap<WadName, Bounds>;
 
+/// One of the eight views of a sprite frame. Some lumps are shared by two opposite rotations (e.g.
+/// `TROOA2A8`), in which case the second rotation is drawn `mirrored` horizontally.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpriteRotation {
+    pub tex_name: WadName,
+    pub mirrored: bool,
+}
+
 pub struct TextureDirectory {
     textures: IndexMap<WadName, Image>,
     patches: Vec<(WadName, Option<Image>)>,
@@ -39,14 +49,18 @@ pub struct MappedPalette {
     pub colormaps: usize,
 }
 
+/// An atlas of `pages` images of the same `size`, stored one after the other in `pixels`.
 pub struct TransparentImage {
     pub pixels: Vec<u16>,
     pub size: Vec2<usize>,
+    pub pages: usize,
 }
 
+/// Like `TransparentImage`, but without an alpha channel.
 pub struct OpaqueImage {
     pub pixels: Vec<u8>,
     pub size: Vec2<usize>,
+    pub pages: usize,
 }
 
 impl TextureDirectory {
@@ -113,6 +127,52 @@ impl TextureDirectory {
         self.flats.get(&name)
     }
 
+    /// Looks up the eight rotations (`1` to `8`) of a sprite frame. Returns `None` if any of them is
+    /// missing, which is the case for frames with a single view (rotation `0`).
+    pub fn sprite_rotations(&self, sprite: WadName, frame: u8) -> Option<[SpriteRotation; 8]> {
+        let lump_name = |suffix: &[u8]| {
+            let mut name = sprite;
+            for &byte in suffix {
+                name.push(byte).ok()?;
+            }
+            if self.textures.contains_key(&name) {
+                Some(name)
+            } else {
+                None
+            }
+        };
+        let find_rotation = |rotation: u8| {
+            if let Some(tex_name) = lump_name(&[frame, rotation]) {
+                return Some(SpriteRotation {
+                    tex_name,
+                    mirrored: false,
+                });
+            }
+            (b'1'..=b'8').find_map(|other| {
+                lump_name(&[frame, rotation, frame, other])
+                    .map(|tex_name| SpriteRotation {
+                        tex_name,
+                        mirrored: false,
+                    })
+                    .or_else(|| {
+                        lump_name(&[frame, other, frame, rotation]).map(|tex_name| SpriteRotation {
+                            tex_name,
+                            mirrored: true,
+                        })
+                    })
+            })
+        };
+
+        let mut rotations = [SpriteRotation {
+            tex_name: sprite,
+            mirrored: false,
+        }; 8];
+        for (rotation, entry) in (b'1'..=b'8').zip(rotations.iter_mut()) {
+            *entry = find_rotation(rotation)?;
+        }
+        Some(rotations)
+    }
+
     pub fn num_patches(&self) -> usize {
         self.patches.len()
     }
@@ -140,20 +200,19 @@ impl TextureDirectory {
         colormap_start: usize,
         colormap_end: usize,
     ) -> MappedPalette {
-        let num_colormaps = colormap_end - colormap_start;
-        let mut mapped = vec![0u8; 256 * num_colormaps * 3];
-        let palette = &self.palettes[palette];
+        let colormaps: Vec<usize> = (colormap_start..colormap_end).collect();
+        self.build_mapped_palette(palette, &colormaps)
+    }
 
-        let colormaps_with_offsets = self
-            .colormaps
-            .iter()
-            .enumerate()
-            .take(colormap_end)
-            .skip(colormap_start)
-            .map(|(i_colormap, colormap)| (i_colormap * 256 * 3, colormap));
+    /// Like `build_palette_texture`, but with an arbitrary colormap for each row, e.g. to map all
+    /// light levels through the same colormap.
+    pub fn build_mapped_palette(&self, palette: usize, colormaps: &[usize]) -> MappedPalette {
+        let mut mapped = vec![0u8; 256 * colormaps.len() * 3];
+        let palette = &self.palettes[palette];
 
-        for (offset, colormap) in colormaps_with_offsets {
-            for (i_color, color) in colormap.0.iter().enumerate() {
+        for (i_row, &i_colormap) in colormaps.iter().enumerate() {
+            let offset = i_row * 256 * 3;
+            for (i_color, color) in self.colormaps[i_colormap].0.iter().enumerate() {
                 mapped[i_color * 3 + offset..][..3]
                     .copy_from_slice(&palette.0[usize::from(*color) * 3..][..3]);
             }
@@ -161,10 +220,13 @@ impl TextureDirectory {
 
         MappedPalette {
             pixels: mapped,
-            colormaps: colormap_end - colormap_start + 1,
+            colormaps: colormaps.len() + 1,
         }
     }
 
+    /// Packs the named textures into an atlas. If they do not all fit in a single
+    /// `MAX_ATLAS_SIZE` square page, they spill into more pages of that size, meant to be
+    /// uploaded as a texture array.
     pub fn build_texture_atlas<T>(&self, names_iter: T) -> (TransparentImage, BoundsLookup)
     where
         T: IntoIterator<Item = WadName>,
@@ -176,17 +238,17 @@ impl TextureDirectory {
             let image = TransparentImage {
                 pixels: Vec::new(),
                 size: Vec2::zero(),
+                pages: 1,
             };
             return (image, BoundsLookup::new());
         };
         let num_pixels = entries.iter().map(|e| e.image.num_pixels()).sum();
         let min_atlas_size = Vec2::new(cmp::min(128, next_pow2(max_image_width)), 128);
-        let max_size = 4096;
 
         let next_size = |size: &mut Vec2<usize>| loop {
             if size[0] <= size[1] {
-                if size[0] == max_size {
-                    panic!("Could not fit wall atlas.");
+                if size[0] == MAX_ATLAS_SIZE {
+                    return false;
                 }
                 size[0] *= 2;
                 size[1] = 128;
@@ -195,66 +257,72 @@ impl TextureDirectory {
             }
 
             if size[0] * size[1] >= num_pixels {
-                break;
+                return true;
             }
         };
 
         let mut atlas_size = min_atlas_size;
-        next_size(&mut atlas_size);
+        let mut single_page = next_size(&mut atlas_size);
 
         let mut transposed = false;
         let mut positions = Vec::with_capacity(entries.len());
-        loop {
-            let mut offset = Vec2::zero();
-            let mut failed = false;
-            let mut row_height = 0;
-            for &AtlasEntry { image, .. } in &entries {
-                let size = image.size();
-                if offset[0] + size[0] > atlas_size[0] {
-                    offset[0] = 0;
-                    offset[1] += row_height;
-                    row_height = 0;
-                }
-                if size[1] > row_height {
-                    row_height = size[1];
-                }
-                if offset[1] + size[1] > atlas_size[1] {
-                    failed = true;
-                    break;
-                }
-                positions.push(AtlasPosition {
-                    offset: Vec2::new(offset[0] as isize, offset[1] as isize),
-                    row_height,
-                });
-                offset[0] += size[0];
+        while single_page {
+            if place_atlas_entries(&entries, atlas_size, 0, &mut positions) == entries.len() {
+                break;
             }
+            positions.clear();
 
-            if failed {
-                positions.clear();
+            // Try swapping width and height to see if it fits that way.
+            atlas_size = vec2(atlas_size.y, atlas_size.x);
+            transposed = !transposed;
+            if transposed && atlas_size[0] != atlas_size[1] {
+                continue;
+            }
 
-                // Try swapping width and height to see if it fits that way.
-                atlas_size = vec2(atlas_size.y, atlas_size.x);
-                transposed = !transposed;
-                if transposed && atlas_size[0] != atlas_size[1] {
-                    continue;
-                }
+            // If all else fails try a larger size for the atlas.
+            transposed = false;
+            single_page = next_size(&mut atlas_size);
+        }
 
-                // If all else fails try a larger size for the atlas.
-                transposed = false;
-                next_size(&mut atlas_size);
-            } else {
-                break;
+        let mut num_pages = 1;
+        if !single_page {
+            // Fill as many maximum size pages as needed.
+            atlas_size = vec2(MAX_ATLAS_SIZE, MAX_ATLAS_SIZE);
+            num_pages = 0;
+            while positions.len() < entries.len() {
+                let remaining = &entries[positions.len()..];
+                let num_placed =
+                    place_atlas_entries(remaining, atlas_size, num_pages, &mut positions);
+                if num_placed == 0 {
+                    let name = remaining[0].name;
+                    error!(
+                        "Texture {} does not fit in an empty atlas page, skipping.",
+                        name
+                    );
+                    let num_skipped = 1 + remaining[1..]
+                        .iter()
+                        .take_while(|entry| entry.frame_offset != 0)
+                        .count();
+                    positions.extend((0..num_skipped).map(|_| AtlasPosition::SKIPPED));
+                } else {
+                    num_pages += 1;
+                }
             }
         }
         let atlas_size = atlas_size;
+        let num_pages = num_pages.max(1);
 
         assert_eq!(positions.len(), entries.len());
-        // TODO(cristicbz): This should probably split things into multiple atlases or
-        // something, but realistically, I'm never going to implement that.
-        let mut atlas = Image::new(atlas_size[0], atlas_size[1]).expect("atlas too big");
+        let mut pages = (0..num_pages)
+            .map(|_| Image::new(atlas_size[0], atlas_size[1]).expect("atlas too big"))
+            .collect::<Vec<_>>();
         let mut bound_map = IndexMap::new();
         for (i, entry) in entries.iter().enumerate() {
-            atlas.blit(entry.image, positions[i].offset, true);
+            let position = &positions[i];
+            if position.page == AtlasPosition::SKIPPED.page {
+                continue;
+            }
+            pages[position.page].blit(entry.image, position.offset, true);
             bound_map.insert(
                 entry.name,
                 img_bound(&positions[i - entry.frame_offset], entry),
@@ -263,10 +331,11 @@ impl TextureDirectory {
 
         let tex = TransparentImage {
             size: atlas_size,
-            pixels: atlas.into_pixels(),
+            pages: num_pages,
+            pixels: pages.into_iter().flat_map(Image::into_pixels).collect(),
         };
 
-        info!("Texture atlas size: {:?}", atlas_size);
+        info!("Texture atlas size: {:?} x {} pages", atlas_size, num_pages);
         (tex, bound_map)
     }
 
@@ -305,6 +374,7 @@ impl TextureDirectory {
             offsets.insert(
                 name,
                 Bounds {
+                    page: 0,
                     pos: anim_start_pos,
                     size: Vec2::new(64.0, 64.0),
                     num_frames,
@@ -328,6 +398,7 @@ impl TextureDirectory {
         let tex = OpaqueImage {
             pixels: data,
             size: Vec2::new(width, height),
+            pages: 1,
         };
         (tex, offsets)
     }
@@ -340,11 +411,65 @@ struct AtlasEntry<'a, ImageType> {
     num_frames: usize,
 }
 
+#[derive(Copy, Clone)]
 struct AtlasPosition {
+    page: usize,
     offset: Vec2<isize>,
     row_height: usize,
 }
 
+impl AtlasPosition {
+    /// Stands in for entries which could not be placed at all.
+    const SKIPPED: AtlasPosition = AtlasPosition {
+        page: usize::MAX,
+        offset: Vec2 { x: 0, y: 0 },
+        row_height: 0,
+    };
+}
+
+/// Lays out `entries` in rows on an `atlas_size` page, appending their positions. Stops before
+/// the first animation which does not fit entirely, and returns the number of entries placed.
+fn place_atlas_entries(
+    entries: &[AtlasEntry<Image>],
+    atlas_size: Vec2<usize>,
+    page: usize,
+    positions: &mut Vec<AtlasPosition>,
+) -> usize {
+    let start_len = positions.len();
+    let mut offset = Vec2::zero();
+    let mut row_height = 0;
+    let mut num_placed = 0;
+    for (i, &AtlasEntry { image, .. }) in entries.iter().enumerate() {
+        let size = image.size();
+        if offset[0] + size[0] > atlas_size[0] {
+            offset[0] = 0;
+            offset[1] += row_height;
+            row_height = 0;
+        }
+        if size[1] > row_height {
+            row_height = size[1];
+        }
+        if offset[0] + size[0] > atlas_size[0] || offset[1] + size[1] > atlas_size[1] {
+            break;
+        }
+        positions.push(AtlasPosition {
+            page,
+            offset: Vec2::new(offset[0] as isize, offset[1] as isize),
+            row_height,
+        });
+        offset[0] += size[0];
+        if entries.get(i + 1).is_none_or(|next| next.frame_offset == 0) {
+            num_placed = i + 1;
+        }
+    }
+    // Animation frames are found relative to the first one, so they must all be on the same page.
+    positions.truncate(start_len + num_placed);
+    num_placed
+}
+
+/// The largest width or height of an atlas page.
+const MAX_ATLAS_SIZE: usize = 4096;
+
 fn next_pow2(x: usize) -> usize {
     let mut pow2 = 1;
     while pow2 < x {
@@ -411,6 +536,7 @@ fn read_patches(wad: &Archive) -> Result<Vec<(WadName, Option<Image>)>> {
 
 fn img_bound(pos: &AtlasPosition, entry: &AtlasEntry<Image>) -> Bounds {
     Bounds {
+        page: pos.page,
         pos: Pnt2f::new(pos.offset[0] as f32, pos.offset[1] as f32),
         size: Vec2f::new(entry.image.width() as f32, entry.image.height() as f32),
         num_frames: entry.num_frames,
@@ -604,3 +730,88 @@ fn read_flats(wad: &Archive) -> Result<IndexMap<WadName, Flat>> {
     }
     Ok(flats)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{place_atlas_entries, AtlasEntry, SpriteRotation, TextureDirectory};
+    use crate::image::Image;
+    use crate::name::WadName;
+    use indexmap::IndexMap;
+    use math::Vec2;
+    use std::str::FromStr;
+
+    /// A directory with only the named sprite lumps in it.
+    fn sprites(names: &[&str]) -> TextureDirectory {
+        TextureDirectory {
+            textures: names
+                .iter()
+                .map(|name| (WadName::from_str(name).unwrap(), Image::new(1, 1).unwrap()))
+                .collect(),
+            patches: Vec::new(),
+            palettes: Vec::new(),
+            colormaps: Vec::new(),
+            flats: IndexMap::new(),
+            animated_walls: Vec::new(),
+            animated_flats: Vec::new(),
+        }
+    }
+
+    fn rotation(name: &str, mirrored: bool) -> SpriteRotation {
+        SpriteRotation {
+            tex_name: WadName::from_str(name).unwrap(),
+            mirrored,
+        }
+    }
+
+    #[test]
+    fn test_sprite_rotations() {
+        let troo = WadName::from_str("TROO").unwrap();
+        let textures = sprites(&[
+            "TROOA1", "TROOA2A8", "TROOA3A7", "TROOA4A6", "TROOA5", "TROOB0", "TROOC1", "TROOC2C8",
+        ]);
+        assert_eq!(
+            textures.sprite_rotations(troo, b'A'),
+            Some([
+                rotation("TROOA1", false),
+                rotation("TROOA2A8", false),
+                rotation("TROOA3A7", false),
+                rotation("TROOA4A6", false),
+                rotation("TROOA5", false),
+                rotation("TROOA4A6", true),
+                rotation("TROOA3A7", true),
+                rotation("TROOA2A8", true),
+            ])
+        );
+        // Frames with a single view, or with missing rotations, have no rotations.
+        assert_eq!(textures.sprite_rotations(troo, b'B'), None);
+        assert_eq!(textures.sprite_rotations(troo, b'C'), None);
+        assert_eq!(textures.sprite_rotations(troo, b'D'), None);
+    }
+
+    #[test]
+    fn test_place_atlas_entries_keeps_animations_on_one_page() {
+        let image = Image::new(64, 64).unwrap();
+        let entry = |name, frame_offset, num_frames| AtlasEntry {
+            name: WadName::from_str(name).unwrap(),
+            image: &image,
+            frame_offset,
+            num_frames,
+        };
+        let entries = [entry("A", 0, 1), entry("B1", 0, 2), entry("B2", 1, 2)];
+
+        let mut positions = Vec::new();
+        assert_eq!(
+            place_atlas_entries(&entries, Vec2::new(128, 64), 0, &mut positions),
+            1
+        );
+        assert_eq!(positions.len(), 1);
+
+        assert_eq!(
+            place_atlas_entries(&entries[1..], Vec2::new(128, 64), 1, &mut positions),
+            2
+        );
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[2].page, 1);
+        assert_eq!(positions[2].offset, Vec2::new(64, 0));
+    }
+}
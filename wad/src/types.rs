@@ -39,6 +39,70 @@ pub struct WadThing {
     pub flags: ThingFlags,
 }
 
+impl WadThing {
+    pub fn on_easy(&self) -> bool {
+        self.flags & 0x0001 != 0
+    }
+
+    pub fn on_medium(&self) -> bool {
+        self.flags & 0x0002 != 0
+    }
+
+    pub fn on_hard(&self) -> bool {
+        self.flags & 0x0004 != 0
+    }
+
+    pub fn deaf(&self) -> bool {
+        self.flags & 0x0008 != 0
+    }
+
+    pub fn not_in_single_player(&self) -> bool {
+        self.flags & 0x0010 != 0
+    }
+
+    pub fn spawns_in(&self, skill: Skill, mode: GameMode) -> bool {
+        if mode == GameMode::SinglePlayer && self.not_in_single_player() {
+            return false;
+        }
+        match skill {
+            Skill::Baby | Skill::Easy => self.on_easy(),
+            Skill::Medium => self.on_medium(),
+            Skill::Hard | Skill::Nightmare => self.on_hard(),
+        }
+    }
+}
+
+/// The five vanilla skill levels. The first two and last two share the same thing flags.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Skill {
+    Baby,
+    Easy,
+    Medium,
+    Hard,
+    Nightmare,
+}
+
+impl Skill {
+    /// Converts from the 1-based numbering used by vanilla's `-skill` option.
+    pub fn from_number(number: u8) -> Option<Skill> {
+        Some(match number {
+            1 => Skill::Baby,
+            2 => Skill::Easy,
+            3 => Skill::Medium,
+            4 => Skill::Hard,
+            5 => Skill::Nightmare,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    SinglePlayer,
+    Cooperative,
+    Deathmatch,
+}
+
 #[derive(Copy, Clone, Deserialize)]
 pub struct WadVertex {
     pub x: WadCoord,
@@ -137,14 +201,15 @@ pub struct WadNode {
     pub line_y: WadCoord,
     pub step_x: WadCoord,
     pub step_y: WadCoord,
+    // Each child's bounding box is stored as top, bottom, left, right.
     pub right_y_max: WadCoord,
     pub right_y_min: WadCoord,
-    pub right_x_max: WadCoord,
     pub right_x_min: WadCoord,
+    pub right_x_max: WadCoord,
     pub left_y_max: WadCoord,
     pub left_y_min: WadCoord,
-    pub left_x_max: WadCoord,
     pub left_x_min: WadCoord,
+    pub left_x_max: WadCoord,
     pub right: ChildId,
     pub left: ChildId,
 }
@@ -1,12 +1,12 @@
-use super::level::{Level, NeighbourHeights};
+use super::level::{partition_line, Level, NeighbourHeights, SEG_TOLERANCE};
 use super::light::{self, Contrast, LightInfo};
 use super::meta::{
     ExitEffectDef, HeightDef, HeightEffectDef, HeightRef, MoveEffectDef, TriggerType, WadMetadata,
 };
 use super::tex::TextureDirectory;
 use super::types::{
-    ChildId, SectorId, SpecialType, ThingType, WadCoord, WadLinedef, WadName, WadNode, WadSector,
-    WadSeg, WadThing,
+    ChildId, GameMode, SectorId, Skill, SpecialType, ThingType, WadCoord, WadLinedef, WadName,
+    WadNode, WadSector, WadSeg, WadThing,
 };
 use super::util::{
     from_wad_coords, from_wad_height, is_sky_flat, is_untextured, parse_child_id, to_wad_height,
@@ -14,7 +14,7 @@ use super::util::{
 use indexmap::IndexMap;
 use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::{Deg, Line2f, Pnt2f, Pnt3f, Radf, Vec2f};
+use math::{Aabb3, Deg, Line2f, Pnt2f, Pnt3f, Radf, Vec2, Vec2f};
 use std::cmp;
 use std::cmp::Ordering;
 use std::f32::EPSILON;
@@ -31,6 +31,8 @@ pub struct StaticQuad<'a> {
     pub scroll: f32,
     pub tex_name: Option<WadName>,
     pub blocker: bool,
+    /// The index of the wall's linedef.
+    pub linedef: usize,
 }
 
 pub struct StaticPoly<'a> {
@@ -55,11 +57,28 @@ pub struct SkyPoly<'a> {
 
 pub struct Decor<'a> {
     pub object_id: ObjectId,
+    pub thing_type: ThingType,
     pub low: Pnt3f,
     pub high: Pnt3f,
     pub half_width: f32,
+    pub yaw: Radf,
+    pub radius: f32,
+    pub obstacle: bool,
+    pub fullbright: bool,
     pub light_info: &'a LightInfo,
     pub tex_name: WadName,
+
+    // Empty for sprites which look the same from all angles, otherwise the eight views of the thing,
+    // starting with the one facing the viewer and going counter-clockwise (as seen from above).
+    pub rotations: &'a [DecorRotation],
+}
+
+pub struct DecorRotation {
+    pub low: Pnt3f,
+    pub high: Pnt3f,
+    pub half_width: f32,
+    pub tex_name: WadName,
+    pub mirrored: bool,
 }
 
 pub trait LevelVisitor: Sized {
@@ -95,15 +114,15 @@ pub trait LevelVisitor: Sized {
         // Default impl is empty to allow visitors to mix and match.
     }
 
-    fn visit_bsp_root(&mut self, _line: &Line2f) {
+    fn visit_bsp_root(&mut self, _line: &Line2f, _bounds: &Aabb3) {
         // Default impl is empty to allow visitors to mix and match.
     }
 
-    fn visit_bsp_node(&mut self, _line: &Line2f, _branch: Branch) {
+    fn visit_bsp_node(&mut self, _line: &Line2f, _branch: Branch, _bounds: &Aabb3) {
         // Default impl is empty to allow visitors to mix and match.
     }
 
-    fn visit_bsp_leaf(&mut self, _branch: Branch) {
+    fn visit_bsp_leaf(&mut self, _branch: Branch, _bounds: &Aabb3) {
         // Default impl is empty to allow visitors to mix and match.
     }
 
@@ -304,6 +323,8 @@ impl HeightEffectDef {
 pub struct Trigger {
     pub trigger_type: TriggerType,
     pub line: Line2f,
+    /// The index of the linedef the trigger was made from.
+    pub linedef: usize,
     pub special_type: SpecialType,
     pub only_once: bool,
 
@@ -334,6 +355,10 @@ impl LevelAnalysis {
         self.num_objects
     }
 
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
     pub fn take_triggers(&mut self) -> Vec<Trigger> {
         mem::take(&mut self.triggers)
     }
@@ -374,11 +399,12 @@ impl LevelAnalysis {
 
         let mut next_dynamic_object_id = ObjectId(1);
         for (i_linedef, linedef) in level.linedefs.iter().enumerate() {
-            let mut trigger = if let Some(trigger) = self.linedef_to_trigger(level, meta, linedef) {
-                trigger
-            } else {
-                continue;
-            };
+            let mut trigger =
+                if let Some(trigger) = self.linedef_to_trigger(level, meta, i_linedef, linedef) {
+                    trigger
+                } else {
+                    continue;
+                };
             num_dynamic_linedefs += 1;
 
             let tag = linedef.sector_tag;
@@ -447,6 +473,7 @@ impl LevelAnalysis {
         &self,
         level: &Level,
         meta: &WadMetadata,
+        i_linedef: usize,
         linedef: &WadLinedef,
     ) -> Option<Trigger> {
         let special_type = linedef.special_type;
@@ -476,6 +503,7 @@ impl LevelAnalysis {
                 special_type,
 
                 line,
+                linedef: i_linedef,
                 move_effects: Vec::new(),
             }
         } else {
@@ -490,6 +518,7 @@ impl LevelAnalysis {
                 special_type,
 
                 line,
+                linedef: i_linedef,
                 move_effects: Vec::new(),
             }
         })
@@ -501,6 +530,8 @@ pub struct LevelWalker<'a, V: LevelVisitor + 'a> {
     tex: &'a TextureDirectory,
     meta: &'a WadMetadata,
     visitor: &'a mut V,
+    skill: Skill,
+    mode: GameMode,
     height_range: (WadCoord, WadCoord),
     bsp_lines: Vec<Line2f>,
 
@@ -528,6 +559,8 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             tex,
             meta,
             visitor,
+            skill: Skill::Medium,
+            mode: GameMode::SinglePlayer,
             height_range: min_max_height(level),
             bsp_lines: Vec::with_capacity(32),
             subsector_points: Vec::with_capacity(32),
@@ -538,6 +571,14 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
         }
     }
 
+    /// Only visit the things which spawn in the given skill level & game mode (defaults to medium
+    /// skill, single player). Markers like player starts are always visited.
+    pub fn with_skill(mut self, skill: Skill, mode: GameMode) -> Self {
+        self.skill = skill;
+        self.mode = mode;
+        self
+    }
+
     pub fn walk(&mut self) {
         let root = match self.level.nodes.last() {
             Some(node) => node,
@@ -547,7 +588,12 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             }
         };
         let partition = partition_line(root);
-        self.visitor.visit_bsp_root(&partition);
+        let bounds = child_bounds(root, Branch::Positive, self.height_range).union(&child_bounds(
+            root,
+            Branch::Negative,
+            self.height_range,
+        ));
+        self.visitor.visit_bsp_root(&partition, &bounds);
         self.children(root, partition);
         self.visitor.visit_bsp_node_end();
 
@@ -587,10 +633,10 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             )
     }
 
-    fn node(&mut self, id: ChildId, branch: Branch) {
+    fn node(&mut self, id: ChildId, branch: Branch, bounds: Aabb3) {
         let (id, is_leaf) = parse_child_id(id);
         if is_leaf {
-            self.visitor.visit_bsp_leaf(branch);
+            self.visitor.visit_bsp_leaf(branch, &bounds);
             self.subsector(id);
             self.visitor.visit_bsp_leaf_end();
             return;
@@ -603,18 +649,20 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             return;
         };
         let partition = partition_line(node);
-        self.visitor.visit_bsp_node(&partition, branch);
+        self.visitor.visit_bsp_node(&partition, branch, &bounds);
         self.children(node, partition);
         self.visitor.visit_bsp_node_end();
     }
 
     fn children(&mut self, node: &WadNode, partition: Line2f) {
         self.bsp_lines.push(partition);
-        self.node(node.left, Branch::Positive);
+        let bounds = child_bounds(node, Branch::Positive, self.height_range);
+        self.node(node.left, Branch::Positive, bounds);
         self.bsp_lines.pop();
 
         self.bsp_lines.push(partition.inverted_halfspaces());
-        self.node(node.right, Branch::Negative);
+        let bounds = child_bounds(node, Branch::Negative, self.height_range);
+        self.node(node.right, Branch::Negative, bounds);
         self.bsp_lines.pop();
     }
 
@@ -933,6 +981,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             blocker,
             scroll,
             object_id,
+            linedef: seg.linedef as usize,
         });
     }
 
@@ -1011,7 +1060,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
         for thing in &self.level.things {
             let pos = from_wad_coords(thing.x, thing.y);
             let yaw = Deg(f32::round(f32::from(thing.angle) / 45.0) * 45.0);
-            let sector = match self.sector_at(pos) {
+            let sector = match self.level.sector_at(pos) {
                 Some(sector) => sector,
                 None => continue,
             };
@@ -1019,47 +1068,15 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             if let Some(marker) = Marker::from(thing.thing_type) {
                 let pos = Pnt3f::new(pos[0], from_wad_height(sector.floor_height), pos[1]);
                 self.visitor.visit_marker(pos, yaw.into(), marker);
-            } else if let Some(sector) = self.sector_at(pos) {
-                self.decor(thing, pos, sector);
-            }
-        }
-    }
-
-    fn sector_at(&self, pos: Pnt2f) -> Option<&'a WadSector> {
-        let mut child_id = (self.level.nodes.len() - 1) as ChildId;
-        loop {
-            let (id, is_leaf) = parse_child_id(child_id);
-            if is_leaf {
-                let segs = self
-                    .level
-                    .ssector(id)
-                    .and_then(|subsector| self.level.ssector_segs(subsector))
-                    .and_then(|segs| if segs.is_empty() { None } else { Some(segs) });
-                let segs = segs?;
-                let sector = self.level.seg_sector(&segs[0])?;
-                return if segs
-                    .iter()
-                    .filter_map(|seg| self.level.seg_vertices(seg))
-                    .map(|(v1, v2)| Line2f::from_two_points(v1, v2))
-                    .all(|line| line.signed_distance(pos) <= SEG_TOLERANCE)
-                {
-                    Some(sector)
-                } else {
-                    None
-                };
-            } else {
-                let node = self.level.nodes.get(id)?;
-                let partition = partition_line(node);
-                if partition.signed_distance(pos) > 0.0f32 {
-                    child_id = node.left;
-                } else {
-                    child_id = node.right;
-                }
+            } else if !thing.spawns_in(self.skill, self.mode) {
+                continue;
+            } else if let Some(sector) = self.level.sector_at(pos) {
+                self.decor(thing, pos, yaw.into(), sector);
             }
         }
     }
 
-    fn decor(&mut self, thing: &WadThing, pos: Pnt2f, sector: &WadSector) {
+    fn decor(&mut self, thing: &WadThing, pos: Pnt2f, yaw: Radf, sector: &WadSector) {
         let meta = match self.meta.find_thing(thing.thing_type) {
             Some(m) => m,
             None => {
@@ -1067,10 +1084,12 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                 return;
             }
         };
+        let frame = meta.sequence.as_bytes()[0];
+        let rotations = self.tex.sprite_rotations(meta.sprite, frame);
         let (name, size) = {
             let mut sprite0 = meta.sprite;
             // Ignore the error: if this fails, so will the `sprite0` and `sprite1` pushes below.
-            let _ = sprite0.push(meta.sequence.as_bytes()[0]);
+            let _ = sprite0.push(frame);
             let mut sprite1 = sprite0;
             let sprite0 = sprite0.push(b'0').ok().map(|_| sprite0);
             let sprite1 = sprite1.push(b'1').ok().map(|_| sprite1);
@@ -1081,6 +1100,11 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                         (sprite0, image.size())
                     } else if let Some(image) = self.tex.texture(sprite1) {
                         (sprite1, image.size())
+                    } else if let Some(front) = rotations.map(|rotations| rotations[0].tex_name) {
+                        match self.tex.texture(front) {
+                            Some(image) => (front, image.size()),
+                            None => return,
+                        }
                     } else {
                         warn!(
                             "No such sprite {} for thing {}",
@@ -1098,41 +1122,68 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                 }
             }
         };
-        let size = Vec2f::new(
-            from_wad_height(size[0] as i16),
-            from_wad_height(size[1] as i16),
-        );
 
-        let (object_id, low, high) = if meta.hanging {
-            (
-                self.ceiling_id(sector),
-                Pnt3f::new(
-                    pos[0],
-                    from_wad_height(sector.ceiling_height) - size[1],
-                    pos[1],
-                ),
-                Pnt3f::new(pos[0], from_wad_height(sector.ceiling_height), pos[1]),
-            )
+        let placement = |size: Vec2<usize>| {
+            let size = Vec2f::new(
+                from_wad_height(size[0] as i16),
+                from_wad_height(size[1] as i16),
+            );
+            let (low, high) = if meta.hanging {
+                (
+                    Pnt3f::new(
+                        pos[0],
+                        from_wad_height(sector.ceiling_height) - size[1],
+                        pos[1],
+                    ),
+                    Pnt3f::new(pos[0], from_wad_height(sector.ceiling_height), pos[1]),
+                )
+            } else {
+                (
+                    Pnt3f::new(pos[0], from_wad_height(sector.floor_height), pos[1]),
+                    Pnt3f::new(
+                        pos[0],
+                        from_wad_height(sector.floor_height) + size[1],
+                        pos[1],
+                    ),
+                )
+            };
+            (low, high, size[0] * 0.5)
+        };
+        let object_id = if meta.hanging {
+            self.ceiling_id(sector)
         } else {
-            (
-                self.floor_id(sector),
-                Pnt3f::new(pos[0], from_wad_height(sector.floor_height), pos[1]),
-                Pnt3f::new(
-                    pos[0],
-                    from_wad_height(sector.floor_height) + size[1],
-                    pos[1],
-                ),
-            )
+            self.floor_id(sector)
         };
-        let half_width = size[0] * 0.5;
+        let (low, high, half_width) = placement(size);
+        let rotations = rotations
+            .iter()
+            .flatten()
+            .filter_map(|rotation| {
+                let (low, high, half_width) =
+                    placement(self.tex.texture(rotation.tex_name)?.size());
+                Some(DecorRotation {
+                    low,
+                    high,
+                    half_width,
+                    tex_name: rotation.tex_name,
+                    mirrored: rotation.mirrored,
+                })
+            })
+            .collect::<Vec<_>>();
 
         self.visitor.visit_decor(&Decor {
             object_id,
+            thing_type: thing.thing_type,
             low,
             high,
             half_width,
+            yaw,
+            radius: from_wad_height(meta.radius as WadCoord),
+            obstacle: meta.obstacle,
+            fullbright: meta.is_fullbright(frame),
             light_info: light_info(&mut self.light_cache, self.level, sector),
             tex_name: name,
+            rotations: &rotations,
         });
     }
 }
@@ -1147,16 +1198,11 @@ fn light_info<'a>(
         .or_insert_with(|| light::new_light(level, sector))
 }
 
-fn partition_line(node: &WadNode) -> Line2f {
-    Line2f::from_two_points(
-        from_wad_coords(node.line_x, node.line_y),
-        from_wad_coords(node.line_x + node.step_x, node.line_y + node.step_y),
-    )
-}
-
 // Distance on the wrong side of a BSP and seg line allowed.
 const BSP_TOLERANCE: f32 = 1e-3;
-const SEG_TOLERANCE: f32 = 0.1;
+
+// BSP node bounding boxes are padded by this amount, to account for polygon fattening and rounding.
+const BSP_BOUNDS_PADDING: f32 = 0.05;
 
 // All polygons are `fattened' by this amount to fill in thin gaps between them.
 const POLY_BIAS: f32 = 0.64 * 3e-4;
@@ -1170,6 +1216,32 @@ enum Peg {
     BottomFloat,
 }
 
+// The bounding box of one of the node's children, spanning `height_range`.
+fn child_bounds(node: &WadNode, branch: Branch, (low, high): (WadCoord, WadCoord)) -> Aabb3 {
+    let (x_min, x_max, y_min, y_max) = match branch {
+        Branch::Positive => (
+            node.left_x_min,
+            node.left_x_max,
+            node.left_y_min,
+            node.left_y_max,
+        ),
+        Branch::Negative => (
+            node.right_x_min,
+            node.right_x_max,
+            node.right_y_min,
+            node.right_y_max,
+        ),
+    };
+    // World x and z are the negated WAD y and x, so the WAD maximum is the world minimum.
+    let min = from_wad_coords(x_max, y_max);
+    let max = from_wad_coords(x_min, y_min);
+    Aabb3::new(
+        Pnt3f::new(min[0], from_wad_height(low), min[1]),
+        Pnt3f::new(max[0], from_wad_height(high), max[1]),
+    )
+    .inflate(BSP_BOUNDS_PADDING)
+}
+
 fn min_max_height(level: &Level) -> (WadCoord, WadCoord) {
     let (min, max) = level
         .sectors
@@ -1304,19 +1376,19 @@ impl<'a, 'b, A: LevelVisitor, B: LevelVisitor> LevelVisitor for VisitorChain<'a,
         self.second.visit_decor(decor);
     }
 
-    fn visit_bsp_root(&mut self, line: &Line2f) {
-        self.first.visit_bsp_root(line);
-        self.second.visit_bsp_root(line);
+    fn visit_bsp_root(&mut self, line: &Line2f, bounds: &Aabb3) {
+        self.first.visit_bsp_root(line, bounds);
+        self.second.visit_bsp_root(line, bounds);
     }
 
-    fn visit_bsp_node(&mut self, line: &Line2f, branch: Branch) {
-        self.first.visit_bsp_node(line, branch);
-        self.second.visit_bsp_node(line, branch);
+    fn visit_bsp_node(&mut self, line: &Line2f, branch: Branch, bounds: &Aabb3) {
+        self.first.visit_bsp_node(line, branch, bounds);
+        self.second.visit_bsp_node(line, branch, bounds);
     }
 
-    fn visit_bsp_leaf(&mut self, branch: Branch) {
-        self.first.visit_bsp_leaf(branch);
-        self.second.visit_bsp_leaf(branch);
+    fn visit_bsp_leaf(&mut self, branch: Branch, bounds: &Aabb3) {
+        self.first.visit_bsp_leaf(branch, bounds);
+        self.second.visit_bsp_leaf(branch, bounds);
     }
 
     fn visit_bsp_leaf_end(&mut self) {
@@ -1362,3 +1434,47 @@ impl Marker {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{child_bounds, Branch};
+    use crate::types::WadNode;
+
+    #[test]
+    fn test_child_bounds() {
+        let node = WadNode {
+            line_x: 0,
+            line_y: 0,
+            step_x: 0,
+            step_y: 64,
+            right_y_max: 32,
+            right_y_min: -32,
+            right_x_min: 0,
+            right_x_max: 64,
+            left_y_max: 256,
+            left_y_min: 0,
+            left_x_min: -64,
+            left_x_max: 128,
+            right: 0x8000,
+            left: 0x8001,
+        };
+        // World x is the negated WAD y, and world z the negated WAD x.
+        let left = child_bounds(&node, Branch::Positive, (0, 128)).inflate(-0.05);
+        assert_eq!(
+            [left.min.x, left.min.y, left.min.z, left.max.x, left.max.y, left.max.z],
+            [-2.56, 0.0, -1.28, 0.0, 1.28, 0.64]
+        );
+        let right = child_bounds(&node, Branch::Negative, (-16, 64)).inflate(-0.05);
+        assert_eq!(
+            [
+                right.min.x,
+                right.min.y,
+                right.min.z,
+                right.max.x,
+                right.max.y,
+                right.max.z
+            ],
+            [-0.32, -0.16, -0.64, 0.32, 0.64, 0.0]
+        );
+    }
+}